@@ -3,6 +3,8 @@ pub mod collapsible;
 pub mod form;
 pub mod header;
 pub mod legend;
+pub mod live;
+pub mod pager;
 pub mod player;
 
 pub use chart::*;
@@ -10,4 +12,6 @@ pub use collapsible::*;
 pub use form::*;
 pub use header::*;
 pub use legend::*;
+pub use live::*;
+pub use pager::*;
 pub use player::*;
@@ -0,0 +1,39 @@
+use leptos::prelude::*;
+use types::{api::LiveEvent, leptos::LiveEvents};
+use wasm_bindgen::{JsCast, prelude::Closure};
+use web_sys::{EventSource, MessageEvent};
+
+/// Subscribes to `/api/live` once for the whole app and provides the latest event as
+/// [`LiveEvents`]. The browser reconnects the stream by itself when it drops.
+pub fn provide_live_events() {
+    let (event, set_event) = signal::<Option<LiveEvent>>(None);
+    // Effects only run in the browser
+    Effect::new(move |_| {
+        let Ok(source) = EventSource::new("/api/live") else {
+            return;
+        };
+        let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |message: MessageEvent| {
+            if let Some(event) = message.data().as_string().and_then(|d| serde_json::from_str(&d).ok()) {
+                set_event(Some(event));
+            }
+        });
+        source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        // Lives as long as the page
+        on_message.forget();
+    });
+    provide_context::<LiveEvents>(event);
+}
+
+/// Calls `on_event` with every live event while the calling component is mounted.
+pub fn on_live_event(on_event: impl Fn(&LiveEvent) + 'static) {
+    let events = expect_context::<LiveEvents>();
+    Effect::watch(
+        move || events.get(),
+        move |event, _, _| {
+            if let Some(event) = event {
+                on_event(event);
+            }
+        },
+        false,
+    );
+}
@@ -0,0 +1,63 @@
+use leptos::prelude::*;
+use leptos_router::{components::A, hooks::use_query_map};
+
+/// Arrows between the pages of a list, which is fetched with the `cursor` query parameter.
+/// `page` only counts the pages for display and `total` items make up the whole list.
+#[component]
+pub fn Pager(
+    #[prop(into)] next: Signal<Option<String>>,
+    #[prop(into)] prev: Signal<Option<String>>,
+    #[prop(into)] total: Signal<Option<i64>>,
+    #[prop(into)] limit: Signal<i64>,
+) -> impl IntoView {
+    let params = use_query_map();
+    let page = Signal::derive(move || {
+        params
+            .read()
+            .get("page")
+            .and_then(|p| p.parse::<i64>().ok())
+            .unwrap_or(0)
+            .max(0)
+    });
+    let href = move |page: i64, cursor: Option<String>| {
+        let mut map = params.get();
+        match cursor.filter(|_| page > 0) {
+            Some(cursor) => {
+                map.replace("page", page.to_string());
+                map.replace("cursor", cursor);
+            }
+            // Without a cursor the list starts over, the first page always can
+            None => {
+                map.remove("page");
+                map.remove("cursor");
+            }
+        }
+        map.to_query_string()
+    };
+
+    view! {
+        <div class="pages row">
+            <Show when=move || page.get() != 0 fallback=|| view! { <div class="arrow disabled">"<"</div> }>
+                <A class:arrow=true href=move || href(page.get() - 1, prev.get())>
+                    "<"
+                </A>
+            </Show>
+            <div class="page">
+                {move || match total.get() {
+                    Some(total) => format!("{} / {}", page.get() + 1, ((total + limit.get() - 1) / limit.get()).max(1)),
+                    None => (page.get() + 1).to_string(),
+                }}
+            </div>
+            <Suspense fallback=|| view! { <div class="arrow disabled">">"</div> }>
+                <Show
+                    when=move || next.read().is_some()
+                    fallback=|| view! { <div class="arrow disabled">">"</div> }
+                >
+                    <A class:arrow=true href=move || href(page.get() + 1, next.get())>
+                        ">"
+                    </A>
+                </Show>
+            </Suspense>
+        </div>
+    }
+}
@@ -1,5 +1,21 @@
 use leptos::prelude::*;
-use types::internal::Proof;
+use types::{api::ProofHost, internal::Proof};
+
+/// Embeddable player for the proof, `None` if the host can't be embedded.
+fn embed_url(proof: &Proof) -> Option<String> {
+    let id = proof.yt_id.as_ref()?;
+    match proof.host {
+        ProofHost::YouTube => Some(format!(
+            "https://www.youtube-nocookie.com/embed/{id}?autoplay=1&rel=0&modestbranding=1&showinfo=0"
+        )),
+        ProofHost::Twitch => Some(format!(
+            "https://clips.twitch.tv/embed?clip={id}&parent={}&autoplay=true",
+            window().location().hostname().unwrap_or_default()
+        )),
+        ProofHost::Streamable => Some(format!("https://streamable.com/e/{id}?autoplay=1")),
+        ProofHost::Other => None,
+    }
+}
 
 #[component]
 pub fn Player(proof: Signal<Option<Proof>>, cover: String) -> impl IntoView {
@@ -20,7 +36,9 @@ pub fn Player(proof: Signal<Option<Proof>>, cover: String) -> impl IntoView {
                         <Show when=move || proof.read().is_some()>
                             <div class="buttons">
                                 <Show
-                                    when=move || proof.get().unwrap().yt_id.is_some()
+                                    when=move || {
+                                        proof.get().is_some_and(|p| p.yt_id.is_some() && p.host != ProofHost::Other)
+                                    }
                                     fallback=move || {
                                         view! {
                                             <a
@@ -59,13 +77,7 @@ pub fn Player(proof: Signal<Option<Proof>>, cover: String) -> impl IntoView {
                     }
                 }
             >
-                <iframe
-                    src=format!(
-                        "https://www.youtube-nocookie.com/embed/{}?autoplay=1&rel=0&modestbranding=1&showinfo=0",
-                        proof.get().unwrap().yt_id.unwrap(),
-                    )
-                    allowfullscreen
-                ></iframe>
+                <iframe src=move || proof.get().as_ref().and_then(embed_url) allowfullscreen></iframe>
             </Show>
         </div>
     }
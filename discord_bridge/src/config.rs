@@ -0,0 +1,269 @@
+//! Which webhooks an event is posted to. Routes are read from the TOML file at
+//! `WEBHOOK_CONFIG`, `webhooks.toml` by default:
+//!
+//! ```toml
+//! # Every WR in Standard, on two servers
+//! [[route]]
+//! event = "wr"
+//! category = "Standard"
+//! webhooks = ["https://discord.com/api/webhooks/1/a", "https://discord.com/api/webhooks/2/b"]
+//!
+//! # Combined titles of Mythic Surfer and up
+//! [[route]]
+//! event = "title"
+//! min_title = "MythicSurfer"
+//! webhooks = ["https://discord.com/api/webhooks/3/c"]
+//! ```
+//!
+//! Without the file, `PB_WEBHOOK`, `WR_WEBHOOK` and `ACTIVITY_WEBHOOK` route every event of
+//! their kind as before.
+
+use std::{env, fs, io::ErrorKind};
+
+use anyhow::{Context, Result, bail};
+use reqwest::Url;
+use serde::Deserialize;
+use strum::{Display, EnumIter};
+use types::api::{Activity, Run, Title};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Display, EnumIter)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Event {
+    Pb,
+    Wr,
+    Title,
+    Rank,
+    Join,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    pub event: Event,
+    pub webhooks: Vec<String>,
+    pub patch: Option<String>,
+    pub layout: Option<String>,
+    pub category: Option<String>,
+    /// Only title changes to at least this title.
+    pub min_title: Option<Title>,
+    /// Only rank digests with a change to this rank or better.
+    pub max_rank: Option<i32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, rename = "route")]
+    pub routes: Vec<Route>,
+}
+
+/// What an event is about, matched against the filters of the routes.
+#[derive(Debug, Default)]
+pub struct Subject<'a> {
+    pub patch: Option<&'a str>,
+    pub layout: Option<&'a str>,
+    pub category: Option<&'a str>,
+    pub title: Option<&'a Title>,
+    pub rank: Option<i32>,
+}
+
+impl<'a> From<&'a Run> for Subject<'a> {
+    fn from(run: &'a Run) -> Self {
+        Self {
+            patch: Some(&run.patch),
+            layout: Some(&run.layout),
+            category: Some(&run.category),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a> From<&'a Activity> for Subject<'a> {
+    fn from(activity: &'a Activity) -> Self {
+        Self {
+            patch: activity.patch.as_deref(),
+            layout: activity.layout.as_deref(),
+            category: activity.category.as_deref(),
+            title: activity.title_new.as_ref(),
+            rank: activity.rank_new,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the routes from `WEBHOOK_CONFIG` or the webhook variables, failing on anything
+    /// that would only come up once an event is sent.
+    pub fn load() -> Result<Self> {
+        let path = env::var("WEBHOOK_CONFIG").ok();
+        let config = match fs::read_to_string(path.as_deref().unwrap_or("webhooks.toml")) {
+            Ok(file) => Self::parse(&file)?,
+            // Only a path that was asked for has to exist
+            Err(e) if e.kind() == ErrorKind::NotFound && path.is_none() => Self::from_env(),
+            Err(e) => return Err(e).context("Failed to read WEBHOOK_CONFIG"),
+        };
+        if config.routes.is_empty() {
+            bail!("No webhooks configured, add routes or set PB_WEBHOOK, WR_WEBHOOK or ACTIVITY_WEBHOOK");
+        }
+        Ok(config)
+    }
+
+    pub fn parse(file: &str) -> Result<Self> {
+        let config = toml::from_str::<Self>(file).context("Invalid webhook config")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_env() -> Self {
+        let route = |event, var| {
+            env::var(var).ok().map(|url| Route {
+                event,
+                webhooks: vec![url],
+                patch: None,
+                layout: None,
+                category: None,
+                min_title: None,
+                max_rank: None,
+            })
+        };
+        Self {
+            routes: [
+                route(Event::Pb, "PB_WEBHOOK"),
+                route(Event::Wr, "WR_WEBHOOK"),
+                route(Event::Title, "ACTIVITY_WEBHOOK"),
+                route(Event::Rank, "ACTIVITY_WEBHOOK"),
+                route(Event::Join, "ACTIVITY_WEBHOOK"),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (i, route) in self.routes.iter().enumerate() {
+            let route_no = i + 1;
+            if route.webhooks.is_empty() {
+                bail!("Route {route_no} has no webhooks");
+            }
+            for webhook in &route.webhooks {
+                let url = Url::parse(webhook).with_context(|| format!("Route {route_no} has an invalid webhook"))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    bail!("Route {route_no} has a webhook that isn't http");
+                }
+            }
+            if route.min_title.is_some() && route.event != Event::Title {
+                bail!("Route {route_no} filters by title, which only title events have");
+            }
+            if route.max_rank.is_some() && route.event != Event::Rank {
+                bail!("Route {route_no} filters by rank, which only rank events have");
+            }
+            let filtered = route.patch.is_some() || route.layout.is_some() || route.category.is_some();
+            if filtered && route.event == Event::Join {
+                bail!("Route {route_no} filters joins by leaderboard, which they don't have");
+            }
+        }
+        Ok(())
+    }
+
+    /// The webhooks `event` goes to, each once.
+    pub fn webhooks(&self, event: Event, subject: &Subject) -> Vec<&str> {
+        let mut webhooks: Vec<&str> = vec![];
+        for route in self.routes.iter().filter(|r| r.event == event && r.matches(subject)) {
+            for webhook in &route.webhooks {
+                if !webhooks.contains(&webhook.as_str()) {
+                    webhooks.push(webhook);
+                }
+            }
+        }
+        webhooks
+    }
+}
+
+impl Route {
+    fn matches(&self, subject: &Subject) -> bool {
+        let field = |filter: &Option<String>, value: Option<&str>| filter.is_none() || filter.as_deref() == value;
+        field(&self.patch, subject.patch)
+            && field(&self.layout, subject.layout)
+            && field(&self.category, subject.category)
+            && self
+                .min_title
+                .as_ref()
+                .is_none_or(|min| subject.title.is_some_and(|t| t >= min))
+            && self.max_rank.is_none_or(|max| subject.rank.is_some_and(|r| r <= max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [[route]]
+        event = "wr"
+        category = "Standard"
+        webhooks = ["https://example.com/standard", "https://example.com/all"]
+
+        [[route]]
+        event = "wr"
+        webhooks = ["https://example.com/all"]
+
+        [[route]]
+        event = "title"
+        min_title = "MythicSurfer"
+        webhooks = ["https://example.com/titles"]
+    "#;
+
+    #[test]
+    fn routes_by_leaderboard_and_threshold() {
+        let config = Config::parse(CONFIG).unwrap();
+        let standard = Subject {
+            patch: Some("2.13"),
+            layout: Some("1"),
+            category: Some("Standard"),
+            ..Default::default()
+        };
+        let nostalgia = Subject {
+            category: Some("Nostalgia"),
+            ..standard
+        };
+        assert_eq!(
+            config.webhooks(Event::Wr, &standard),
+            vec!["https://example.com/standard", "https://example.com/all"]
+        );
+        assert_eq!(config.webhooks(Event::Wr, &nostalgia), vec!["https://example.com/all"]);
+        assert!(config.webhooks(Event::Pb, &nostalgia).is_empty());
+
+        let title = |title| Subject {
+            title: Some(title),
+            ..Default::default()
+        };
+        assert!(config.webhooks(Event::Title, &title(&Title::EpicSurfer)).is_empty());
+        assert_eq!(config.webhooks(Event::Title, &title(&Title::TopOne)).len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_routes() {
+        for config in [
+            r#"[[route]]
+            event = "record"
+            webhooks = ["https://example.com"]"#,
+            r#"[[route]]
+            event = "wr"
+            webhooks = []"#,
+            r#"[[route]]
+            event = "wr"
+            webhooks = ["not a url"]"#,
+            r#"[[route]]
+            event = "wr"
+            min_title = "MythicSurfer"
+            webhooks = ["https://example.com"]"#,
+            r#"[[route]]
+            event = "join"
+            patch = "2.13"
+            webhooks = ["https://example.com"]"#,
+        ] {
+            assert!(Config::parse(config).is_err(), "{config}");
+        }
+    }
+}
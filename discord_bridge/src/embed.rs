@@ -0,0 +1,150 @@
+//! Typed bodies of webhook requests. Text is cut to Discord's limits here, so a long name
+//! or digest gets shortened instead of the whole message being rejected.
+
+use serde::Serialize;
+
+const TITLE: usize = 256;
+const DESCRIPTION: usize = 4096;
+const FIELD_NAME: usize = 256;
+const FIELD_VALUE: usize = 1024;
+const FIELDS: usize = 25;
+const FOOTER: usize = 2048;
+const CONTENT: usize = 2000;
+
+#[derive(Debug, Default, Serialize)]
+pub struct WebhookMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<Embed>,
+}
+
+impl WebhookMessage {
+    pub fn content(content: &str) -> Self {
+        Self {
+            content: Some(cut(content, CONTENT)),
+            ..Default::default()
+        }
+    }
+
+    pub fn embed(embed: Embed) -> Self {
+        Self {
+            embeds: vec![embed],
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Embed {
+    pub color: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<Image>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Field>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer: Option<Footer>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Image {
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub value: String,
+    pub inline: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Footer {
+    pub text: String,
+}
+
+impl Embed {
+    pub fn new(color: u32) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(cut(title, TITLE));
+        self
+    }
+
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    pub fn thumbnail(mut self, url: &str) -> Self {
+        self.thumbnail = Some(Image { url: url.into() });
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(cut(description, DESCRIPTION));
+        self
+    }
+
+    /// Adds a field, up to Discord's 25. Discord rejects empty names and values, they are
+    /// sent as a zero width space instead.
+    pub fn field(mut self, name: &str, value: &str, inline: bool) -> Self {
+        if self.fields.len() < FIELDS {
+            self.fields.push(Field {
+                name: cut(or_blank(name), FIELD_NAME),
+                value: cut(or_blank(value), FIELD_VALUE),
+                inline,
+            });
+        }
+        self
+    }
+
+    pub fn footer(mut self, text: &str) -> Self {
+        self.footer = Some(Footer {
+            text: cut(text, FOOTER),
+        });
+        self
+    }
+}
+
+fn or_blank(text: &str) -> &str {
+    if text.is_empty() { "\u{200b}" } else { text }
+}
+
+/// `text` with at most `max` characters, ending in an ellipsis when it was cut.
+fn cut(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some(_) => {
+            let end = text.char_indices().nth(max - 1).map_or(text.len(), |(i, _)| i);
+            format!("{}…", &text[..end])
+        }
+        None => text.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_to_limits() {
+        assert_eq!(cut("abc", 3), "abc");
+        assert_eq!(cut("abcd", 3), "ab…");
+        assert_eq!(cut("äöüß", 2), "ä…");
+
+        let embed = (0..30).fold(Embed::new(0), |e, i| e.field(&i.to_string(), "", true));
+        assert_eq!(embed.fields.len(), FIELDS);
+        assert_eq!(embed.fields[0].value, "\u{200b}");
+    }
+}
@@ -1,20 +1,28 @@
-#![feature(future_join)]
-use std::future::join;
+mod config;
+mod embed;
+mod outbox;
+mod templates;
 
+use anyhow::{Result, anyhow};
 use chrono::{DateTime, Local, TimeDelta};
-use log::debug;
-use reqwest::Client;
+use reqwest::{Client, Method};
 use serde_json::json;
-use sqlx::postgres::{PgConnectOptions, PgListener};
+use server::auth::ssr::{connect_to_database, current_patch, verify_database};
 use sqlx::prelude::FromRow;
-use sqlx::{query, query_as, PgPool};
+use sqlx::{query, query_as, query_scalar, PgPool};
 use types::{api::*, internal::ssr::AuthRes};
-use urlencoding::encode;
+
+use config::{Config, Event, Subject};
+use embed::WebhookMessage;
+use outbox::{Entry, Message};
+use templates::Templates;
+
+/// Rank changes within this many ranks make it into the digest.
+const DIGEST_RANKS: i32 = 10;
 
 #[derive(Clone, FromRow)]
 struct Discord {
     id: i32,
-    user_id: i64,
     access: String,
     refresh: String,
     expires_at: DateTime<Local>,
@@ -24,335 +32,228 @@ struct Discord {
 async fn main() {
     simple_logger::init_with_env().expect("couldn't initialize logging");
 
-    let submit_client = Client::new();
-    let activity_client = submit_client.clone();
-    let discord_client = submit_client.clone();
-
-    let connect_opts = PgConnectOptions::new()
-        .database(&std::env::var("PG_DB").unwrap())
-        .username(&std::env::var("PG_USER").unwrap())
-        .password(&std::env::var("PG_PASS").unwrap())
-        .host(&std::env::var("PG_HOST").unwrap())
-        .port(std::env::var("PG_PORT").unwrap().parse::<u16>().unwrap());
-
-    let submit_pool = PgPool::connect_with(connect_opts).await.unwrap();
-    let activity_pool = submit_pool.clone();
-    let discord_pool = submit_pool.clone();
-    let submit = tokio::spawn(async move {
-        let mut listener = PgListener::connect_with(&submit_pool).await.unwrap();
-        listener.listen("submit").await.unwrap();
-        loop {
-            match listener.recv().await {
-                Ok(notification) => {
-                    let run = query_as::<_, Run>(
-                        r#"SELECT r.id, r.user_id, u.name, r.section_id, s.patch, s.layout, s.category, 
-                            s.map, r.time, r.proof, r.verified, r.yt_id, r.is_pb, r.is_wr, r.created_at
-                        FROM run r
-                        INNER JOIN "user" u ON r.user_id = u.id
-                        INNER JOIN section s ON r.section_id = s.id
-                        WHERE r.id = $1::integer;"#,
-                    )
-                    .bind(notification.payload())
-                    .fetch_one(&submit_pool)
-                    .await;
-
-                    match run {
-                        Ok(r) => {
-                            if r.is_wr {
-                                let old = query_as::<_, PartialRun>(
-                                    r#"SELECT r.id, r.section_id, r.user_id, u.name, r.time, r.proof, 
-                                        r.verified, r.yt_id, r.is_pb, r.is_wr, r.created_at
-                                    FROM run r
-                                    INNER JOIN "user" u ON r.user_id = u.id
-                                    WHERE section_id = $1
-                                    ORDER BY time ASC, created_at ASC
-                                    OFFSET 1
-                                    LIMIT 1;"#,
-                                )
-                                .bind(r.section_id)
-                                .fetch_optional(&submit_pool)
-                                .await
-                                .ok()
-                                .flatten();
-                                send_wr(&r, &old, &submit_client).await;
-                            } else if r.is_pb {
-                                let old = query_as::<_, PartialRun>(
-                                    r#"SELECT r.id, r.section_id, r.user_id, u.name, r.time, r.proof, 
-                                        r.verified, r.yt_id, r.is_pb, r.is_wr, r.created_at
-                                    FROM run r
-                                    INNER JOIN "user" u ON r.user_id = u.id
-                                    WHERE section_id = $1 AND user_id = $2
-                                    ORDER BY time ASC, created_at ASC
-                                    OFFSET 1
-                                    LIMIT 1;"#,
-                                )
-                                .bind(r.section_id)
-                                .bind(r.user_id)
-                                .fetch_optional(&submit_pool)
-                                .await
-                                .ok()
-                                .flatten();
-                                send_pb(&r, &old, &submit_client).await;
-                            }
-                        }
-                        Err(e) => debug!("{e:?}"),
-                    }
-                }
-                Err(e) => debug!("{e:?}"),
-            };
-        }
-    });
-    let activity = tokio::spawn(async move {
-        let mut listener = PgListener::connect_with(&activity_pool).await.unwrap();
-        listener.listen("activity").await.unwrap();
-        loop {
-            match listener.recv().await {
-                Ok(notification) => {
-                    let activity = query_as::<_, Activity>(
-                        r#"SELECT a.id, a.user_id, u.name, a.rank_id, 
-                            r.patch, r.layout, r.category, a.title_old, 
-                            a.title_new, a.rank_old, a.rank_new, a.created_at
-                        FROM activity a
-                        INNER JOIN "user" u ON a.user_id = u.id
-                        INNER JOIN rank r ON a.rank_id = r.id
-                        WHERE a.id = $1::integer;"#,
-                    )
-                    .bind(notification.payload())
-                    .fetch_one(&activity_pool)
-                    .await;
-
-                    match activity {
-                        Ok(a) => {
-                            if a.title_old.is_some() && a.title_new.is_some() {
-                                send_title(&a, &activity_client).await;
-                            } else if a.rank_old.is_some() && a.rank_new.is_some() {
-                                send_rank(&a, &activity_client).await;
-                            } else {
-                                send_join(&a, &activity_client).await;
-                            }
-                            if a.title_new.is_some() && a.layout.is_none() && a.category.is_none() {
-                                let discord = query_as::<_, Discord>(
-                                    r#"SELECT id, user_id, access, refresh, expires_at
-                                    FROM discord
-                                    WHERE user_id = $1;"#
-                                )
-                                .bind(a.user_id)
-                                .fetch_all(&activity_pool)
-                                .await;
-
-                                match discord {
-                                    Ok(d) => update_title(&a, &d, &activity_client, &activity_pool).await,
-                                    Err(_) => {},
-                                }
-                            }
-                        }
-                        Err(e) => debug!("{e:?}"),
-                    }
-                }
-                Err(e) => debug!("{e:?}"),
-            };
+    // Needed for role connections, fail now rather than on the first title change
+    for var in ["DISCORD_ID", "DISCORD_SECRET"] {
+        if std::env::var(var).is_err() {
+            panic!("Missing {var}!");
         }
-    });
-    let discord = tokio::spawn(async move {
-        let mut listener = PgListener::connect_with(&discord_pool).await.unwrap();
-        listener.listen("discord").await.unwrap();
-        loop {
-            match listener.recv().await {
-                Ok(notification) => {
-                    let discord = query_as::<_, Discord>(
-                        r#"SELECT id, user_id, access, refresh, expires_at
-                        FROM discord
-                        WHERE id = $1::integer;"#
-                    )
-                    .bind(notification.payload())
-                    .fetch_one(&discord_pool)
-                    .await;
-
-                    match discord {
-                        Ok(d) => {
-                            let ranks = query_as::<_, Ranking>(
-                                r#"SELECT r.id, r.patch, r.layout, r.category, r.user_id, 
-                                u.name, r.title, r.rank, r.rating, r.created_at, r.updated_at
-                                FROM rank r
-                                INNER JOIN "user" u ON r.user_id = u.id
-                                WHERE r.user_id = $1 AND r.layout IS NULL AND r.category IS NULL;"#
-                            )
-                            .bind(d.user_id)
-                            .fetch_all(&discord_pool)
-                            .await;
-                            match ranks {
-                                Ok(r) => set_title(&r, &d, &discord_client, &discord_pool).await,
-                                Err(_) => {},
-                            }
-                        }
-                        Err(e) => debug!("{e:?}"),
-                    }
-                }
-                Err(e) => debug!("{e:?}"),
-            }
-        }
-    });
+    }
+    let config = Config::load().expect("Invalid webhook config");
+    let templates = Templates::load().expect("Invalid templates");
 
-    let _ = join!(submit, activity, discord).await;
+    let pool = connect_to_database().await;
+    verify_database(&pool).await;
+    outbox::work(pool, Client::new(), config, templates).await;
 }
 
-async fn send_pb(new: &Run, old: &Option<PartialRun>, client: &Client) {
-    let _ = client.post(std::env::var("PB_WEBHOOK").unwrap()).json(&json!({
-        "embeds": [{
-            "color": 16764928,
-            "title": format!("New Personal Best by {}", new.username),
-            "url": format!("https://youtube.com/watch?v={}", new.yt_id.as_ref().unwrap()),
-            "thumbnail": { "url": format!("https://lucio.surf/cdn/maps/{}.jpg", encode(&new.map))},
-            "description": format!("Patch: *{}*\nLayout: *{}*\nCategory: *{}*\nMap: *{}*", 
-                new.patch, new.layout, new.category, new.map),
-            "fields": [{
-                "name": "New PB",
-                "value": format!("Time: *{}*\nProof: *[link](https://youtube.com/watch?v={})*\nDate: *<t:{}>*", 
-                    new.time.to_string(), new.yt_id.as_ref().unwrap(), new.created_at.timestamp()),
-                "inline": true
-            },
-            {
-                "name": "Old PB",
-                "value": match old {
-                    Some(o) => format!("Time: *{}*\nProof: *[link](https://youtube.com/watch?v={})*\nDate: *<t:{}>*", 
-                        o.time.to_string(), o.yt_id.as_ref().unwrap(), o.created_at.timestamp()),
-                    None => "User: *none*\nTime: *none*\nProof: *none*\nDate: *none*".into(),
-                },
-                "inline": true
-            },
-            {
-                "name": "Comparison",
-                "value": match old {
-                    Some(o) => {
-                        let diff = new.created_at - o.created_at;
-                        format!("Time save: *{}*\nAchieved after: *{} weeks, {} days, {} hours, {} mins, {} secs*", 
-                            (o.time - new.time).to_string(), diff.num_weeks(), diff.num_days() % 7, 
-                            diff.num_hours() % 24, diff.num_minutes() % 60, diff.num_seconds() % 60
-                        )},
-                    None => "Time save: *undefined*\nAchieved after: *undefined*".into(),
-                }
-            }],
-            "footer": { "text": format!("ID: {}", new.id) }
-        }] 
-    })).send().await;
-    let _ = client.post(std::env::var("PB_WEBHOOK").unwrap()).json(&json!({
-        "content": format!("https://youtube.com/watch?v={}", new.yt_id.as_ref().unwrap())
-    })).send().await;
+/// The requests an outbox entry stands for, none when what it is about is gone.
+async fn messages(entry: &Entry, config: &Config, templates: &Templates, pool: &PgPool) -> Result<Vec<Message>> {
+    match entry.channel.as_str() {
+        "submit" => submit(entry.payload, config, templates, pool).await,
+        "activity" => activity(entry, config, templates, pool).await,
+        "discord" => discord(entry.payload, pool).await,
+        _ => Ok(vec![]),
+    }
 }
 
-async fn send_wr(new: &Run, old: &Option<PartialRun>, client: &Client) {
-    let _ = client.post(std::env::var("WR_WEBHOOK").unwrap()).json(&json!({
-        "embeds": [{
-            "color": 7798548,
-            "title": format!("New World Record by {}", new.username),
-            "url": format!("https://youtube.com/watch?v={}", new.yt_id.as_ref().unwrap()),
-            "thumbnail": { "url": format!("https://lucio.surf/cdn/maps/{}.jpg", encode(&new.map))},
-            "description": format!("Patch: *{}*\nLayout: *{}*\nCategory: *{}*\nMap: *{}*", 
-                new.patch, new.layout, new.category, new.map),
-            "fields": [{
-                "name": "New Record",
-                "value": format!("User: *{}*\nTime: *{}*\nProof: *[link](https://youtube.com/watch?v={})*\nDate: *<t:{}>*", 
-                    new.username, new.time.to_string(), new.yt_id.as_ref().unwrap(), new.created_at.timestamp()),
-                "inline": true
-            },
-            {
-                "name": "Old Record",
-                "value": match old {
-                    Some(o) => format!("User: *{}*\nTime: *{}*\nProof: *[link](https://youtube.com/watch?v={})*\nDate: *<t:{}>*", 
-                        o.name, o.time.to_string(), o.yt_id.as_ref().unwrap(), o.created_at.timestamp()),
-                    None => "User: *none*\nTime: *none*\nProof: *none*\nDate: *none*".into(),
-                },
-                "inline": true
-            },
-            {
-                "name": "Comparison",
-                "value": match old {
-                    Some(o) => {
-                        let diff = new.created_at - o.created_at;
-                        format!("Time save: *{}*\nAchieved after: *{} weeks, {} days, {} hours, {} mins, {} secs*", 
-                            (o.time - new.time).to_string(), diff.num_weeks(), diff.num_days() % 7, 
-                            diff.num_hours() % 24, diff.num_minutes() % 60, diff.num_seconds() % 60
-                        )},
-                    None => "Time save: *undefined*\nAchieved after: *undefined*".into(),
-                }
-            }],
-            "footer": { "text": format!("ID: {}", new.id) }
-        }] 
-    })).send().await;
-    let _ = client.post(std::env::var("WR_WEBHOOK").unwrap()).json(&json!({
-        "content": format!("https://youtube.com/watch?v={}", new.yt_id.as_ref().unwrap())
-    })).send().await;
+async fn submit(id: i32, config: &Config, templates: &Templates, pool: &PgPool) -> Result<Vec<Message>> {
+    let Some((r, old)) = announced_run(id, pool).await? else {
+        return Ok(vec![]);
+    };
+    let event = if r.is_wr { Event::Wr } else { Event::Pb };
+    let webhooks = config.webhooks(event, &Subject::from(&r));
+    if webhooks.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(post(&webhooks, templates.run(&r, old.as_ref())?))
 }
 
-async fn send_title(activity: &Activity, client: &Client) {
-    let new = activity.title_new.as_ref().unwrap();
-    let old = activity.title_old.as_ref().unwrap();
-    let _ = client.post(std::env::var("ACTIVITY_WEBHOOK").unwrap()).json(&json!({
-        "embeds": [{
-            "color": if new > old { 7798548 } else { 12064000 },
-            "title": "Title update",
-            "description": match (activity.layout.as_ref(), activity.category.as_ref()) {
-                (Some(l), Some(c)) => format!("User: *{}*\nCombo: *Layout {} - {}*", 
-                    activity.username, l, c),
-                _ => format!("User: *{}*\nCombo: *Combined*", activity.username),
-            },
-            "fields": [{
-                "name": old.to_string(),
-                "value": "",
-                "inline": true
-            },
-            {
-                "name": new.to_string(),
-                "value": "",
-                "inline": true
-            }]
-        }]
-    })).send().await;
+/// A run with the PB or WR it was when it was submitted and the record it beat, however
+/// late the bridge gets to it. `None` if it was neither, or was rejected in the meantime.
+async fn announced_run(id: i32, pool: &PgPool) -> Result<Option<(Run, Option<PartialRun>)>, sqlx::Error> {
+    let run = query_as::<_, Run>(
+        r#"SELECT r.id, r.user_id, u.name, r.section_id, s.patch, s.layout, s.category, 
+            s.map, r.time, r.proof, r.verified, r.rejected, r.reason, r.yt_id, r.proof_host, 
+            NOT EXISTS (
+                SELECT 1 FROM run o
+                WHERE o.section_id = r.section_id AND o.user_id = r.user_id AND NOT o.rejected
+                    AND (o.created_at, o.id) < (r.created_at, r.id) AND o.time <= r.time
+            ) AS is_pb,
+            NOT EXISTS (
+                SELECT 1 FROM run o
+                WHERE o.section_id = r.section_id AND NOT o.rejected
+                    AND (o.created_at, o.id) < (r.created_at, r.id) AND o.time <= r.time
+            ) AS is_wr,
+            r.created_at
+        FROM run r
+        INNER JOIN "user" u ON r.user_id = u.id
+        INNER JOIN section s ON r.section_id = s.id
+        WHERE r.id = $1 AND NOT r.rejected;"#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(r) = run.filter(|r| r.is_pb) else {
+        return Ok(None);
+    };
+    // The best run before it, of anyone for a WR
+    let old = query_as::<_, PartialRun>(
+        r#"SELECT r.id, r.section_id, r.user_id, u.name, r.time, r.proof, 
+            r.verified, r.yt_id, r.proof_host, r.is_pb, r.is_wr, r.created_at
+        FROM run r
+        INNER JOIN "user" u ON r.user_id = u.id
+        WHERE section_id = $1 AND ($5 OR user_id = $2) AND NOT r.rejected
+            AND (r.created_at, r.id) < ($3, $4)
+        ORDER BY time ASC, created_at ASC
+        LIMIT 1;"#,
+    )
+    .bind(r.section_id)
+    .bind(r.user_id)
+    .bind(r.created_at)
+    .bind(r.id)
+    .bind(r.is_wr)
+    .fetch_optional(pool)
+    .await?;
+    Ok(Some((r, old)))
 }
 
-async fn send_rank(_activity: &Activity, _client: &Client) {}
+async fn activity(entry: &Entry, config: &Config, templates: &Templates, pool: &PgPool) -> Result<Vec<Message>> {
+    let activity = query_as::<_, Activity>(
+        r#"SELECT a.id, a.user_id, u.name, a.rank_id, 
+            r.patch, r.layout, r.category, a.title_old, 
+            a.title_new, a.rank_old, a.rank_new, a.created_at
+        FROM activity a
+        INNER JOIN "user" u ON a.user_id = u.id
+        LEFT JOIN rank r ON a.rank_id = r.id
+        WHERE a.id = $1;"#,
+    )
+    .bind(entry.payload)
+    .fetch_optional(pool)
+    .await?;
 
-async fn send_join(activity: &Activity, client: &Client) {
-    let _ = client.post(std::env::var("ACTIVITY_WEBHOOK").unwrap()).json(&json!({
-        "embeds": [{
-            "color": 1342207,
-            "title": format!("{} joined the leaderboards!", activity.username)
-        }]
-    })).send().await;
+    let Some(a) = activity else {
+        return Ok(vec![]);
+    };
+    let mut messages = if a.title_old.is_some() && a.title_new.is_some() {
+        post(&config.webhooks(Event::Title, &Subject::from(&a)), templates.title(&a)?)
+    } else if a.rank_old.is_some() && a.rank_new.is_some() {
+        let changes = rank_changes(entry, &a, pool).await?;
+        let subject = Subject {
+            rank: changes.iter().filter_map(|c| c.rank_new).min(),
+            ..Subject::from(&a)
+        };
+        post(&config.webhooks(Event::Rank, &subject), templates.rank(&changes)?)
+    } else {
+        post(&config.webhooks(Event::Join, &Subject::from(&a)), templates.join(&a)?)
+    };
+    if a.title_new.is_some()
+        && a.layout.is_none()
+        && a.category.is_none()
+        && a.patch == current_patch(pool).await.ok()
+    {
+        let discord = query_scalar::<_, i32>("SELECT id FROM discord WHERE user_id = $1;")
+            .bind(a.user_id)
+            .fetch_all(pool)
+            .await?;
+        messages.extend(update_title(&a, &discord));
+    }
+    Ok(messages)
 }
 
-async fn update_title(activity: &Activity, auth: &Vec<Discord>, client: &Client, pool: &PgPool) {
-    for tokens in auth {
-        let token = get_access_token(tokens, client, pool).await;
-        if token.is_err() {
-            return;
-        }
-        let _ = client
-            .put(format!("https://discord.com/api/v10/users/@me/applications/{}/role-connection", 
-                std::env::var("DISCORD_ID").unwrap()))
-            .bearer_auth(token.unwrap())
-            .json(&json!({
+/// The changes to the top of the leaderboard of `activity` from the same submission, best
+/// rank first. One submission can shift many users, so they are announced together by the
+/// first of them and the rest is left empty.
+async fn rank_changes(entry: &Entry, activity: &Activity, pool: &PgPool) -> Result<Vec<Activity>, sqlx::Error> {
+    let changes = query_as::<_, Activity>(
+        r#"SELECT a.id, a.user_id, u.name, a.rank_id, 
+            r.patch, r.layout, r.category, a.title_old, 
+            a.title_new, a.rank_old, a.rank_new, a.created_at
+        FROM outbox o
+        INNER JOIN activity a ON o.payload = a.id
+        INNER JOIN "user" u ON a.user_id = u.id
+        INNER JOIN rank r ON a.rank_id = r.id
+        WHERE o.channel = 'activity' AND o.batch = $1 AND a.rank_old IS NOT NULL AND a.rank_new IS NOT NULL
+            AND r.patch = $2 AND r.layout IS NOT DISTINCT FROM $3 AND r.category IS NOT DISTINCT FROM $4
+        ORDER BY o.id;"#,
+    )
+    .bind(entry.batch)
+    .bind(&activity.patch)
+    .bind(&activity.layout)
+    .bind(&activity.category)
+    .fetch_all(pool)
+    .await?;
+    Ok(digest(changes, activity))
+}
+
+/// The changes within `DIGEST_RANKS` of the top, if `activity` is the first of them.
+fn digest(changes: Vec<Activity>, activity: &Activity) -> Vec<Activity> {
+    if changes.first().is_none_or(|first| first.id != activity.id) {
+        return vec![];
+    }
+    let mut changes: Vec<Activity> = changes
+        .into_iter()
+        .filter(|c| c.rank_new.min(c.rank_old).is_some_and(|r| r <= DIGEST_RANKS))
+        .collect();
+    changes.sort_by_key(|c| c.rank_new);
+    changes
+}
+
+async fn discord(id: i32, pool: &PgPool) -> Result<Vec<Message>> {
+    let user_id = query_scalar::<_, i64>("SELECT user_id FROM discord WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    let Some(user_id) = user_id else {
+        return Ok(vec![]);
+    };
+    let ranks = query_as::<_, Ranking>(
+        r#"SELECT r.id, r.patch, r.layout, r.category, r.user_id, u.name, r.title,
+        r.rank, r.rating, r.percentage, r.points, r.created_at, r.updated_at
+        FROM rank r
+        INNER JOIN "user" u ON r.user_id = u.id
+        INNER JOIN patch p ON r.patch = p.name
+        WHERE r.user_id = $1 AND r.layout IS NULL AND r.category IS NULL AND p.current;"#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(set_title(&ranks, id).into_iter().collect())
+}
+
+/// Posts each of `bodies` to every one of `webhooks`.
+fn post(webhooks: &[&str], bodies: Vec<WebhookMessage>) -> Vec<Message> {
+    let bodies: Vec<_> = bodies.iter().map(|body| json!(body)).collect();
+    webhooks
+        .iter()
+        .flat_map(|url| bodies.iter().map(|body| Message::webhook(url.to_string(), body.clone())))
+        .collect()
+}
+
+fn update_title(activity: &Activity, discord: &[i32]) -> Vec<Message> {
+    let key = metadata_key(activity.patch.as_ref().unwrap());
+    let mut messages = vec![];
+    for &id in discord {
+        messages.push(Message {
+            method: Method::PUT,
+            url: format!("https://discord.com/api/v10/users/@me/applications/{}/role-connection", 
+                std::env::var("DISCORD_ID").unwrap()),
+            discord: Some(id),
+            body: json!({
                 "platform_name": "Lucio Surf League",
                 "platform_username": activity.username,
                 "metadata": {
-                    "2_13": activity.title_new.as_ref().unwrap().clone() as i32
+                    key.clone(): activity.title_new.as_ref().unwrap().clone() as i32
                 }
-            })).send().await;
+            }),
+        });
     }
+    messages
 }
 
-async fn set_title(ranks: &Vec<Ranking>, discord: &Discord, client: &Client, pool: &PgPool) {
-    let token = get_access_token(discord, client, pool).await;
-    if token.is_err() {
-        return;
-    }
-    let token = token.unwrap();
-
-    let name = ranks.first().map(|v| v.username.clone());
-    if name.is_none() {
-        return;
-    }
-    let name = name.unwrap();
+fn set_title(ranks: &Vec<Ranking>, discord: i32) -> Option<Message> {
+    let name = ranks.first().map(|v| v.username.clone())?;
     // Somehow contruct the json payload from the vec
     let mut json = json!({
         "platform_name": "Lucio Surf League",
@@ -360,20 +261,44 @@ async fn set_title(ranks: &Vec<Ranking>, discord: &Discord, client: &Client, poo
         "metadata": {}
     });
     for rank in ranks {
-        json["metadata"]
-            .as_object_mut()
-            .map(|v| {
-                v.insert(
-                    rank.patch.clone().replace(".", "_"), 
-                    (rank.title.clone() as i32).into(),
-                );
-            });
+        if let Some(v) = json["metadata"].as_object_mut() {
+            v.insert(
+                metadata_key(&rank.patch),
+                (rank.title.clone() as i32).into(),
+            );
+        }
+    }
+    Some(Message {
+        method: Method::PUT,
+        url: format!("https://discord.com/api/v10/users/@me/applications/{}/role-connection", 
+            std::env::var("DISCORD_ID").unwrap()),
+        discord: Some(discord),
+        body: json,
+    })
+}
+
+/// Role connection metadata key of a patch, Discord only allows `a-z0-9_` in keys.
+fn metadata_key(patch: &str) -> String {
+    patch.replace(".", "_")
+}
+
+/// Access token of a linked account, refreshed when it expired. `None` once it was unlinked.
+async fn access_token(id: i32, client: &Client, pool: &PgPool) -> Result<Option<String>> {
+    let discord = query_as::<_, Discord>(
+        r#"SELECT id, access, refresh, expires_at
+        FROM discord
+        WHERE id = $1;"#
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    match discord {
+        Some(d) => get_access_token(&d, client, pool)
+            .await
+            .map(Some)
+            .map_err(|_| anyhow!("Failed to refresh the token of Discord account {id}")),
+        None => Ok(None),
     }
-    let _ = client
-        .put(format!("https://discord.com/api/v10/users/@me/applications/{}/role-connection", 
-            std::env::var("DISCORD_ID").unwrap()))
-        .bearer_auth(&token)
-        .json(&json).send().await;
 }
 
 async fn get_access_token(tokens: &Discord, client: &Client, pool: &PgPool) -> Result<String, ()> {
@@ -402,4 +327,173 @@ async fn get_access_token(tokens: &Discord, client: &Client, pool: &PgPool) -> R
             }
             Err(_) => Err(())
         }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use server::auth::ssr::MIGRATOR;
+
+    use super::*;
+
+    /// Submits runs to one section, one minute apart, and returns their ids.
+    async fn submit_runs(pool: &PgPool, runs: &[(&str, &str, bool)]) -> Vec<i32> {
+        let section = query_scalar::<_, i32>(
+            r#"INSERT INTO section (patch, layout, category, map, code)
+            VALUES ('2.13', '1', 'Standard', 'Busan', 'BUS1')
+            RETURNING id;"#,
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let mut ids = vec![];
+        for (i, (user, time, rejected)) in runs.iter().enumerate() {
+            let id = query_scalar::<_, i32>(
+                r#"WITH u AS (
+                    INSERT INTO "user" (name, password) VALUES ($2, '')
+                    ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+                    RETURNING id
+                )
+                INSERT INTO run (section_id, user_id, time, proof, verified, rejected, created_at)
+                SELECT $1, u.id, $3::numeric, 'https://youtube.com/watch?v=dQw4w9WgXcQ', TRUE, $4,
+                    now() - make_interval(mins => 10 - $5)
+                FROM u
+                RETURNING id;"#,
+            )
+            .bind(section)
+            .bind(user)
+            .bind(time)
+            .bind(rejected)
+            .bind(i as i32)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        ids
+    }
+
+    /// Whether `id` is announced as a WR, and who held the record it beat.
+    async fn announced(pool: &PgPool, id: i32) -> Option<(bool, Option<String>)> {
+        announced_run(id, pool)
+            .await
+            .unwrap()
+            .map(|(r, old)| (r.is_wr, old.map(|o| o.name)))
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn submits_are_announced_as_they_were(pool: PgPool) {
+        let ids = submit_runs(
+            &pool,
+            &[
+                ("alpha", "12.000", false),
+                ("bravo", "11.000", false),
+                ("alpha", "11.500", false),
+                // Ties and rejected runs are no records
+                ("alpha", "11.500", false),
+                ("charlie", "9.000", true),
+                ("charlie", "10.000", false),
+            ],
+        )
+        .await;
+
+        // Beaten since, but a WR when it was submitted
+        assert_eq!(announced(&pool, ids[0]).await, Some((true, None)));
+        assert_eq!(announced(&pool, ids[1]).await, Some((true, Some("alpha".into()))));
+        assert_eq!(announced(&pool, ids[2]).await, Some((false, Some("alpha".into()))));
+        assert_eq!(announced(&pool, ids[3]).await, None);
+        assert_eq!(announced(&pool, ids[4]).await, None);
+        assert_eq!(announced(&pool, ids[5]).await, Some((true, Some("bravo".into()))));
+    }
+
+    /// Queues rank changes of the Standard leaderboard, by (user, rank_old, rank_new), in
+    /// one transaction and returns their outbox entries.
+    async fn queue_ranks(pool: &PgPool, changes: &[(&str, i32, i32)], category: &str) -> Vec<Entry> {
+        let mut tx = pool.begin().await.unwrap();
+        let mut entries = vec![];
+        for &(user, old, new) in changes {
+            let id = query_scalar::<_, i32>(
+                r#"WITH u AS (
+                    INSERT INTO "user" (name, password) VALUES ($1, '')
+                    ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+                    RETURNING id
+                ), r AS (
+                    INSERT INTO rank (user_id, patch, layout, category, title, rank, rating, percentage)
+                    SELECT u.id, '2.13', '1', $4, 'Surfer', $3, 0, 0 FROM u
+                    ON CONFLICT (patch, layout, category, user_id) DO UPDATE SET rank = EXCLUDED.rank
+                    RETURNING id, user_id
+                )
+                INSERT INTO activity (user_id, rank_id, rank_old, rank_new)
+                SELECT r.user_id, r.id, $2, $3 FROM r
+                RETURNING id;"#,
+            )
+            .bind(user)
+            .bind(old)
+            .bind(new)
+            .bind(category)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+            let entry = query_as::<_, Entry>(
+                r#"SELECT id, channel, payload, attempts, sent, batch, messages
+                FROM outbox
+                WHERE channel = 'activity' AND payload = $1;"#,
+            )
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await
+            .unwrap();
+            entries.push(entry);
+        }
+        tx.commit().await.unwrap();
+        entries
+    }
+
+    /// Users in the digest of `entry`.
+    async fn digested(pool: &PgPool, entry: &Entry) -> Vec<String> {
+        let activity = query_as::<_, Activity>(
+            r#"SELECT a.id, a.user_id, u.name, a.rank_id, 
+                r.patch, r.layout, r.category, a.title_old, 
+                a.title_new, a.rank_old, a.rank_new, a.created_at
+            FROM activity a
+            INNER JOIN "user" u ON a.user_id = u.id
+            LEFT JOIN rank r ON a.rank_id = r.id
+            WHERE a.id = $1;"#,
+        )
+        .bind(entry.payload)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        rank_changes(entry, &activity, pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| c.username)
+            .collect()
+    }
+
+    #[sqlx::test(migrator = "MIGRATOR")]
+    async fn rank_changes_of_a_submission_are_digested_together(pool: PgPool) {
+        let submission = queue_ranks(
+            &pool,
+            &[
+                ("alpha", 2, 1),
+                // Leaving the top counts, moving below it doesn't
+                ("bravo", 11, 12),
+                ("charlie", 10, 11),
+                ("delta", 1, 2),
+            ],
+            "Standard",
+        )
+        .await;
+        let nostalgia = queue_ranks(&pool, &[("echo", 5, 3)], "Nostalgia").await;
+        let later = queue_ranks(&pool, &[("foxtrot", 4, 3)], "Standard").await;
+        assert!(submission.iter().all(|e| e.batch == submission[0].batch));
+        assert_ne!(submission[0].batch, nostalgia[0].batch);
+
+        assert_eq!(digested(&pool, &submission[0]).await, ["alpha", "delta", "charlie"]);
+        for entry in &submission[1..] {
+            assert!(digested(&pool, entry).await.is_empty());
+        }
+        assert_eq!(digested(&pool, &nostalgia[0]).await, ["echo"]);
+        assert_eq!(digested(&pool, &later[0]).await, ["foxtrot"]);
+    }
+}
@@ -0,0 +1,377 @@
+//! Delivery of the `outbox` table. Entries are claimed one at a time, turned into the
+//! requests they stand for by `crate::messages` and sent in order. The requests are stored
+//! on the first claim and how many of them went through after each one, so a retry sends
+//! the rest of the same list. Entries are retried with backoff until they are delivered or
+//! run out of attempts, and pruned `RETENTION` after they were queued.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use log::{debug, error, warn};
+use reqwest::{Client, Method, StatusCode, header::RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgPool, postgres::PgListener, prelude::FromRow, query, query_as, query_scalar, types::Json};
+
+use crate::{config::Config, templates::Templates};
+
+/// Attempts before an entry is dead-lettered, rate limits aside.
+const MAX_ATTEMPTS: i32 = 10;
+/// Longest wait between retries, and between checks for due entries.
+const MAX_WAIT: Duration = Duration::from_secs(60 * 60);
+const POLL: Duration = Duration::from_secs(60);
+/// How long delivered and dead entries are kept around.
+const RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+#[derive(Debug, FromRow)]
+pub struct Entry {
+    pub id: i64,
+    pub channel: String,
+    pub payload: i32,
+    pub attempts: i32,
+    pub sent: i32,
+    /// Id of the transaction that queued the entry, shared by everything it queued.
+    pub batch: i64,
+    /// Frozen on the first claim.
+    pub messages: Option<Json<Vec<Message>>>,
+}
+
+/// A JSON request to Discord.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    #[serde(with = "method")]
+    pub method: Method,
+    pub url: String,
+    /// Linked account whose token authorizes the request. Tokens expire, so they are only
+    /// looked up when sending.
+    pub discord: Option<i32>,
+    pub body: Value,
+}
+
+impl Message {
+    pub fn webhook(url: String, body: Value) -> Self {
+        Self {
+            method: Method::POST,
+            url,
+            discord: None,
+            body,
+        }
+    }
+}
+
+enum Outcome {
+    /// Sent, waiting out the rate limit before the next request if it ran out.
+    Sent(Option<Duration>),
+    RateLimited(Duration),
+    /// Worth retrying, like server errors and timeouts.
+    Failed(String),
+    /// Won't go through however often it is retried.
+    Rejected(String),
+}
+
+/// Delivers the outbox forever. Everything still pending from before the start is
+/// caught up on first, after that inserts wake the worker up.
+pub async fn work(pool: PgPool, client: Client, config: Config, templates: Templates) {
+    let mut listener = PgListener::connect_with(&pool).await.unwrap();
+    listener.listen("outbox").await.unwrap();
+    let mut next_prune = Instant::now();
+    loop {
+        if Instant::now() >= next_prune {
+            prune_done(&pool).await;
+            next_prune = Instant::now() + MAX_WAIT;
+        }
+        loop {
+            match claim(&pool).await {
+                Ok(Some(entry)) => {
+                    if let Some(pause) = deliver(&pool, &client, &config, &templates, entry).await {
+                        tokio::time::sleep(pause).await;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Failed to claim outbox entry: {e}");
+                    break;
+                }
+            }
+        }
+        let wait = match query_scalar::<_, Option<DateTime<Local>>>(
+            "SELECT MIN(next_attempt_at) FROM outbox WHERE status = 'Pending'",
+        )
+        .fetch_one(&pool)
+        .await
+        {
+            Ok(Some(due)) => (due - Local::now()).to_std().unwrap_or_default().min(POLL),
+            Ok(None) => POLL,
+            Err(e) => {
+                error!("Failed to look up outbox: {e}");
+                POLL
+            }
+        };
+        if let Ok(Err(e)) = tokio::time::timeout(wait, listener.recv()).await {
+            // The listener reconnects on the next call, don't spin until it can
+            warn!("Lost outbox listener: {e}");
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+}
+
+/// Takes the oldest due entry. Claiming pushes its next attempt back, so an entry of a
+/// worker that died halfway is retried once that passes.
+async fn claim(pool: &PgPool) -> Result<Option<Entry>, sqlx::Error> {
+    query_as::<_, Entry>(
+        r#"UPDATE outbox
+        SET attempts = attempts + 1, next_attempt_at = now() + interval '5 minutes'
+        WHERE id = (
+            SELECT id FROM outbox
+            WHERE status = 'Pending' AND next_attempt_at <= now()
+            ORDER BY id
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, channel, payload, attempts, sent, batch, messages"#,
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Sends the rest of the requests of `entry` and records how far it got. Returns how
+/// long to hold off all requests when Discord rate limits the bridge.
+async fn deliver(
+    pool: &PgPool,
+    client: &Client,
+    config: &Config,
+    templates: &Templates,
+    mut entry: Entry,
+) -> Option<Duration> {
+    let messages = match entry.messages.take() {
+        Some(Json(messages)) => messages,
+        None => match freeze(pool, &entry, config, templates).await {
+            Ok(messages) => messages,
+            Err(e) => {
+                retry(pool, &entry, format!("{e:#}")).await;
+                return None;
+            }
+        },
+    };
+    for (i, message) in messages.iter().enumerate().skip(entry.sent as usize) {
+        match send(client, pool, message).await {
+            Outcome::Sent(wait) => {
+                let sent = query("UPDATE outbox SET sent = $2 WHERE id = $1")
+                    .bind(entry.id)
+                    .bind(i as i32 + 1)
+                    .execute(pool)
+                    .await;
+                if let Err(e) = sent {
+                    error!("Failed to update outbox entry {}: {e}", entry.id);
+                }
+                if let Some(wait) = wait {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            Outcome::RateLimited(after) => {
+                debug!("Rate limited for {after:?} on outbox entry {}", entry.id);
+                // Waiting for the limit isn't the entry's fault, give the attempt back
+                let res = query(
+                    r#"UPDATE outbox
+                    SET attempts = attempts - 1, next_attempt_at = now() + $2, last_error = 'Rate limited'
+                    WHERE id = $1"#,
+                )
+                .bind(entry.id)
+                .bind(after)
+                .execute(pool)
+                .await;
+                if let Err(e) = res {
+                    error!("Failed to update outbox entry {}: {e}", entry.id);
+                }
+                return Some(after);
+            }
+            Outcome::Failed(e) => {
+                retry(pool, &entry, e).await;
+                return None;
+            }
+            Outcome::Rejected(e) => {
+                dead(pool, &entry, e).await;
+                return None;
+            }
+        }
+    }
+    let res = query("UPDATE outbox SET status = 'Delivered', delivered_at = now(), last_error = NULL WHERE id = $1")
+        .bind(entry.id)
+        .execute(pool)
+        .await;
+    if let Err(e) = res {
+        error!("Failed to update outbox entry {}: {e}", entry.id);
+    }
+    None
+}
+
+/// Builds the requests of an entry on its first claim and stores them, nothing is sent
+/// until they are.
+async fn freeze(pool: &PgPool, entry: &Entry, config: &Config, templates: &Templates) -> anyhow::Result<Vec<Message>> {
+    let messages = crate::messages(entry, config, templates, pool).await?;
+    query("UPDATE outbox SET messages = $2 WHERE id = $1")
+        .bind(entry.id)
+        .bind(Json(&messages))
+        .execute(pool)
+        .await?;
+    Ok(messages)
+}
+
+/// Schedules the next attempt, or gives up after `MAX_ATTEMPTS`.
+async fn retry(pool: &PgPool, entry: &Entry, error: String) {
+    if entry.attempts >= MAX_ATTEMPTS {
+        dead(pool, entry, error).await;
+        return;
+    }
+    warn!("Outbox entry {} failed, retrying: {error}", entry.id);
+    let res = query("UPDATE outbox SET next_attempt_at = now() + $2, last_error = $3 WHERE id = $1")
+        .bind(entry.id)
+        .bind(backoff(entry.attempts))
+        .bind(error)
+        .execute(pool)
+        .await;
+    if let Err(e) = res {
+        error!("Failed to update outbox entry {}: {e}", entry.id);
+    }
+}
+
+async fn dead(pool: &PgPool, entry: &Entry, error: String) {
+    error!("Outbox entry {} dead-lettered: {error}", entry.id);
+    let res = query("UPDATE outbox SET status = 'Dead', last_error = $2 WHERE id = $1")
+        .bind(entry.id)
+        .bind(error)
+        .execute(pool)
+        .await;
+    if let Err(e) = res {
+        error!("Failed to update outbox entry {}: {e}", entry.id);
+    }
+}
+
+/// Wait before the next attempt, doubling from 10 seconds up to `MAX_WAIT`.
+fn backoff(attempts: i32) -> Duration {
+    let doublings = attempts.clamp(1, 16) as u32 - 1;
+    (Duration::from_secs(10) * 2u32.pow(doublings)).min(MAX_WAIT)
+}
+
+/// Deletes delivered and dead entries older than `RETENTION`.
+async fn prune_done(pool: &PgPool) {
+    let res = query("DELETE FROM outbox WHERE status <> 'Pending' AND created_at < now() - $1")
+        .bind(RETENTION)
+        .execute(pool)
+        .await;
+    match res {
+        Ok(res) if res.rows_affected() > 0 => debug!("Pruned {} outbox entries", res.rows_affected()),
+        Ok(_) => {}
+        Err(e) => error!("Failed to prune outbox: {e}"),
+    }
+}
+
+async fn send(client: &Client, pool: &PgPool, message: &Message) -> Outcome {
+    let mut request = client.request(message.method.clone(), &message.url).json(&message.body);
+    if let Some(id) = message.discord {
+        match crate::access_token(id, client, pool).await {
+            Ok(Some(token)) => request = request.bearer_auth(token),
+            // Unlinked since, there is no one to update anymore
+            Ok(None) => return Outcome::Sent(None),
+            Err(e) => return Outcome::Failed(format!("{e:#}")),
+        }
+    }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    let status = response.status();
+    // Discord gives both in seconds, with fractions for the bucket reset
+    let seconds = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(Duration::from_secs_f64)
+    };
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Outcome::RateLimited(seconds(RETRY_AFTER.as_str()).unwrap_or(Duration::from_secs(5)));
+    }
+    if status.is_success() {
+        let exhausted = response
+            .headers()
+            .get("x-ratelimit-remaining")
+            .is_some_and(|v| v.as_bytes() == b"0");
+        return Outcome::Sent(seconds("x-ratelimit-reset-after").filter(|_| exhausted));
+    }
+    let error = format!("{status}: {}", response.text().await.unwrap_or_default());
+    if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+        Outcome::Failed(error)
+    } else {
+        Outcome::Rejected(error)
+    }
+}
+
+/// Stores a `Method` by name.
+mod method {
+    use reqwest::Method;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(method: &Method, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(method.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Method, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        assert_eq!(backoff(1), Duration::from_secs(10));
+        assert_eq!(backoff(2), Duration::from_secs(20));
+        assert_eq!(backoff(5), Duration::from_secs(160));
+        assert_eq!(backoff(MAX_ATTEMPTS), MAX_WAIT);
+    }
+
+    #[sqlx::test(migrator = "server::auth::ssr::MIGRATOR")]
+    async fn prunes_old_finished_entries(pool: PgPool) {
+        query(
+            r#"INSERT INTO outbox (channel, payload, status, created_at)
+            VALUES ('submit', 1, 'Delivered', now() - interval '31 days'),
+                ('submit', 2, 'Dead', now() - interval '31 days'),
+                ('submit', 3, 'Pending', now() - interval '31 days'),
+                ('submit', 4, 'Delivered', now() - interval '29 days');"#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        prune_done(&pool).await;
+        let left = query_scalar::<_, i32>("SELECT payload FROM outbox ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, [3, 4]);
+    }
+
+    #[test]
+    fn messages_survive_storage() {
+        let messages = vec![
+            Message::webhook(
+                "https://discord.com/api/webhooks/1/a".into(),
+                serde_json::json!({"content": "hi"}),
+            ),
+            Message {
+                method: Method::PUT,
+                url: "https://discord.com/api/v10/users/@me/applications/1/role-connection".into(),
+                discord: Some(7),
+                body: serde_json::json!({"metadata": {}}),
+            },
+        ];
+        let stored: Vec<Message> = serde_json::from_value(serde_json::to_value(&messages).unwrap()).unwrap();
+        assert_eq!(stored[0].method, Method::POST);
+        assert_eq!(stored[1].method, Method::PUT);
+        assert_eq!(stored[1].discord, Some(7));
+        assert_eq!(stored[1].body, messages[1].body);
+    }
+}
@@ -0,0 +1,592 @@
+//! Text of the announcements. Each event is rendered from a template, the file named after
+//! it in `templates/` unless `TEMPLATE_DIR` has one of the same name:
+//!
+//! ```toml
+//! color = "#ffcd00"
+//! title = "New Personal Best by {{user}}"
+//! description = "{{#if old}}{{old.time_save}} faster{{else}}First run{{/if}}"
+//!
+//! [[field]]
+//! name = "Time"
+//! value = "{{time}}"
+//! inline = true
+//! ```
+//!
+//! Every string is a Handlebars template over the variables of the event, the fields of
+//! `RunVars`, `TitleVars`, `RankVars` and `JoinVars`. Parts that render empty are left out.
+//! Each template is rendered with sample data on startup, so a misspelled variable fails
+//! there rather than on the next event. Links point to `SITE_URL`, the live site by default.
+
+use std::{collections::HashMap, env, fs, io::ErrorKind, path::Path};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use handlebars::{Handlebars, no_escape};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use types::api::{Activity, PartialRun, Run};
+use urlencoding::encode;
+
+use crate::{
+    config::Event,
+    embed::{Embed, WebhookMessage},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Template {
+    /// Decimal or hex starting with `#`.
+    color: String,
+    title: Option<String>,
+    url: Option<String>,
+    thumbnail: Option<String>,
+    description: Option<String>,
+    #[serde(default, rename = "field")]
+    fields: Vec<FieldTemplate>,
+    footer: Option<String>,
+    /// Sent as a message of its own after the embed.
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldTemplate {
+    name: String,
+    value: String,
+    #[serde(default)]
+    inline: bool,
+}
+
+pub struct Templates {
+    site: String,
+    registry: Handlebars<'static>,
+    fields: HashMap<Event, Vec<bool>>,
+}
+
+/// Variables of `pb` and `wr`.
+#[derive(Debug, Serialize)]
+struct RunVars {
+    id: i32,
+    user: String,
+    user_url: String,
+    time: String,
+    proof: String,
+    /// Unix timestamp of the submission, shown in the reader's timezone as `<t:{{date}}>`.
+    date: i64,
+    patch: String,
+    layout: String,
+    category: String,
+    map: String,
+    map_image: String,
+    /// The PB or WR before, `null` for the first.
+    old: Option<OldRunVars>,
+}
+
+#[derive(Debug, Serialize)]
+struct OldRunVars {
+    user: String,
+    time: String,
+    proof: String,
+    date: i64,
+    time_save: String,
+    /// Time between the two runs, from weeks down to seconds.
+    after: String,
+}
+
+/// Variables of `title`.
+#[derive(Debug, Serialize)]
+struct TitleVars {
+    user: String,
+    user_url: String,
+    ranking_url: String,
+    patch: Option<String>,
+    layout: Option<String>,
+    category: Option<String>,
+    title_old: Option<String>,
+    title_new: Option<String>,
+    promoted: bool,
+}
+
+/// Variables of `rank`, a digest of the changes to the top of one leaderboard.
+#[derive(Debug, Serialize)]
+struct RankVars {
+    patch: Option<String>,
+    layout: Option<String>,
+    category: Option<String>,
+    /// Ranking of the climber, or of the first change when nobody climbed.
+    url: String,
+    /// Whoever climbed the furthest, usually the one who submitted.
+    climber: Option<RankChange>,
+    /// Best rank first.
+    changes: Vec<RankChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RankChange {
+    user: String,
+    url: String,
+    rank_old: i32,
+    rank_new: i32,
+    climbed: bool,
+}
+
+/// Variables of `join`.
+#[derive(Debug, Serialize)]
+struct JoinVars {
+    user: String,
+    user_url: String,
+    ranking_url: String,
+}
+
+impl Templates {
+    pub fn load() -> Result<Self> {
+        let site = env::var("SITE_URL").unwrap_or("https://lucio.surf".into());
+        let dir = env::var("TEMPLATE_DIR").ok();
+        let mut templates = Self::new(&site);
+        for event in Event::iter() {
+            let custom = match &dir {
+                Some(dir) => match fs::read_to_string(Path::new(dir).join(format!("{event}.toml"))) {
+                    Ok(file) => Some(file),
+                    Err(e) if e.kind() == ErrorKind::NotFound => None,
+                    Err(e) => return Err(e).with_context(|| format!("Failed to read {event} template")),
+                },
+                None => None,
+            };
+            templates.add(event, custom.as_deref().unwrap_or(default(event)))?;
+        }
+        templates.check()?;
+        Ok(templates)
+    }
+
+    /// The defaults of every event, as if `TEMPLATE_DIR` was empty.
+    #[cfg(test)]
+    pub fn defaults() -> Self {
+        let mut templates = Templates::new("https://lucio.surf/");
+        for event in Event::iter() {
+            templates.add(event, default(event)).unwrap();
+        }
+        templates.check().unwrap();
+        templates
+    }
+
+    fn new(site: &str) -> Self {
+        let mut registry = Handlebars::new();
+        // Misspelled variables are errors rather than empty
+        registry.set_strict_mode(true);
+        // Embeds are markdown, not HTML
+        registry.register_escape_fn(no_escape);
+        Self {
+            site: site.trim_end_matches('/').into(),
+            registry,
+            fields: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, event: Event, file: &str) -> Result<()> {
+        let template = toml::from_str::<Template>(file).with_context(|| format!("Invalid {event} template"))?;
+        let parts = [
+            ("color", Some(&template.color)),
+            ("title", template.title.as_ref()),
+            ("url", template.url.as_ref()),
+            ("thumbnail", template.thumbnail.as_ref()),
+            ("description", template.description.as_ref()),
+            ("footer", template.footer.as_ref()),
+            ("content", template.content.as_ref()),
+        ];
+        for (part, text) in parts {
+            let name = format!("{event}.{part}");
+            match text {
+                Some(text) => self.register(&name, text)?,
+                None => self.registry.unregister_template(&name),
+            }
+        }
+        for (i, field) in template.fields.iter().enumerate() {
+            self.register(&format!("{event}.field{i}.name"), &field.name)?;
+            self.register(&format!("{event}.field{i}.value"), &field.value)?;
+        }
+        self.fields
+            .insert(event, template.fields.iter().map(|f| f.inline).collect());
+        Ok(())
+    }
+
+    fn register(&mut self, name: &str, text: &str) -> Result<()> {
+        self.registry
+            .register_template_string(name, text)
+            .with_context(|| format!("Invalid template {name}"))
+    }
+
+    /// Renders every template with each kind of sample.
+    fn check(&self) -> Result<()> {
+        for run in samples::runs() {
+            self.run(&run, Some(&samples::old_run()))?;
+            self.run(&run, None)?;
+        }
+        for activity in samples::titles() {
+            self.title(&activity)?;
+        }
+        self.rank(&samples::ranks())?;
+        self.rank(&samples::ranks()[1..])?;
+        self.join(&samples::join())?;
+        Ok(())
+    }
+
+    pub fn run(&self, run: &Run, old: Option<&PartialRun>) -> Result<Vec<WebhookMessage>> {
+        let vars = RunVars {
+            id: run.id,
+            user: run.username.clone(),
+            user_url: self.user_url(run.user_id),
+            time: run.time.to_string(),
+            proof: run.proof.clone(),
+            date: run.created_at.timestamp(),
+            patch: run.patch.clone(),
+            layout: run.layout.clone(),
+            category: run.category.clone(),
+            map: run.map.clone(),
+            map_image: format!("{}/cdn/maps/{}.jpg", self.site, encode(&run.map)),
+            old: old.map(|o| OldRunVars {
+                user: o.name.clone(),
+                time: o.time.to_string(),
+                proof: o.proof.clone(),
+                date: o.created_at.timestamp(),
+                time_save: (o.time - run.time).to_string(),
+                after: duration(o.created_at, run.created_at),
+            }),
+        };
+        self.render(if run.is_wr { Event::Wr } else { Event::Pb }, &vars)
+    }
+
+    pub fn title(&self, activity: &Activity) -> Result<Vec<WebhookMessage>> {
+        let vars = TitleVars {
+            user: activity.username.clone(),
+            user_url: self.user_url(activity.user_id),
+            ranking_url: self.ranking_url(activity.user_id),
+            patch: activity.patch.clone(),
+            layout: activity.layout.clone(),
+            category: activity.category.clone(),
+            title_old: activity.title_old.as_ref().map(|t| t.to_string()),
+            title_new: activity.title_new.as_ref().map(|t| t.to_string()),
+            promoted: activity.title_new > activity.title_old,
+        };
+        self.render(Event::Title, &vars)
+    }
+
+    /// One digest of `changes`, which all have to be on the same leaderboard. Nothing when
+    /// there are none.
+    pub fn rank(&self, changes: &[Activity]) -> Result<Vec<WebhookMessage>> {
+        let Some(first) = changes.first() else {
+            return Ok(vec![]);
+        };
+        let changes: Vec<RankChange> = changes
+            .iter()
+            .map(|c| {
+                let (old, new) = (c.rank_old.unwrap_or_default(), c.rank_new.unwrap_or_default());
+                RankChange {
+                    user: c.username.clone(),
+                    url: self.ranking_url(c.user_id),
+                    rank_old: old,
+                    rank_new: new,
+                    climbed: new < old,
+                }
+            })
+            .collect();
+        let climber = changes
+            .iter()
+            .filter(|c| c.climbed)
+            .max_by_key(|c| c.rank_old - c.rank_new)
+            .cloned();
+        let vars = RankVars {
+            patch: first.patch.clone(),
+            layout: first.layout.clone(),
+            category: first.category.clone(),
+            url: climber.as_ref().unwrap_or(&changes[0]).url.clone(),
+            climber,
+            changes,
+        };
+        self.render(Event::Rank, &vars)
+    }
+
+    pub fn join(&self, activity: &Activity) -> Result<Vec<WebhookMessage>> {
+        let vars = JoinVars {
+            user: activity.username.clone(),
+            user_url: self.user_url(activity.user_id),
+            ranking_url: self.ranking_url(activity.user_id),
+        };
+        self.render(Event::Join, &vars)
+    }
+
+    fn render(&self, event: Event, vars: &impl Serialize) -> Result<Vec<WebhookMessage>> {
+        let render = |part: &str| -> Result<String> {
+            let name = format!("{event}.{part}");
+            let text = self
+                .registry
+                .render(&name, vars)
+                .with_context(|| format!("Failed to render {name}"))?;
+            Ok(text.trim().into())
+        };
+        let optional = |part: &str| -> Result<Option<String>> {
+            if !self.registry.has_template(&format!("{event}.{part}")) {
+                return Ok(None);
+            }
+            Ok(Some(render(part)?).filter(|text| !text.is_empty()))
+        };
+
+        let color = render("color")?;
+        let mut embed = Embed::new(parse_color(&color).with_context(|| format!("Invalid {event} color {color:?}"))?);
+        if let Some(title) = optional("title")? {
+            embed = embed.title(&title);
+        }
+        if let Some(url) = optional("url")? {
+            embed = embed.url(&url);
+        }
+        if let Some(thumbnail) = optional("thumbnail")? {
+            embed = embed.thumbnail(&thumbnail);
+        }
+        if let Some(description) = optional("description")? {
+            embed = embed.description(&description);
+        }
+        for (i, inline) in self.fields[&event].iter().enumerate() {
+            embed = embed.field(
+                &render(&format!("field{i}.name"))?,
+                &render(&format!("field{i}.value"))?,
+                *inline,
+            );
+        }
+        if let Some(footer) = optional("footer")? {
+            embed = embed.footer(&footer);
+        }
+        let mut messages = vec![WebhookMessage::embed(embed)];
+        if let Some(content) = optional("content")? {
+            messages.push(WebhookMessage::content(&content));
+        }
+        Ok(messages)
+    }
+
+    fn user_url(&self, user_id: i64) -> String {
+        format!("{}/user/{}/leaderboard", self.site, user_id)
+    }
+
+    fn ranking_url(&self, user_id: i64) -> String {
+        format!("{}/user/{}/ranking", self.site, user_id)
+    }
+}
+
+fn default(event: Event) -> &'static str {
+    match event {
+        Event::Pb => include_str!("../templates/pb.toml"),
+        Event::Wr => include_str!("../templates/wr.toml"),
+        Event::Title => include_str!("../templates/title.toml"),
+        Event::Rank => include_str!("../templates/rank.toml"),
+        Event::Join => include_str!("../templates/join.toml"),
+    }
+}
+
+fn parse_color(color: &str) -> Result<u32> {
+    Ok(match color.strip_prefix('#') {
+        Some(hex) => u32::from_str_radix(hex, 16)?,
+        None => color.parse()?,
+    })
+}
+
+fn duration(from: DateTime<Local>, to: DateTime<Local>) -> String {
+    let diff = to - from;
+    format!(
+        "{} weeks, {} days, {} hours, {} mins, {} secs",
+        diff.num_weeks(),
+        diff.num_days() % 7,
+        diff.num_hours() % 24,
+        diff.num_minutes() % 60,
+        diff.num_seconds() % 60
+    )
+}
+
+/// Events the templates are checked with on startup.
+mod samples {
+    use chrono::{DateTime, Local, TimeZone};
+    use rust_decimal::Decimal;
+    use types::api::{Activity, PartialRun, ProofHost, Run, Title};
+
+    fn date(timestamp: i64) -> DateTime<Local> {
+        Local.timestamp_opt(timestamp, 0).unwrap()
+    }
+
+    /// A PB and a WR, the PB with a proof that isn't on YouTube.
+    pub fn runs() -> [Run; 2] {
+        let pb = Run {
+            id: 42,
+            section_id: 7,
+            patch: "2.13".into(),
+            layout: "1".into(),
+            category: "Standard".into(),
+            map: "King's Row".into(),
+            user_id: 3,
+            username: "charlie".into(),
+            time: Decimal::new(7512, 2),
+            proof: "https://streamable.com/abc123".into(),
+            yt_id: Some("abc123".into()),
+            proof_host: ProofHost::Streamable,
+            verified: false,
+            rejected: false,
+            reason: None,
+            is_pb: true,
+            is_wr: false,
+            created_at: date(1_760_000_000),
+        };
+        let wr = Run {
+            id: 43,
+            proof: "https://youtube.com/watch?v=dQw4w9WgXcQ".into(),
+            yt_id: Some("dQw4w9WgXcQ".into()),
+            proof_host: ProofHost::YouTube,
+            is_wr: true,
+            ..pb.clone()
+        };
+        [pb, wr]
+    }
+
+    pub fn old_run() -> PartialRun {
+        PartialRun {
+            id: 17,
+            section_id: 7,
+            user_id: 1,
+            name: "alpha".into(),
+            time: Decimal::new(7698, 2),
+            proof: "https://clips.twitch.tv/Example".into(),
+            yt_id: None,
+            proof_host: ProofHost::Other,
+            verified: true,
+            is_pb: true,
+            is_wr: true,
+            created_at: date(1_759_000_000),
+        }
+    }
+
+    fn activity(user_id: i64, username: &str) -> Activity {
+        Activity {
+            id: 0,
+            user_id,
+            username: username.into(),
+            rank_id: Some(9),
+            patch: Some("2.13".into()),
+            layout: Some("1".into()),
+            category: Some("Standard".into()),
+            title_old: None,
+            title_new: None,
+            rank_old: None,
+            rank_new: None,
+            created_at: date(1_760_000_000),
+        }
+    }
+
+    /// A promotion on a leaderboard and a demotion in the combined ranking.
+    pub fn titles() -> [Activity; 2] {
+        [
+            Activity {
+                title_old: Some(Title::LegendarySurfer),
+                title_new: Some(Title::MythicSurfer),
+                ..activity(3, "charlie")
+            },
+            Activity {
+                layout: None,
+                category: None,
+                title_old: Some(Title::TopOne),
+                title_new: Some(Title::MythicSurfer),
+                ..activity(1, "alpha")
+            },
+        ]
+    }
+
+    /// A climb to the top and the users it pushed down, best rank first.
+    pub fn ranks() -> [Activity; 3] {
+        let change = |user_id, username, rank_old, rank_new| Activity {
+            rank_old: Some(rank_old),
+            rank_new: Some(rank_new),
+            ..activity(user_id, username)
+        };
+        [
+            change(3, "charlie", 5, 1),
+            change(1, "alpha", 1, 2),
+            change(2, "bravo", 2, 3),
+        ]
+    }
+
+    pub fn join() -> Activity {
+        activity(3, "charlie")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_pb() {
+        let [pb, _] = samples::runs();
+        let templates = Templates::defaults();
+        insta::assert_json_snapshot!(templates.run(&pb, Some(&samples::old_run())).unwrap());
+        insta::assert_json_snapshot!("renders_first_pb", templates.run(&pb, None).unwrap());
+    }
+
+    #[test]
+    fn renders_wr() {
+        let [_, wr] = samples::runs();
+        insta::assert_json_snapshot!(Templates::defaults().run(&wr, Some(&samples::old_run())).unwrap());
+    }
+
+    #[test]
+    fn renders_title() {
+        let [promotion, demotion] = samples::titles();
+        let templates = Templates::defaults();
+        insta::assert_json_snapshot!(templates.title(&promotion).unwrap());
+        insta::assert_json_snapshot!("renders_combined_title", templates.title(&demotion).unwrap());
+    }
+
+    #[test]
+    fn renders_rank() {
+        let templates = Templates::defaults();
+        insta::assert_json_snapshot!(templates.rank(&samples::ranks()).unwrap());
+        // Nobody climbed
+        insta::assert_json_snapshot!("renders_rank_drops", templates.rank(&samples::ranks()[1..]).unwrap());
+        assert!(templates.rank(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn renders_join() {
+        insta::assert_json_snapshot!(Templates::defaults().join(&samples::join()).unwrap());
+    }
+
+    #[test]
+    fn custom_templates_replace_defaults() {
+        let mut templates = Templates::defaults();
+        let file = r##"
+            color = "#ffcd00"
+            title = "{{user}} joined"
+            url = "{{user_url}}"
+        "##;
+        templates.add(Event::Join, file).unwrap();
+        templates.check().unwrap();
+        let [message] = &templates.join(&samples::join()).unwrap()[..] else {
+            panic!("Expected one message");
+        };
+        assert_eq!(message.embeds[0].color, 0xffcd00);
+        assert_eq!(
+            message.embeds[0].url.as_deref(),
+            Some("https://lucio.surf/user/3/leaderboard")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_variables() {
+        let mut templates = Templates::defaults();
+        let misspelled = r#"
+            color = "0"
+            title = "{{username}} joined"
+        "#;
+        templates.add(Event::Join, misspelled).unwrap();
+        assert!(templates.check().is_err());
+
+        let unclosed = r#"
+            color = "0"
+            title = "{{#if user}}"
+        "#;
+        assert!(templates.add(Event::Join, unclosed).is_err());
+    }
+}
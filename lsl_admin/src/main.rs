@@ -0,0 +1,193 @@
+use std::{collections::BTreeMap, env, fs, process::exit};
+
+use server::{
+    auth::ssr::{connect_to_database, verify_database},
+    ranking::recompute_rankings,
+};
+use sqlx::{PgConnection, prelude::FromRow};
+use types::{api::Title, rating::RatingParams};
+
+const USAGE: &str = "Usage: lsl-admin recompute <patch> [options]
+
+Recomputes pb and wr flags, points, ratings, ranks and titles of a patch from its runs
+and prints every rank that changes. Connects with the same PG_* variables as the site.
+
+Options:
+  --layout <layout>       only recompute the category rankings of this layout
+  --category <category>   only recompute the rankings of this category
+  --params <file>         preview the rating parameters in a JSON file, requires --dry-run
+  --dry-run               roll back instead of writing the new ranks";
+
+struct Args {
+    patch: String,
+    layout: Option<String>,
+    category: Option<String>,
+    params: Option<String>,
+    dry_run: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    if args.next().as_deref() != Some("recompute") {
+        return Err(USAGE.into());
+    }
+    let (mut patch, mut layout, mut category, mut params, mut dry_run) = (None, None, None, None, false);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--layout" => layout = Some(value()?),
+            "--category" => category = Some(value()?),
+            "--params" => params = Some(value()?),
+            "--dry-run" => dry_run = true,
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}\n\n{USAGE}")),
+            _ if patch.is_none() => patch = Some(arg),
+            _ => return Err(format!("Unexpected argument {arg}\n\n{USAGE}")),
+        }
+    }
+    // The site always ranks with the default parameters, so others would be undone by the next submit.
+    if params.is_some() && !dry_run {
+        return Err("--params can only be previewed with --dry-run, adopt them in RatingParams::default".into());
+    }
+    Ok(Args {
+        patch: patch.ok_or(USAGE)?,
+        layout,
+        category,
+        params,
+        dry_run,
+    })
+}
+
+#[derive(Clone, FromRow)]
+struct StoredRank {
+    name: String,
+    layout: Option<String>,
+    category: Option<String>,
+    rank: i32,
+    title: Title,
+    rating: f64,
+}
+
+/// Ranks of a patch by leaderboard and user name.
+async fn ranks(conn: &mut PgConnection, patch: &str) -> BTreeMap<(String, String), StoredRank> {
+    sqlx::query_as::<_, StoredRank>(
+        r#"SELECT u.name, r.layout, r.category, r.rank, r.title, r.rating
+        FROM rank r
+        INNER JOIN "user" u ON r.user_id = u.id
+        WHERE r.patch = $1;"#,
+    )
+    .bind(patch)
+    .fetch_all(conn)
+    .await
+    .expect("Failed to read ranks")
+    .into_iter()
+    .map(|r| ((leaderboard(&r), r.name.clone()), r))
+    .collect()
+}
+
+fn leaderboard(rank: &StoredRank) -> String {
+    match (&rank.layout, &rank.category) {
+        (Some(layout), Some(category)) => format!("Layout {layout} {category}"),
+        _ => "Overall".into(),
+    }
+}
+
+/// Prints one line per added, removed or changed rank and returns how many there were.
+fn print_diff(
+    before: &BTreeMap<(String, String), StoredRank>,
+    after: &BTreeMap<(String, String), StoredRank>,
+) -> usize {
+    let mut changes = vec![];
+    for (key, new) in after {
+        match before.get(key) {
+            None => changes.push(('+', key, None, Some(new))),
+            Some(old) if old.rank != new.rank || old.title != new.title || (old.rating - new.rating).abs() >= 0.005 => {
+                changes.push(('~', key, Some(old), Some(new)))
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, old) in before {
+        if !after.contains_key(key) {
+            changes.push(('-', key, Some(old), None));
+        }
+    }
+    changes.sort_by_key(|(_, key, old, new)| (key.0.clone(), new.or(*old).map(|r| r.rank)));
+
+    let show = |old: Option<String>, new: Option<String>| match (old, new) {
+        (Some(old), Some(new)) if old == new => old,
+        (Some(old), Some(new)) => format!("{old} -> {new}"),
+        (old, new) => old.or(new).unwrap_or_default(),
+    };
+    for (sign, (board, name), old, new) in &changes {
+        println!(
+            "{sign} {board:<24} {name:<24} {:<12} {:<36} {}",
+            show(old.map(|r| format!("#{}", r.rank)), new.map(|r| format!("#{}", r.rank))),
+            show(old.map(|r| r.title.to_string()), new.map(|r| r.title.to_string())),
+            show(
+                old.map(|r| format!("{:.2}", r.rating)),
+                new.map(|r| format!("{:.2}", r.rating))
+            ),
+        );
+    }
+    changes.len()
+}
+
+#[tokio::main]
+async fn main() {
+    simple_logger::init_with_env().expect("couldn't initialize logging");
+
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(2);
+    });
+    let params = match &args.params {
+        Some(path) => {
+            let file = fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("Couldn't read {path}: {e}");
+                exit(1);
+            });
+            serde_json::from_str::<RatingParams>(&file).unwrap_or_else(|e| {
+                eprintln!("Invalid rating parameters in {path}: {e}");
+                exit(1);
+            })
+        }
+        None => RatingParams::default(),
+    };
+
+    let pool = connect_to_database().await;
+    verify_database(&pool).await;
+    let mut tx = pool.begin().await.expect("Failed to start transaction");
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM patch WHERE name = $1);")
+        .bind(&args.patch)
+        .fetch_one(&mut *tx)
+        .await
+        .expect("Failed to read patches");
+    if !exists {
+        eprintln!("Unknown patch {}", args.patch);
+        exit(1);
+    }
+
+    let before = ranks(&mut tx, &args.patch).await;
+    if let Err(e) = recompute_rankings(
+        &mut tx,
+        &params,
+        &args.patch,
+        args.layout.as_deref(),
+        args.category.as_deref(),
+    )
+    .await
+    {
+        eprintln!("Recompute failed: {e}");
+        exit(1);
+    }
+    let after = ranks(&mut tx, &args.patch).await;
+    let changed = print_diff(&before, &after);
+
+    if args.dry_run {
+        tx.rollback().await.expect("Failed to roll back");
+        println!("{changed} of {} ranks would change, nothing was written.", after.len());
+    } else {
+        tx.commit().await.expect("Failed to commit");
+        println!("{changed} of {} ranks changed.", after.len());
+    }
+}
@@ -0,0 +1,31 @@
+-- Enum types shared by the permission and rank tables.
+-- Wrapped in DO blocks so the baseline can be applied to databases that
+-- predate versioned migrations.
+DO $$ BEGIN
+	CREATE TYPE permissions AS ENUM (
+		'View',
+		'Submit',
+		'Trusted',
+		'Delete',
+		'Verify',
+		'ManageRuns',
+		'ManageUsers',
+		'Administrator'
+	);
+EXCEPTION
+	WHEN duplicate_object THEN NULL;
+END $$;
+
+DO $$ BEGIN
+	CREATE TYPE title AS ENUM (
+		'None',
+		'Surfer',
+		'SuperSurfer',
+		'EpicSurfer',
+		'LegendarySurfer',
+		'MythicSurfer',
+		'TopOne'
+	);
+EXCEPTION
+	WHEN duplicate_object THEN NULL;
+END $$;
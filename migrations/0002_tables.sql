@@ -0,0 +1,93 @@
+-- Core tables. Sections are maintained by hand, every other table is written
+-- by the site or the triggers defined in later migrations.
+CREATE TABLE IF NOT EXISTS "user" (
+	id bigint GENERATED ALWAYS AS IDENTITY,
+	name character varying(32) NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	password character varying(512) NOT NULL,
+	pfp character varying(512) DEFAULT 'default' NOT NULL,
+	bio character varying(2048),
+	CONSTRAINT user_pkey PRIMARY KEY (id),
+	CONSTRAINT unique_user UNIQUE (name)
+);
+
+CREATE TABLE IF NOT EXISTS permission (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	user_id bigint NOT NULL,
+	token permissions NOT NULL,
+	CONSTRAINT permission_pkey PRIMARY KEY (id),
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS session (
+	id character varying(128) NOT NULL,
+	expires bigint,
+	session text NOT NULL,
+	CONSTRAINT session_pkey PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS discord (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	user_id bigint NOT NULL,
+	snowflake character varying(32) NOT NULL,
+	name character varying(32) NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	access character varying(2048) NOT NULL,
+	refresh character varying(512) NOT NULL,
+	expires_at timestamp with time zone NOT NULL,
+	CONSTRAINT discord_pkey PRIMARY KEY (id),
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS section (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	patch character varying(128) NOT NULL,
+	layout character varying(128) NOT NULL,
+	category character varying(128) NOT NULL,
+	map character varying(128) NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	code character(4) NOT NULL,
+	CONSTRAINT section_pkey PRIMARY KEY (id)
+);
+
+CREATE TABLE IF NOT EXISTS rank (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	user_id bigint NOT NULL,
+	patch character varying(128) NOT NULL,
+	layout character varying(128),
+	category character varying(128),
+	title title NOT NULL,
+	rank integer NOT NULL,
+	rating double precision NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	updated_at timestamp with time zone DEFAULT now() NOT NULL,
+	percentage double precision NOT NULL,
+	CONSTRAINT rank_pkey PRIMARY KEY (id),
+	CONSTRAINT unique_title UNIQUE NULLS NOT DISTINCT (patch, layout, category, user_id),
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id)
+);
+-- Average points of the pbs counted towards the rank.
+ALTER TABLE rank ADD COLUMN IF NOT EXISTS points double precision DEFAULT 0.0 NOT NULL;
+
+CREATE INDEX IF NOT EXISTS rank_rating ON rank (rating);
+CREATE INDEX IF NOT EXISTS rank_section ON rank (patch, layout, category);
+CREATE INDEX IF NOT EXISTS rank_user_id_index ON rank (user_id);
+
+CREATE TABLE IF NOT EXISTS activity (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	user_id bigint NOT NULL,
+	rank_id integer,
+	title_old title,
+	title_new title,
+	rank_old integer,
+	rank_new integer,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	CONSTRAINT activity_pkey PRIMARY KEY (id),
+	CONSTRAINT either_of CHECK ((title_old IS NULL) OR (rank_old IS NULL)),
+	CONSTRAINT full_rank CHECK ((rank_old IS NULL) = (rank_new IS NULL)),
+	CONSTRAINT full_title CHECK ((title_old IS NULL) = (title_new IS NULL)),
+	CONSTRAINT rank_id FOREIGN KEY (rank_id) REFERENCES rank(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS activity_created_at_index ON activity (created_at);
@@ -0,0 +1,29 @@
+-- Runs are partitioned by section id, one partition per game patch.
+-- The newest patch lives in the default partition until the next patch
+-- gets its own sections.
+CREATE TABLE IF NOT EXISTS run (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	section_id integer NOT NULL,
+	user_id bigint NOT NULL,
+	proof character varying(256) NOT NULL,
+	verified boolean DEFAULT false NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	is_pb boolean NOT NULL,
+	is_wr boolean NOT NULL,
+	points real,
+	yt_id character(11),
+	"time" numeric(8,3) NOT NULL,
+	CONSTRAINT id PRIMARY KEY (id, section_id),
+	CONSTRAINT section_id FOREIGN KEY (section_id) REFERENCES section(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE CASCADE
+) PARTITION BY RANGE (section_id);
+
+CREATE INDEX IF NOT EXISTS run_created_at_index ON run (created_at);
+CREATE INDEX IF NOT EXISTS run_section_index ON run (section_id);
+CREATE INDEX IF NOT EXISTS run_user_id_index ON run (user_id);
+
+CREATE TABLE IF NOT EXISTS patch_1_00 PARTITION OF run FOR VALUES FROM (1) TO (125);
+CREATE TABLE IF NOT EXISTS patch_1_41 PARTITION OF run FOR VALUES FROM (125) TO (373);
+CREATE TABLE IF NOT EXISTS patch_1_50 PARTITION OF run FOR VALUES FROM (373) TO (683);
+CREATE TABLE IF NOT EXISTS patch_2_00 PARTITION OF run FOR VALUES FROM (683) TO (1093);
+CREATE TABLE IF NOT EXISTS patch_2_13 PARTITION OF run DEFAULT;
@@ -0,0 +1,408 @@
+-- Ranking maintenance. `run_submit` decides pb/wr state and points of a new
+-- run, `run_submit_ranks` and `run_remove` keep the rank table in sync and
+-- `update_rank` re-derives rank positions and titles for one leaderboard.
+CREATE OR REPLACE PROCEDURE update_rank(IN patch character varying, IN layout character varying, IN category character varying)
+    LANGUAGE sql
+    AS $_$WITH ra AS (SELECT rank() OVER (ORDER BY rating DESC, updated_at ASC) AS rank, id
+	FROM rank r2
+	WHERE r2.patch IS NOT DISTINCT FROM $1 
+		AND r2.layout IS NOT DISTINCT FROM $2 
+		AND r2.category IS NOT DISTINCT FROM $3)
+UPDATE rank r
+SET rank = (SELECT ra.rank FROM ra WHERE ra.id = r.id),
+	title = (SELECT CASE
+		WHEN (SELECT ra.rank FROM ra WHERE ra.id = r.id) = 1 THEN 'TopOne'::title
+		WHEN r.rating < 1500 THEN 'None'::title
+		WHEN r.rating < 3000 THEN 'Surfer'::title
+		WHEN r.rating < 5000 THEN 'SuperSurfer'::title
+		WHEN r.rating < 7500 THEN 'EpicSurfer'::title
+		WHEN r.rating < 9000 THEN 'LegendarySurfer'::title
+		ELSE 'MythicSurfer'::title 
+	END)
+WHERE r.patch IS NOT DISTINCT FROM $1 
+	AND r.layout IS NOT DISTINCT FROM $2 
+	AND r.category IS NOT DISTINCT FROM $3;$_$;
+
+CREATE OR REPLACE FUNCTION run_submit() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$DECLARE
+	wr record;
+	pb record;
+BEGIN
+	SELECT id, time, created_at
+	INTO pb
+	FROM run
+	WHERE section_id = NEW.section_id
+		AND user_id = NEW.user_id
+		AND is_pb = true
+	LIMIT 1;
+	if found and pb.time <= NEW.time then
+		NEW.is_wr = false;
+		NEW.is_pb = false;
+		NEW.points = NULL;
+		return NEW;
+	end if;
+	NEW.is_pb = true;
+	-- Don't do work if it isn't needed.
+	if found then
+		UPDATE run
+		SET is_pb = false, points = NULL
+		WHERE id = pb.id;
+	end if;
+	SELECT id, time, created_at
+	INTO wr
+	FROM run
+	WHERE section_id = NEW.section_id AND is_wr = true
+	LIMIT 1;
+	if found and wr.time <= NEW.time then
+		NEW.points = GREATEST(3.0 - 2 * NEW.time::double precision / wr.time::double precision, 0.0);
+		NEW.is_wr = false;
+		return NEW;
+	end if;
+	NEW.is_wr = true;
+	NEW.points = 1.0;
+	-- Don't do work if it isn't needed.
+	if found then
+		UPDATE run
+		SET is_wr = false
+		WHERE id = wr.id;
+		UPDATE run
+		SET points = GREATEST(3.0 - 2 * time::double precision / NEW.time::double precision, 0.0)
+		WHERE section_id = NEW.section_id AND is_pb = true;
+	end if;
+	return NEW;
+END;$$;
+
+CREATE OR REPLACE FUNCTION run_submit_ranks() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$DECLARE
+	submits integer;
+	ra record;
+	se record;
+BEGIN
+	-- Don't do unneeded work.
+	if NOT NEW.is_pb then
+		RETURN NULL;
+	end if;
+	-- Get section of run.
+	SELECT patch, layout, category FROM section INTO se
+	WHERE id = NEW.section_id;
+	-- Add new rank if first submit.
+	SELECT COUNT(id) FROM run INTO submits 
+	WHERE section_id = NEW.section_id AND user_id = NEW.user_id;
+	if submits = 1 then
+		INSERT INTO rank (user_id, patch, layout, category, title, rank, rating, percentage, created_at, updated_at)
+		VALUES (NEW.user_id, se.patch, se.layout, se.category, 'None', (SELECT COUNT(id) 
+			FROM rank WHERE patch = se.patch AND layout = se.layout AND category = se.category) + 1,
+			0.0, 0.0, NEW.created_at, NEW.created_at),
+			(NEW.user_id, se.patch, NULL, NULL, 'None', (SELECT COUNT(id) 
+			FROM rank WHERE patch = se.patch AND layout IS NULL AND category IS NULL) + 1,
+			0.0, 0.0, NEW.created_at, NEW.created_at)
+		ON CONFLICT DO NOTHING;
+		-- Update percentage of submitted maps.
+		UPDATE rank
+		SET percentage = (SELECT COUNT(DISTINCT section_id)
+			FROM run r
+			JOIN section s ON section_id = s.id
+			WHERE patch = se.patch AND layout = se.layout 
+				AND category = se.category 
+				AND user_id = NEW.user_id)::double precision / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch AND layout = se.layout 
+				AND category = se.category)::double precision
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = NEW.user_id;
+		UPDATE rank
+		SET percentage = (SELECT COUNT(DISTINCT section_id) 
+			FROM run r
+			JOIN section s ON section_id = s.id
+			WHERE patch = se.patch AND user_id = NEW.user_id) / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch)::double precision
+		WHERE patch = se.patch AND layout IS NULL 
+			AND category IS NULL AND user_id = NEW.user_id;
+	end if;
+	-- When pb update only user rating.
+	if NOT NEW.is_wr then
+		-- Update category rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = NEW.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category AND user_id = NEW.user_id)
+		UPDATE rank
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126 
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+		-- Update overall rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout IS NULL
+			AND category IS NULL AND user_id = NEW.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND user_id = NEW.user_id)
+		UPDATE rank
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+	-- When wr update all ranks.
+	else
+		-- Update category ratings.
+		WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout = se.layout AND category = se.category;
+		-- Update overall ratings.
+		WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout IS NULL 
+					AND category IS NULL),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout IS NULL AND category IS NULL;
+	end if;
+	-- Update ranks and titles
+	CALL update_rank(se.patch, se.layout, se.category);
+	CALL update_rank(se.patch, NULL, NULL);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION run_remove() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$DECLARE
+	submits integer;
+	rank_count integer;
+	se record;
+	ra record;
+	wr record;
+	pb record;
+	perce double precision;
+	found_wr boolean = false;
+BEGIN
+	if NOT OLD.is_pb then
+		RETURN OLD;
+	end if;
+	SELECT patch, layout, category FROM section INTO se
+	WHERE id = OLD.section_id;
+	SELECT COUNT(DISTINCT map) 
+	FROM run 
+	JOIN section ON section_id = section.id
+	INTO submits 
+	WHERE patch = se.patch AND layout = se.layout 
+		AND category = se.category AND user_id = OLD.user_id;
+	-- Update percentage on user rank.
+	if submits = 0 then
+		DELETE FROM rank
+		WHERE patch = se.patch AND layout = se.layout 
+		AND category = se.category AND user_id = OLD.user_id;
+	else
+		UPDATE rank
+		SET percentage = submits::double precision / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch AND layout = se.layout 
+				AND category = se.category)::double precision
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = OLD.user_id
+		RETURNING percentage INTO perce;
+	end if;
+	SELECT COUNT(id)
+	FROM rank
+	INTO rank_count
+	WHERE patch = se.patch AND user_id = OLD.user_id;
+	if rank_count = 1 then
+		DELETE FROM rank
+		WHERE patch = se.patch AND layout IS NULL 
+			AND category IS NULL AND user_id = OLD.user_id;
+	else
+		UPDATE rank
+		SET percentage = (SELECT COUNT(DISTINCT section_id) 
+			FROM run r
+			JOIN section s ON section_id = s.id
+			WHERE patch = se.patch AND user_id = OLD.user_id) / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch)::double precision
+		WHERE patch = se.patch AND layout IS NULL 
+			AND category IS NULL AND user_id = OLD.user_id;
+	end if;
+	-- Old run was a wr.
+	if OLD.is_wr then
+		-- Find current wr.
+		SELECT id, time, created_at
+		INTO wr
+		FROM run
+		WHERE section_id = OLD.section_id
+		ORDER BY time ASC, created_at ASC
+		LIMIT 1;
+		if found then
+			found_wr = true;
+			-- Set new wr.
+			UPDATE run
+			SET is_wr = true
+			WHERE id = wr.id;
+			-- Set new pb for user from deleted run.
+			SELECT id, time, created_at
+			INTO pb
+			FROM run
+			WHERE section_id = OLD.section_id
+				AND user_id = OLD.user_id
+			ORDER BY time ASC, created_at ASC
+			LIMIT 1;
+			if found then
+				UPDATE run
+				SET is_pb = true
+				WHERE id = pb.id;
+			end if;
+			-- Calculate points for all pbs.
+			UPDATE run
+			SET points = GREATEST(3.0 - 2.0 * time::double precision / wr.time::double precision, 0.0)
+			WHERE section_id = OLD.section_id AND is_pb = true;
+			WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout = se.layout AND category = se.category;
+		-- Update overall ratings.
+		WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout IS NULL 
+					AND category IS NULL),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout IS NULL AND category IS NULL;
+		end if;
+	-- Old run was pb.
+	else
+	-- Find current pb.
+		SELECT id, time, created_at
+		INTO pb
+		FROM run
+		WHERE section_id = OLD.section_id
+			AND user_id = OLD.user_id
+		ORDER BY time ASC, created_at ASC
+		LIMIT 1;
+		-- Get current wr
+		if found then
+			SELECT id, time, created_at
+			INTO wr
+			FROM run
+			WHERE section_id = OLD.section_id
+				AND is_wr = true;
+			-- Set current pb as pb.
+			UPDATE run
+			SET is_pb = true, points = GREATEST(3.0 - 2.0 * time::double precision / wr.time::double precision, 0.0)
+			WHERE id = pb.id;
+		end if;
+		-- Update category rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = OLD.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category AND user_id = OLD.user_id)
+		UPDATE rank
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126 
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+		-- Update overall rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout IS NULL
+			AND category IS NULL AND user_id = OLD.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND user_id = OLD.user_id)
+		UPDATE rank
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+	end if;
+	CALL update_rank(se.patch, se.layout, se.category);
+	CALL update_rank(se.patch, NULL, NULL);
+	return OLD;
+END;
+$$;
+
+CREATE OR REPLACE TRIGGER run_insert BEFORE INSERT ON run FOR EACH ROW EXECUTE FUNCTION run_submit();
+CREATE OR REPLACE TRIGGER run_insert_ranks AFTER INSERT ON run FOR EACH ROW EXECUTE FUNCTION run_submit_ranks();
+CREATE OR REPLACE TRIGGER run_delete AFTER DELETE ON run FOR EACH ROW EXECUTE FUNCTION run_remove();
@@ -0,0 +1,49 @@
+-- Activity feed and NOTIFY channels consumed by the discord bridge.
+CREATE OR REPLACE FUNCTION activity_rank_update() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	if OLD.title IS NOT NULL AND NEW.title IS NOT NULL AND OLD.title <> NEW.title then
+		INSERT INTO activity (user_id, rank_id, title_old, title_new, created_at)
+		VALUES (OLD.user_id, OLD.id, OLD.title, NEW.title, NEW.updated_at);
+	end if;
+	if OLD.rank IS NOT NULL AND NEW.rank IS NOT NULL AND OLD.rank <> NEW.rank then
+		INSERT INTO activity (user_id, rank_id, rank_old, rank_new, created_at)
+		VALUES (OLD.user_id, OLD.id, OLD.rank, NEW.rank, NEW.updated_at);
+	end if;
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION activity_user_add() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	INSERT INTO activity (user_id)
+	VALUES (NEW.id);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION activity_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	PERFORM pg_notify('activity', NEW.id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION submit_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	PERFORM pg_notify('submit', NEW.id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION discord_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	PERFORM pg_notify('discord', NEW.id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE TRIGGER rank_update AFTER UPDATE OF title, rank ON rank FOR EACH ROW EXECUTE FUNCTION activity_rank_update();
+CREATE OR REPLACE TRIGGER user_insert AFTER INSERT ON "user" FOR EACH ROW EXECUTE FUNCTION activity_user_add();
+CREATE OR REPLACE TRIGGER activity_insert AFTER INSERT ON activity FOR EACH ROW EXECUTE FUNCTION activity_notify();
+CREATE OR REPLACE TRIGGER run_insert_notify AFTER INSERT ON run FOR EACH ROW EXECUTE FUNCTION submit_notify();
+CREATE OR REPLACE TRIGGER discord_insert AFTER INSERT ON discord FOR EACH ROW EXECUTE FUNCTION discord_notify();
@@ -0,0 +1,27 @@
+-- Game patches. Exactly one patch is flagged as current, it receives new
+-- submissions and is the default everywhere a patch isn't given explicitly.
+CREATE TABLE patch (
+	name character varying(128) NOT NULL,
+	display_name character varying(128) NOT NULL,
+	released_at timestamp with time zone DEFAULT now() NOT NULL,
+	current boolean DEFAULT false NOT NULL,
+	CONSTRAINT patch_pkey PRIMARY KEY (name)
+);
+
+CREATE UNIQUE INDEX patch_current ON patch (current) WHERE current;
+
+INSERT INTO patch (name, display_name, released_at, current)
+SELECT p.name, 'Patch ' || p.name, COALESCE(MIN(s.created_at), now()), p.name = '2.13'
+FROM (VALUES ('1.00'), ('1.41'), ('1.50'), ('2.00'), ('2.13')) AS p(name)
+LEFT JOIN section s ON s.patch = p.name
+GROUP BY p.name;
+
+-- Catch patches that were added to the section table by hand.
+INSERT INTO patch (name, display_name, released_at)
+SELECT patch, 'Patch ' || patch, MIN(created_at)
+FROM section
+GROUP BY patch
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE section
+	ADD CONSTRAINT patch FOREIGN KEY (patch) REFERENCES patch(name) ON UPDATE CASCADE;
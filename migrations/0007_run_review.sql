@@ -0,0 +1,346 @@
+-- Moderator review of runs. A rejected run keeps its row so the submitter
+-- can read the reason, but no longer counts towards pbs, wrs and rankings.
+ALTER TABLE run
+	ADD COLUMN rejected boolean DEFAULT false NOT NULL,
+	ADD COLUMN reason character varying(512),
+	ADD COLUMN reviewed_by bigint,
+	ADD COLUMN reviewed_at timestamp with time zone,
+	ADD CONSTRAINT reviewed_by FOREIGN KEY (reviewed_by) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE SET NULL;
+
+CREATE INDEX run_review_queue_index ON run (created_at) WHERE NOT verified AND NOT rejected;
+
+-- Same as in 0004, ignoring rejected runs.
+CREATE OR REPLACE FUNCTION run_submit_ranks() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$DECLARE
+	submits integer;
+	ra record;
+	se record;
+BEGIN
+	-- Don't do unneeded work.
+	if NOT NEW.is_pb then
+		RETURN NULL;
+	end if;
+	-- Get section of run.
+	SELECT patch, layout, category FROM section INTO se
+	WHERE id = NEW.section_id;
+	-- Add new rank if first submit.
+	SELECT COUNT(id) FROM run INTO submits 
+	WHERE section_id = NEW.section_id AND user_id = NEW.user_id AND NOT rejected;
+	if submits = 1 then
+		INSERT INTO rank (user_id, patch, layout, category, title, rank, rating, percentage, created_at, updated_at)
+		VALUES (NEW.user_id, se.patch, se.layout, se.category, 'None', (SELECT COUNT(id) 
+			FROM rank WHERE patch = se.patch AND layout = se.layout AND category = se.category) + 1,
+			0.0, 0.0, NEW.created_at, NEW.created_at),
+			(NEW.user_id, se.patch, NULL, NULL, 'None', (SELECT COUNT(id) 
+			FROM rank WHERE patch = se.patch AND layout IS NULL AND category IS NULL) + 1,
+			0.0, 0.0, NEW.created_at, NEW.created_at)
+		ON CONFLICT DO NOTHING;
+		-- Update percentage of submitted maps.
+		UPDATE rank
+		SET percentage = (SELECT COUNT(DISTINCT section_id)
+			FROM run r
+			JOIN section s ON section_id = s.id
+			WHERE patch = se.patch AND layout = se.layout 
+				AND category = se.category 
+				AND user_id = NEW.user_id AND NOT rejected)::double precision / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch AND layout = se.layout 
+				AND category = se.category)::double precision
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = NEW.user_id;
+		UPDATE rank
+		SET percentage = (SELECT COUNT(DISTINCT section_id) 
+			FROM run r
+			JOIN section s ON section_id = s.id
+			WHERE patch = se.patch AND user_id = NEW.user_id AND NOT rejected) / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch)::double precision
+		WHERE patch = se.patch AND layout IS NULL 
+			AND category IS NULL AND user_id = NEW.user_id;
+	end if;
+	-- When pb update only user rating.
+	if NOT NEW.is_wr then
+		-- Update category rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = NEW.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category AND user_id = NEW.user_id)
+		UPDATE rank
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126 
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+		-- Update overall rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout IS NULL
+			AND category IS NULL AND user_id = NEW.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND user_id = NEW.user_id)
+		UPDATE rank
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+	-- When wr update all ranks.
+	else
+		-- Update category ratings.
+		WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout = se.layout AND category = se.category;
+		-- Update overall ratings.
+		WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout IS NULL 
+					AND category IS NULL),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = NEW.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout IS NULL AND category IS NULL;
+	end if;
+	-- Update ranks and titles
+	CALL update_rank(se.patch, se.layout, se.category);
+	CALL update_rank(se.patch, NULL, NULL);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION run_remove() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$DECLARE
+	submits integer;
+	rank_count integer;
+	se record;
+	ra record;
+	wr record;
+	pb record;
+	perce double precision;
+	found_wr boolean = false;
+BEGIN
+	if NOT OLD.is_pb then
+		RETURN OLD;
+	end if;
+	SELECT patch, layout, category FROM section INTO se
+	WHERE id = OLD.section_id;
+	SELECT COUNT(DISTINCT map) 
+	FROM run 
+	JOIN section ON section_id = section.id
+	INTO submits 
+	WHERE patch = se.patch AND layout = se.layout 
+		AND category = se.category AND user_id = OLD.user_id AND NOT rejected;
+	-- Update percentage on user rank.
+	if submits = 0 then
+		DELETE FROM rank
+		WHERE patch = se.patch AND layout = se.layout 
+		AND category = se.category AND user_id = OLD.user_id;
+	else
+		UPDATE rank
+		SET percentage = submits::double precision / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch AND layout = se.layout 
+				AND category = se.category)::double precision
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = OLD.user_id
+		RETURNING percentage INTO perce;
+	end if;
+	SELECT COUNT(id)
+	FROM rank
+	INTO rank_count
+	WHERE patch = se.patch AND user_id = OLD.user_id;
+	if rank_count = 1 then
+		DELETE FROM rank
+		WHERE patch = se.patch AND layout IS NULL 
+			AND category IS NULL AND user_id = OLD.user_id;
+	else
+		UPDATE rank
+		SET percentage = (SELECT COUNT(DISTINCT section_id) 
+			FROM run r
+			JOIN section s ON section_id = s.id
+			WHERE patch = se.patch AND user_id = OLD.user_id AND NOT rejected) / 
+			(SELECT COUNT(id) 
+				FROM section 
+				WHERE patch = se.patch)::double precision
+		WHERE patch = se.patch AND layout IS NULL 
+			AND category IS NULL AND user_id = OLD.user_id;
+	end if;
+	-- Old run was a wr.
+	if OLD.is_wr then
+		-- Find current wr.
+		SELECT id, time, created_at
+		INTO wr
+		FROM run
+		WHERE section_id = OLD.section_id AND NOT rejected
+		ORDER BY time ASC, created_at ASC
+		LIMIT 1;
+		if found then
+			found_wr = true;
+			-- Set new wr.
+			UPDATE run
+			SET is_wr = true
+			WHERE id = wr.id;
+			-- Set new pb for user from deleted run.
+			SELECT id, time, created_at
+			INTO pb
+			FROM run
+			WHERE section_id = OLD.section_id
+				AND user_id = OLD.user_id AND NOT rejected
+			ORDER BY time ASC, created_at ASC
+			LIMIT 1;
+			if found then
+				UPDATE run
+				SET is_pb = true
+				WHERE id = pb.id;
+			end if;
+			-- Calculate points for all pbs.
+			UPDATE run
+			SET points = GREATEST(3.0 - 2.0 * time::double precision / wr.time::double precision, 0.0)
+			WHERE section_id = OLD.section_id AND is_pb = true;
+			WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout = se.layout AND category = se.category;
+		-- Update overall ratings.
+		WITH ran AS (SELECT percentage AS p, user_id AS u 
+				FROM rank
+				WHERE patch = se.patch AND layout IS NULL 
+					AND category IS NULL),
+			perc AS (SELECT AVG(points) AS p, user_id AS u
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch
+				GROUP BY user_id)
+		UPDATE rank r
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 
+				* LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.15) / 2.03688192726104 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4)) 
+			* POW(LN((SELECT p FROM perc WHERE u = r.user_id) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + (SELECT p FROM ran WHERE u = r.user_id) / 0.01) / 4.61512051684126 
+				* (1.25 - (SELECT p FROM ran WHERE u = r.user_id) / 4))
+		WHERE patch = se.patch AND layout IS NULL AND category IS NULL;
+		end if;
+	-- Old run was pb.
+	else
+	-- Find current pb.
+		SELECT id, time, created_at
+		INTO pb
+		FROM run
+		WHERE section_id = OLD.section_id
+			AND user_id = OLD.user_id AND NOT rejected
+		ORDER BY time ASC, created_at ASC
+		LIMIT 1;
+		-- Get current wr
+		if found then
+			SELECT id, time, created_at
+			INTO wr
+			FROM run
+			WHERE section_id = OLD.section_id
+				AND is_wr = true;
+			-- Set current pb as pb.
+			UPDATE run
+			SET is_pb = true, points = GREATEST(3.0 - 2.0 * time::double precision / wr.time::double precision, 0.0)
+			WHERE id = pb.id;
+		end if;
+		-- Update category rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout = se.layout 
+			AND category = se.category AND user_id = OLD.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND layout = se.layout 
+					AND category = se.category AND user_id = OLD.user_id)
+		UPDATE rank
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126 
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+		-- Update overall rating.
+		SELECT id, percentage INTO ra
+		FROM rank
+		WHERE patch = se.patch AND layout IS NULL
+			AND category IS NULL AND user_id = OLD.user_id;
+		WITH perc AS (SELECT AVG(points) 
+				FROM run r 
+				JOIN section s ON section_id = s.id
+				WHERE patch = se.patch AND user_id = OLD.user_id)
+		UPDATE rank
+		SET updated_at = OLD.created_at,
+			rating = (2000 + 8000 * LN(1 + ra.percentage / 0.15) / 2.03688192726104 
+				* (1.25 - ra.percentage / 4)) 
+			* POW(LN((SELECT * FROM perc) * (EXP(1) - 1) + 1), 
+				50 - 44 * LN(1 + ra.percentage / 0.01) / 4.61512051684126
+				* (1.25 - ra.percentage / 4))
+		WHERE id = ra.id;
+	end if;
+	CALL update_rank(se.patch, se.layout, se.category);
+	CALL update_rank(se.patch, NULL, NULL);
+	return OLD;
+END;
+$$;
+
+-- Rejecting clears the pb/wr flags of the run in the same update, after which
+-- the rankings are fixed up exactly as if the run was deleted.
+CREATE OR REPLACE TRIGGER run_reject AFTER UPDATE OF rejected ON run FOR EACH ROW
+	WHEN (NEW.rejected AND NOT OLD.rejected) EXECUTE FUNCTION run_remove();
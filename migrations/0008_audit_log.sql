@@ -0,0 +1,31 @@
+-- Record of every mutating action taken through the site. `before` and
+-- `after` hold the changed fields, `target` identifies the changed row.
+CREATE TYPE audit_action AS ENUM (
+	'Register',
+	'UpdateUsername',
+	'UpdatePassword',
+	'UpdateBio',
+	'UpdateAvatar',
+	'Submit',
+	'Approve',
+	'Reject',
+	'Delete',
+	'DiscordLink',
+	'DiscordUnlink'
+);
+
+CREATE TABLE audit_log (
+	id bigint GENERATED ALWAYS AS IDENTITY,
+	actor_id bigint,
+	action audit_action NOT NULL,
+	target character varying(128) NOT NULL,
+	before jsonb,
+	after jsonb,
+	ip inet,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	CONSTRAINT audit_log_pkey PRIMARY KEY (id),
+	CONSTRAINT actor_id FOREIGN KEY (actor_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE SET NULL
+);
+
+CREATE INDEX audit_log_created_at_index ON audit_log (created_at);
+CREATE INDEX audit_log_actor_id_index ON audit_log (actor_id);
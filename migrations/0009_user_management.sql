@@ -0,0 +1,11 @@
+-- Permissions are granted and revoked from the admin page, so every token can
+-- only be held once per user.
+DELETE FROM permission a
+	USING permission b
+	WHERE a.user_id = b.user_id AND a.token = b.token AND a.id > b.id;
+
+ALTER TABLE permission
+	ADD CONSTRAINT unique_permission UNIQUE (user_id, token);
+
+ALTER TYPE audit_action ADD VALUE 'GrantPermission';
+ALTER TYPE audit_action ADD VALUE 'RevokePermission';
@@ -0,0 +1,6 @@
+-- Disabled accounts keep their row so runs and ranks stay on the leaderboard,
+-- but are anonymized and can no longer log in.
+ALTER TABLE "user"
+	ADD COLUMN disabled_at timestamp with time zone;
+
+ALTER TYPE audit_action ADD VALUE 'Disable';
@@ -0,0 +1,15 @@
+-- One-time codes to regain access to an account without the password. Only
+-- argon2 hashes are stored, a code is deleted once it has been used.
+CREATE TABLE recovery_code (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	user_id bigint NOT NULL,
+	code character varying(512) NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	CONSTRAINT recovery_code_pkey PRIMARY KEY (id),
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX recovery_code_user_id_index ON recovery_code (user_id);
+
+ALTER TYPE audit_action ADD VALUE 'GenerateRecoveryCodes';
+ALTER TYPE audit_action ADD VALUE 'Recover';
@@ -0,0 +1,9 @@
+-- Failed login attempts per username and per IP. A key is locked out with
+-- exponential backoff once it has failed too often.
+CREATE TABLE login_attempt (
+	key character varying(128) NOT NULL,
+	failures integer DEFAULT 0 NOT NULL,
+	locked_until timestamp with time zone,
+	updated_at timestamp with time zone DEFAULT now() NOT NULL,
+	CONSTRAINT login_attempt_pkey PRIMARY KEY (key)
+);
@@ -0,0 +1,19 @@
+-- Metadata for the sessions stored by axum_session, which only knows the
+-- session data itself. Revoked sessions are logged out on their next request.
+CREATE TABLE user_session (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	session_id character varying(128) NOT NULL,
+	user_id bigint NOT NULL,
+	user_agent character varying(512),
+	remember boolean DEFAULT false NOT NULL,
+	revoked boolean DEFAULT false NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	last_seen timestamp with time zone DEFAULT now() NOT NULL,
+	CONSTRAINT user_session_pkey PRIMARY KEY (id),
+	CONSTRAINT unique_session UNIQUE (session_id),
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX user_session_user_id_index ON user_session (user_id);
+
+ALTER TYPE audit_action ADD VALUE 'RevokeSessions';
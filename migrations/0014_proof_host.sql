@@ -0,0 +1,9 @@
+-- Proofs can be hosted on other sites than YouTube. `yt_id` keeps the video id
+-- on whichever host `proof_host` names. The host is plain text instead of an
+-- enum so it can be part of the run records aggregated for the leaderboards.
+ALTER TABLE run
+	ALTER COLUMN yt_id TYPE character varying(128),
+	ADD COLUMN proof_host character varying(16) DEFAULT 'Other' NOT NULL,
+	ADD CONSTRAINT proof_host CHECK (proof_host IN ('YouTube', 'Twitch', 'Streamable', 'Other'));
+
+UPDATE run SET proof_host = 'YouTube' WHERE yt_id IS NOT NULL;
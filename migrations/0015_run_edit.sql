@@ -0,0 +1,37 @@
+-- Editing runs. The previous time and proof of every edited run is kept in
+-- `run_history`, and `server::ranking::recalculate_section` re-derives pb/wr
+-- state, points and ratings of the section in the same transaction.
+CREATE TABLE run_history (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	run_id integer NOT NULL,
+	section_id integer NOT NULL,
+	edited_by bigint,
+	"time" numeric(8,3) NOT NULL,
+	proof character varying(256) NOT NULL,
+	yt_id character varying(128),
+	proof_host character varying(16) NOT NULL,
+	edited_at timestamp with time zone DEFAULT now() NOT NULL,
+	CONSTRAINT run_history_pkey PRIMARY KEY (id),
+	CONSTRAINT run_id FOREIGN KEY (run_id, section_id) REFERENCES run(id, section_id) ON UPDATE CASCADE ON DELETE CASCADE,
+	CONSTRAINT edited_by FOREIGN KEY (edited_by) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE SET NULL
+);
+
+CREATE INDEX run_history_run_id_index ON run_history (run_id);
+
+-- Rank changes are logged at the time they happen. `updated_at` of a rank is
+-- the time its rating was achieved, which lies in the past after an edit.
+CREATE OR REPLACE FUNCTION activity_rank_update() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	if OLD.title IS NOT NULL AND NEW.title IS NOT NULL AND OLD.title <> NEW.title then
+		INSERT INTO activity (user_id, rank_id, title_old, title_new)
+		VALUES (OLD.user_id, OLD.id, OLD.title, NEW.title);
+	end if;
+	if OLD.rank IS NOT NULL AND NEW.rank IS NOT NULL AND OLD.rank <> NEW.rank then
+		INSERT INTO activity (user_id, rank_id, rank_old, rank_new)
+		VALUES (OLD.user_id, OLD.id, OLD.rank, NEW.rank);
+	end if;
+	RETURN NULL;
+END;$$;
+
+ALTER TYPE audit_action ADD VALUE 'Edit';
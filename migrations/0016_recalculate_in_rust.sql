@@ -0,0 +1,7 @@
+-- Deletes and rejections recalculate their section with
+-- `server::ranking::recalculate_section` in the same transaction, like edits
+-- already do, which replaces the delete and reject triggers.
+-- Runs deleted by hand need a recalculation afterwards.
+DROP TRIGGER IF EXISTS run_delete ON run;
+DROP TRIGGER IF EXISTS run_reject ON run;
+DROP FUNCTION IF EXISTS run_remove();
@@ -0,0 +1,11 @@
+-- Submits recalculate their section with `server::ranking::recalculate_section`
+-- as well, and ranks and titles are assigned from `types::rating`. This drops
+-- the insert triggers and `update_rank`. Runs inserted by hand start without
+-- pb and wr and need a recalculation afterwards.
+DROP TRIGGER IF EXISTS run_insert ON run;
+DROP TRIGGER IF EXISTS run_insert_ranks ON run;
+DROP FUNCTION IF EXISTS run_submit();
+DROP FUNCTION IF EXISTS run_submit_ranks();
+DROP PROCEDURE IF EXISTS update_rank(character varying, character varying, character varying);
+
+ALTER TABLE run ALTER COLUMN is_pb SET DEFAULT FALSE, ALTER COLUMN is_wr SET DEFAULT FALSE;
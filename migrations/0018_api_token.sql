@@ -0,0 +1,21 @@
+-- Personal access tokens for bots and tools, sent as `Authorization: Bearer`.
+-- Only the SHA-256 of a token is stored, the plain token is shown once on
+-- creation. A request made with a token only has the permissions in `scopes`
+-- that its user still has.
+CREATE TABLE api_token (
+	id integer GENERATED ALWAYS AS IDENTITY,
+	user_id bigint NOT NULL,
+	name character varying(64) NOT NULL,
+	hash bytea NOT NULL,
+	scopes permissions[] NOT NULL,
+	created_at timestamp with time zone DEFAULT now() NOT NULL,
+	last_used_at timestamp with time zone,
+	CONSTRAINT api_token_pkey PRIMARY KEY (id),
+	CONSTRAINT unique_token UNIQUE (hash),
+	CONSTRAINT user_id FOREIGN KEY (user_id) REFERENCES "user"(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX api_token_user_id_index ON api_token (user_id);
+
+ALTER TYPE audit_action ADD VALUE 'CreateToken';
+ALTER TYPE audit_action ADD VALUE 'RevokeToken';
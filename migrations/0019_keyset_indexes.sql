@@ -0,0 +1,8 @@
+-- Runs and activity are paged by their sort key followed by their id, see
+-- `server::pagination`. These replace the indexes on created_at alone.
+DROP INDEX IF EXISTS run_created_at_index;
+CREATE INDEX run_created_at_id_index ON run (created_at, id);
+CREATE INDEX run_time_id_index ON run ("time", id);
+
+DROP INDEX IF EXISTS activity_created_at_index;
+CREATE INDEX activity_created_at_id_index ON activity (created_at, id);
@@ -0,0 +1,52 @@
+-- Durable queue for the discord bridge. The notify triggers write every event to
+-- the outbox as well, so events survive the bridge being down and are retried until
+-- they are delivered or given up on. Inserts wake the bridge on the `outbox` channel.
+CREATE TYPE outbox_status AS ENUM ('Pending', 'Delivered', 'Dead');
+
+CREATE TABLE outbox (
+	id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+	channel character varying(16) NOT NULL,
+	payload integer NOT NULL,
+	status outbox_status NOT NULL DEFAULT 'Pending',
+	attempts integer NOT NULL DEFAULT 0,
+	sent integer NOT NULL DEFAULT 0,
+	next_attempt_at timestamp with time zone NOT NULL DEFAULT now(),
+	last_error text,
+	created_at timestamp with time zone NOT NULL DEFAULT now(),
+	delivered_at timestamp with time zone
+);
+
+CREATE INDEX outbox_pending_index ON outbox (next_attempt_at, id) WHERE status = 'Pending';
+
+CREATE OR REPLACE FUNCTION activity_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	INSERT INTO outbox (channel, payload) VALUES ('activity', NEW.id);
+	PERFORM pg_notify('activity', NEW.id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION submit_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	INSERT INTO outbox (channel, payload) VALUES ('submit', NEW.id);
+	PERFORM pg_notify('submit', NEW.id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION discord_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	INSERT INTO outbox (channel, payload) VALUES ('discord', NEW.id);
+	PERFORM pg_notify('discord', NEW.id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE FUNCTION outbox_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	PERFORM pg_notify('outbox', NEW.id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE TRIGGER outbox_insert AFTER INSERT ON outbox FOR EACH ROW EXECUTE FUNCTION outbox_notify();
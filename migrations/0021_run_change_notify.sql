@@ -0,0 +1,13 @@
+-- Open pages are told about runs that were verified, rejected, edited or deleted on the
+-- `run_change` channel, with the id of their section. New runs notify `submit`.
+CREATE OR REPLACE FUNCTION run_change_notify() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$BEGIN
+	PERFORM pg_notify('run_change', OLD.section_id::text);
+	RETURN NULL;
+END;$$;
+
+CREATE OR REPLACE TRIGGER run_update_notify AFTER UPDATE OF time, proof, verified, rejected ON run FOR EACH ROW
+	WHEN ((OLD.time, OLD.proof, OLD.verified, OLD.rejected) IS DISTINCT FROM (NEW.time, NEW.proof, NEW.verified, NEW.rejected))
+	EXECUTE FUNCTION run_change_notify();
+CREATE OR REPLACE TRIGGER run_delete_notify AFTER DELETE ON run FOR EACH ROW EXECUTE FUNCTION run_change_notify();
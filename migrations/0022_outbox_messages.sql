@@ -0,0 +1,6 @@
+-- The requests of an outbox entry are frozen on its first claim, so retries send the rest
+-- of the same list however the data changed in between. `sent` counts into this list.
+ALTER TABLE outbox ADD COLUMN messages jsonb;
+
+-- Delivered and dead entries are pruned by age
+CREATE INDEX outbox_done_index ON outbox (created_at) WHERE status <> 'Pending';
@@ -0,0 +1,12 @@
+-- Entries queued by the same transaction share its id as `batch`, so the rank changes of
+-- one submission are found together rather than by matching `created_at`. Entries from
+-- before get a negative batch per `created_at`, which no transaction id collides with.
+ALTER TABLE outbox ADD COLUMN batch bigint;
+
+UPDATE outbox o SET batch = -b.n
+FROM (SELECT id, dense_rank() OVER (ORDER BY created_at) AS n FROM outbox) b
+WHERE o.id = b.id;
+
+ALTER TABLE outbox ALTER COLUMN batch SET DEFAULT txid_current(), ALTER COLUMN batch SET NOT NULL;
+
+CREATE INDEX outbox_batch_index ON outbox (channel, batch);
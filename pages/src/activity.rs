@@ -1,13 +1,17 @@
 use chrono::{Local, NaiveDateTime, TimeZone};
-use components::{Collapsible, Filter, Select};
+use components::{Collapsible, Filter, Pager, Select, on_live_event};
 use leptos::prelude::*;
-use leptos_router::{components::A, hooks::use_query_map};
+use leptos_router::hooks::use_query_map;
 use server::api::get_activity;
-use types::api::ActivityFilters;
+use types::{
+    api::{ActivityFilters, LiveEvent, PageQuery},
+    leptos::PatchResource,
+};
 
 #[component]
 pub fn Activity() -> impl IntoView {
     let params = use_query_map();
+    let patches = expect_context::<PatchResource>();
     let filters = Signal::derive(move || {
         params.with(|p| ActivityFilters {
             event: p.get("event").filter(|v| !v.is_empty()),
@@ -35,27 +39,39 @@ pub fn Activity() -> impl IntoView {
             ascending: !p.get("order").filter(|v| !v.is_empty()).is_none_or(|s| s == "desc"),
         })
     });
-    let offset = Signal::derive(move || {
+    let limit = Signal::derive(move || {
         params
-            .get()
-            .get("page")
-            .unwrap_or(String::from("0"))
-            .parse::<i32>()
-            .unwrap()
+            .read()
+            .get("limit")
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(50)
+            .clamp(1, 200)
+    });
+    let page = Signal::derive(move || PageQuery {
+        cursor: params.read().get("cursor"),
+        limit: Some(limit.get()),
+        total: true,
     });
     let activities = Resource::new(
-        move || (filters.get(), offset.get()),
-        move |f| async move { get_activity(f.0, f.1 * 50).await },
+        move || (filters.get(), page.get()),
+        move |f| async move { get_activity(f.0, f.1).await },
     );
-    let last = Signal::derive(move || {
-        let mut last = true;
-        activities.map(|res| {
-            let _ = res.as_ref().inspect(|v| {
-                last = v.len() < 50;
-            });
-        });
-        last
+    on_live_event(move |event| {
+        if matches!(event, LiveEvent::Activity(_)) {
+            activities.refetch();
+        }
+    });
+    let next = Signal::derive(move || {
+        activities
+            .map(|res| res.as_ref().ok().and_then(|p| p.next_cursor.clone()))
+            .flatten()
     });
+    let prev = Signal::derive(move || {
+        activities
+            .map(|res| res.as_ref().ok().and_then(|p| p.prev_cursor.clone()))
+            .flatten()
+    });
+    let total = Signal::derive(move || activities.map(|res| res.as_ref().ok().and_then(|p| p.total)).flatten());
 
     view! {
         <section id="filter-list" class="activity">
@@ -72,6 +88,11 @@ pub fn Activity() -> impl IntoView {
                         selected=1
                         options=[("asc", "Ascending"), ("desc", "Descending")]
                     />
+                    <Select
+                        name="limit"
+                        indicator="Per Page"
+                        options=[("50", "50"), ("25", "25"), ("100", "100")]
+                    />
                     <div class="input-box">
                         <label for="before" class="indicator">
                             "Before"
@@ -107,18 +128,35 @@ pub fn Activity() -> impl IntoView {
                             step="1"
                         />
                     </div>
-                    <Select
-                        name="patch"
-                        indicator="Patch"
-                        options=[
-                            ("", "All"),
-                            ("1.00", "1.00"),
-                            ("1.41", "1.41"),
-                            ("1.50", "1.50"),
-                            ("2.00", "2.00"),
-                            ("2.13", "Current"),
-                        ]
-                    />
+                    <div>
+                        <label for="patch" class="indicator">
+                            "Patch"
+                        </label>
+                        <select class="select" name="patch" id="patch">
+                            <option value="">"All"</option>
+                            <Transition>
+                                {move || {
+                                    patches
+                                        .get()
+                                        .map(|res| {
+                                            res.map(|patches| {
+                                                patches
+                                                    .into_iter()
+                                                    .map(|p| {
+                                                        let value = p.name.clone();
+                                                        view! {
+                                                            <option value=value>
+                                                                {if p.current { "Current".into() } else { p.name }}
+                                                            </option>
+                                                        }
+                                                    })
+                                                    .collect_view()
+                                            })
+                                        })
+                                }}
+                            </Transition>
+                        </select>
+                    </div>
                     <Select
                         name="layout"
                         indicator="Layout"
@@ -149,7 +187,7 @@ pub fn Activity() -> impl IntoView {
                 <span class="heading">"old rank"</span>
                 <span class="heading">"new rank"</span>
                 <div class="divider header"></div>
-                <Suspense fallback=|| { "Fetching Runs" }>
+                <Transition fallback=|| { "Fetching Runs" }>
                     <ErrorBoundary fallback=|_| {
                         view! { <div class="error-display">"You are not logged in"</div> }
                     }>
@@ -158,7 +196,7 @@ pub fn Activity() -> impl IntoView {
                                 .get()
                                 .map(|res| {
                                     res.map(|runs| {
-                                        runs.into_iter()
+                                        runs.data.into_iter()
                                             .map(|r| {
                                                 view! {
                                                     <span>
@@ -218,43 +256,9 @@ pub fn Activity() -> impl IntoView {
                                 })
                         }}
                     </ErrorBoundary>
-                </Suspense>
-            </div>
-            <div class="pages row">
-                <Show
-                    when=move || offset.read() != 0
-                    fallback=|| view! { <div class="arrow disabled">"<"</div> }
-                >
-                    <A
-                        class:arrow=true
-                        href=move || {
-                            let mut map = params.get();
-                            map.replace("page", (offset.get() - 1).to_string());
-                            map.to_query_string()
-                        }
-                    >
-                        "<"
-                    </A>
-                </Show>
-                <div class="page">{move || offset.get() + 1}</div>
-                <Suspense fallback=|| view! { <div class="arrow disabled">">"</div> }>
-                    <Show
-                        when=move || !*last.read()
-                        fallback=|| view! { <div class="arrow disabled">">"</div> }
-                    >
-                        <A
-                            class:arrow=true
-                            href=move || {
-                                let mut map = params.get();
-                                map.replace("page", (offset.get() + 1).to_string());
-                                map.to_query_string()
-                            }
-                        >
-                            ">"
-                        </A>
-                    </Show>
-                </Suspense>
+                </Transition>
             </div>
+            <Pager next prev total limit />
         </section>
     }
 }
@@ -0,0 +1,342 @@
+use components::{Collapsible, Filter, Select};
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::{components::A, hooks::use_query_map};
+use server::admin::{SetPermission, get_audit_log, get_users};
+use types::api::{ApiError, AuditAction, Permissions};
+
+#[component]
+pub fn AuditLog() -> impl IntoView {
+    let params = use_query_map();
+    let actor = Signal::derive(move || params.read().get("actor").and_then(|v| v.parse::<i64>().ok()));
+    let action = Signal::derive(move || params.read().get("action").and_then(|v| v.parse::<AuditAction>().ok()));
+    let offset = Signal::derive(move || {
+        params
+            .get()
+            .get("page")
+            .unwrap_or(String::from("0"))
+            .parse::<i32>()
+            .unwrap_or(0)
+    });
+    let entries = Resource::new(
+        move || (actor.get(), action.get(), offset.get()),
+        move |(actor, action, offset)| get_audit_log(actor, action, offset * 50),
+    );
+    let last = Signal::derive(move || {
+        let mut last = true;
+        entries.map(|res| {
+            let _ = res.as_ref().inspect(|v| last = v.len() < 50);
+        });
+        last
+    });
+
+    view! {
+        <Title text="Audit Log" />
+        <section id="filter-list" class="audit">
+            <Collapsible id="filter" class="filter" header=|| "Show Filters">
+                <Filter attr:class="filter">
+                    <div class="input-box">
+                        <label for="actor" class="indicator">
+                            "User ID"
+                        </label>
+                        <input
+                            class="select"
+                            type="number"
+                            name="actor"
+                            id="actor"
+                            min="1"
+                            step="1"
+                        />
+                    </div>
+                    <Select
+                        name="action"
+                        indicator="Action"
+                        options=[
+                            ("", "All"),
+                            ("Register", "Register"),
+                            ("UpdateUsername", "Username"),
+                            ("UpdatePassword", "Password"),
+                            ("UpdateBio", "Bio"),
+                            ("UpdateAvatar", "Avatar"),
+                            ("Submit", "Submit"),
+                            ("Approve", "Approve"),
+                            ("Reject", "Reject"),
+                            ("Delete", "Delete"),
+                            ("DiscordLink", "Discord Link"),
+                            ("DiscordUnlink", "Discord Unlink"),
+                            ("GrantPermission", "Grant Permission"),
+                            ("RevokePermission", "Revoke Permission"),
+                            ("Disable", "Disable"),
+                            ("GenerateRecoveryCodes", "Recovery Codes"),
+                            ("Recover", "Recover"),
+                            ("RevokeSessions", "Revoke Sessions"),
+                            ("Edit", "Edit"),
+                            ("CreateToken", "Create Token"),
+                            ("RevokeToken", "Revoke Token"),
+                        ]
+                    />
+                </Filter>
+            </Collapsible>
+            <div class="grid">
+                <span class="heading">"date"</span>
+                <span class="heading">"user"</span>
+                <span class="heading">"action"</span>
+                <span class="heading">"target"</span>
+                <span class="heading">"before"</span>
+                <span class="heading">"after"</span>
+                <span class="heading">"ip"</span>
+                <div class="divider header"></div>
+                <Suspense fallback=|| { "Fetching Entries" }>
+                    <ErrorBoundary fallback=|_| {
+                        view! { <div class="error-display">"You are not an administrator"</div> }
+                    }>
+                        {move || {
+                            entries
+                                .get()
+                                .map(|res| {
+                                    res.map(|entries| {
+                                        entries
+                                            .into_iter()
+                                            .map(|e| {
+                                                view! {
+                                                    <span>
+                                                        {format!("{}", e.created_at.format("%d/%m/%Y %H:%M:%S"))}
+                                                    </span>
+                                                    <span>
+                                                        {match (e.actor_id, e.actor) {
+                                                            (Some(id), Some(name)) => {
+                                                                view! {
+                                                                    <A href=format!(
+                                                                        "/user/{id}/leaderboard",
+                                                                    )>{name}</A>
+                                                                }
+                                                                    .into_any()
+                                                            }
+                                                            _ => "Deleted User".into_any(),
+                                                        }}
+                                                    </span>
+                                                    <span>{e.action.to_string()}</span>
+                                                    <span>{e.target}</span>
+                                                    <code>{e.before.map(|v| v.to_string()).unwrap_or("-".into())}</code>
+                                                    <code>{e.after.map(|v| v.to_string()).unwrap_or("-".into())}</code>
+                                                    <span>{e.ip.unwrap_or("-".into())}</span>
+                                                    <div class="divider"></div>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()
+                                    })
+                                })
+                        }}
+                    </ErrorBoundary>
+                </Suspense>
+            </div>
+            <div class="pages row">
+                <Show
+                    when=move || offset.read() != 0
+                    fallback=|| view! { <div class="arrow disabled">"<"</div> }
+                >
+                    <A
+                        class:arrow=true
+                        href=move || {
+                            let mut map = params.get();
+                            map.replace("page", (offset.get() - 1).to_string());
+                            map.to_query_string()
+                        }
+                    >
+                        "<"
+                    </A>
+                </Show>
+                <div class="page">{move || offset.get() + 1}</div>
+                <Suspense fallback=|| view! { <div class="arrow disabled">">"</div> }>
+                    <Show
+                        when=move || !*last.read()
+                        fallback=|| view! { <div class="arrow disabled">">"</div> }
+                    >
+                        <A
+                            class:arrow=true
+                            href=move || {
+                                let mut map = params.get();
+                                map.replace("page", (offset.get() + 1).to_string());
+                                map.to_query_string()
+                            }
+                        >
+                            ">"
+                        </A>
+                    </Show>
+                </Suspense>
+            </div>
+        </section>
+    }
+}
+
+pub(crate) const PERMISSIONS: [Permissions; 8] = [
+    Permissions::View,
+    Permissions::Submit,
+    Permissions::Trusted,
+    Permissions::Delete,
+    Permissions::Verify,
+    Permissions::ManageRuns,
+    Permissions::ManageUsers,
+    Permissions::Administrator,
+];
+
+#[component]
+pub fn ManageUsers() -> impl IntoView {
+    let params = use_query_map();
+    let search = Signal::derive(move || params.read().get("search").filter(|v| !v.is_empty()));
+    let offset = Signal::derive(move || {
+        params
+            .get()
+            .get("page")
+            .unwrap_or(String::from("0"))
+            .parse::<i32>()
+            .unwrap_or(0)
+    });
+    let action = ServerAction::<SetPermission>::new();
+    let users = Resource::new(
+        move || (search.get(), action.version().get(), offset.get()),
+        move |(search, _, offset)| get_users(search, offset * 50),
+    );
+    let last = Signal::derive(move || {
+        let mut last = true;
+        users.map(|res| {
+            let _ = res.as_ref().inspect(|v| last = v.len() < 50);
+        });
+        last
+    });
+
+    view! {
+        <Title text="Manage Users" />
+        <section id="filter-list" class="users">
+            <Filter attr:class="filter">
+                <div class="input-box">
+                    <label for="search" class="indicator">
+                        "Name or ID"
+                    </label>
+                    <input class="select" type="text" name="search" id="search" />
+                </div>
+            </Filter>
+            <ErrorBoundary fallback=|e| {
+                view! {
+                    <span class="error">
+                        {move || {
+                            let e = e.get().into_iter().next().unwrap().1;
+                            if e.is::<ApiError>() {
+                                let e = e.downcast_ref::<ApiError>().unwrap();
+                                match e {
+                                    ApiError::Unauthenticated => "🛈 Please log in",
+                                    ApiError::Unauthorized => "🛈 Missing permission",
+                                    _ => "🛈 Something went wrong. Try again",
+                                }
+                            } else {
+                                "🛈 Something went wrong. Try again"
+                            }
+                        }}
+                    </span>
+                }
+            }>
+                <div class="hidden">{move || action.value().get()}</div>
+            </ErrorBoundary>
+            <div class="grid">
+                <span class="heading">"id"</span>
+                <span class="heading">"user"</span>
+                <span class="heading">"joined"</span>
+                <span class="heading">"permissions"</span>
+                <div class="divider header"></div>
+                <Suspense fallback=|| { "Fetching Users" }>
+                    <ErrorBoundary fallback=|_| {
+                        view! { <div class="error-display">"You are not allowed to manage users"</div> }
+                    }>
+                        {move || {
+                            users
+                                .get()
+                                .map(|res| {
+                                    res.map(|users| {
+                                        users
+                                            .into_iter()
+                                            .map(|u| {
+                                                view! {
+                                                    <span>{u.id}</span>
+                                                    <A href=format!(
+                                                        "/user/{}/leaderboard",
+                                                        u.id,
+                                                    )>{u.username}</A>
+                                                    <span>{format!("{}", u.created_at.format("%d/%m/%Y"))}</span>
+                                                    <div class="permissions">
+                                                        {PERMISSIONS
+                                                            .iter()
+                                                            .map(|p| {
+                                                                let held = u.permissions.contains(p);
+                                                                view! {
+                                                                    <ActionForm action>
+                                                                        <input type="text" name="id" hidden value=u.id />
+                                                                        <input
+                                                                            type="text"
+                                                                            name="token"
+                                                                            hidden
+                                                                            value=p.to_string()
+                                                                        />
+                                                                        <input
+                                                                            type="text"
+                                                                            name="grant"
+                                                                            hidden
+                                                                            value=(!held).to_string()
+                                                                        />
+                                                                        <input
+                                                                            type="submit"
+                                                                            class:held=held
+                                                                            value=p.to_string()
+                                                                        />
+                                                                    </ActionForm>
+                                                                }
+                                                            })
+                                                            .collect_view()}
+                                                    </div>
+                                                    <div class="divider"></div>
+                                                }
+                                            })
+                                            .collect::<Vec<_>>()
+                                    })
+                                })
+                        }}
+                    </ErrorBoundary>
+                </Suspense>
+            </div>
+            <div class="pages row">
+                <Show
+                    when=move || offset.read() != 0
+                    fallback=|| view! { <div class="arrow disabled">"<"</div> }
+                >
+                    <A
+                        class:arrow=true
+                        href=move || {
+                            let mut map = params.get();
+                            map.replace("page", (offset.get() - 1).to_string());
+                            map.to_query_string()
+                        }
+                    >
+                        "<"
+                    </A>
+                </Show>
+                <div class="page">{move || offset.get() + 1}</div>
+                <Suspense fallback=|| view! { <div class="arrow disabled">">"</div> }>
+                    <Show
+                        when=move || !*last.read()
+                        fallback=|| view! { <div class="arrow disabled">">"</div> }
+                    >
+                        <A
+                            class:arrow=true
+                            href=move || {
+                                let mut map = params.get();
+                                map.replace("page", (offset.get() + 1).to_string());
+                                map.to_query_string()
+                            }
+                        >
+                            ">"
+                        </A>
+                    </Show>
+                </Suspense>
+            </div>
+        </section>
+    }
+}
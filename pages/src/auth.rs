@@ -3,15 +3,26 @@ use leptos_meta::Title;
 use leptos_router::{components::A, hooks::use_query_map};
 use server::{
     api::get_maps,
-    auth::{Login, Register, Submit},
+    auth::{DiscordLogin, Login, Recover, Register, Submit},
 };
 use types::api::{ApiError, Map};
 use util::escape_regex;
 use web_sys::Event;
 
+fn lockout_message(secs: i64) -> String {
+    let wait = match secs {
+        ..=1 => "a second".into(),
+        2..60 => format!("{secs} seconds"),
+        60..=60 => "a minute".into(),
+        _ => format!("{} minutes", (secs + 59) / 60),
+    };
+    format!("🛈 Too many failed attempts. Try again in {wait}")
+}
+
 #[component]
 pub fn Login() -> impl IntoView {
     let action = expect_context::<ServerAction<Login>>();
+    let discord = ServerAction::<DiscordLogin>::new();
     let result = Signal::derive(move || action.value().get().unwrap_or(Ok(())));
     let (username, set_username) = signal(String::new());
     let (password, set_password) = signal(String::new());
@@ -28,12 +39,13 @@ pub fn Login() -> impl IntoView {
                                 let e = e.downcast_ref::<ApiError>().unwrap();
                                 match e {
                                     ApiError::InvalidCredentials => {
-                                        "🛈 Incorrect username or password"
+                                        "🛈 Incorrect username or password".into()
                                     }
-                                    _ => "🛈 Something went wrong. Try again",
+                                    ApiError::TooManyAttempts(secs) => lockout_message(*secs),
+                                    _ => "🛈 Something went wrong. Try again".into(),
                                 }
                             } else {
-                                "🛈 Something went wrong. Try again"
+                                "🛈 Something went wrong. Try again".into()
                             }
                         }}
                     </span>
@@ -83,9 +95,109 @@ pub fn Login() -> impl IntoView {
                     <A href="/register" attr:class="link">
                         "Create account"
                     </A>
+                    <A href="/recover" attr:class="link">
+                        "Forgot password?"
+                    </A>
                     <input type="submit" class="button primary" value="Sign In" />
                 </div>
             </ActionForm>
+            <ActionForm action=discord>
+                <input type="submit" class="discord-login" value="Sign in with Discord" />
+            </ActionForm>
+        </section>
+    }
+}
+
+#[component]
+pub fn Recover() -> impl IntoView {
+    let action = expect_context::<ServerAction<Recover>>();
+    let result = Signal::derive(move || action.value().get().unwrap_or(Ok(())));
+    let (password, set_password) = signal(String::new());
+    let (password_rep, set_password_rep) = signal(String::new());
+    view! {
+        <Title text="Recover Account" />
+        <section id="box">
+            <h1>"Recover Account"</h1>
+            <ErrorBoundary fallback=|e| {
+                view! {
+                    <span class="error">
+                        {move || {
+                            let e = e.get().into_iter().next().unwrap().1;
+                            if e.is::<ApiError>() {
+                                let e = e.downcast_ref::<ApiError>().unwrap();
+                                match e {
+                                    ApiError::InvalidCredentials => {
+                                        "🛈 Incorrect username or recovery code".into()
+                                    }
+                                    ApiError::InvalidInput => {
+                                        "🛈 Password must be between 8 and 256 characters long".into()
+                                    }
+                                    ApiError::TooManyAttempts(secs) => lockout_message(*secs),
+                                    _ => "🛈 Something went wrong. Try again".into(),
+                                }
+                            } else {
+                                "🛈 Something went wrong. Try again".into()
+                            }
+                        }}
+                    </span>
+                }
+            }>
+                <div class="hidden">{result}</div>
+            </ErrorBoundary>
+            <ActionForm action=action>
+                <div class="input-box">
+                    <input type="text" name="username" id="username" required />
+                    <label for="username" class="placeholder">
+                        "Username"
+                    </label>
+                </div>
+                <div class="input-box">
+                    <input type="text" name="code" id="code" required autocomplete="off" />
+                    <label for="code" class="placeholder">
+                        "Recovery Code"
+                    </label>
+                </div>
+                <div class="input-box">
+                    <input
+                        type="password"
+                        name="password"
+                        id="password"
+                        required
+                        minlength="8"
+                        maxlength="256"
+                        value=password
+                        on:input=move |e| set_password(event_target_value::<Event>(&e))
+                    />
+                    <label for="password" class="placeholder">
+                        "New Password"
+                    </label>
+                    <label for="password" class="error">
+                        "Password must be between 8 and 256 characters long."
+                    </label>
+                </div>
+                <div class="input-box">
+                    <input
+                        type="password"
+                        id="password-repeat"
+                        required
+                        pattern=move || escape_regex(&password.read())
+                        value=password_rep
+                        on:input=move |e| set_password_rep(event_target_value::<Event>(&e))
+                    />
+                    <label for="password-repeat" class="placeholder">
+                        "Repeat Password"
+                    </label>
+                    <label for="password-repeat" class="error">
+                        "Passwords must match."
+                    </label>
+                </div>
+                <div class="row">
+                    <A href="/login" attr:class="link">
+                        "Back to sign in"
+                    </A>
+                    <input type="submit" class="button primary" value="Recover" />
+                </div>
+            </ActionForm>
         </section>
     }
 }
@@ -193,7 +305,7 @@ pub fn Submit() -> impl IntoView {
     let (code, set_code) = signal(String::new());
     let (time, set_time) = signal(String::new());
     let (map, set_map) = signal(String::new());
-    let (yt, set_yt) = signal(String::new());
+    let (proof, set_proof) = signal(String::new());
     let (maps, set_maps) = signal::<Option<Vec<Map>>>(None);
     view! {
         <Title text="Submit" />
@@ -208,7 +320,7 @@ pub fn Submit() -> impl IntoView {
                                 let e = e.downcast_ref::<ApiError>().unwrap();
                                 match e {
                                     ApiError::InvalidSection => "🛈 Invalid map name",
-                                    ApiError::InvalidYtId => "🛈 YT video id does not exist",
+                                    ApiError::InvalidProof => "🛈 Proof video does not exist",
                                     _ => "🛈 Something went wrong. Try again",
                                 }
                             } else {
@@ -364,20 +476,18 @@ pub fn Submit() -> impl IntoView {
                 <div class="input-box">
                     <input
                         type="text"
-                        name="yt_id"
-                        id="yt_id"
+                        name="proof"
+                        id="proof"
                         required
-                        minlength="11"
-                        maxlength="11"
-                        pattern="[a-zA-Z0-9_\\-]*"
-                        value=yt
-                        on:input=move |e| set_yt(event_target_value::<Event>(&e))
+                        maxlength="256"
+                        value=proof
+                        on:input=move |e| set_proof(event_target_value::<Event>(&e))
                     />
-                    <label for="yt_id" class="placeholder">
-                        "Video ID"
+                    <label for="proof" class="placeholder">
+                        "Proof Link"
                     </label>
-                    <label for="yt_id" class="error">
-                        "Invalid video ID."
+                    <label for="proof" class="error">
+                        "Invalid proof link."
                     </label>
                 </div>
                 <div class="text">"∗ Fields are optional."</div>
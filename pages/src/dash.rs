@@ -1,12 +1,17 @@
 use leptos::{either::Either, html::Input, prelude::*};
 use leptos_meta::Title;
 use leptos_router::components::{A, Outlet};
-use server::auth::{DiscordAdd, DiscordDelete, Logout, UpdateBio, UpdateCreds, discord_list};
+use server::auth::{
+    CreateToken, DisableAccount, DiscordAdd, DiscordDelete, GenerateRecoveryCodes, Logout, LogoutEverywhere,
+    RevokeSession, RevokeToken, UpdateBio, UpdateCreds, discord_list, get_sessions, get_tokens,
+};
 use types::{
-    api::ApiError,
+    api::{ApiError, Permissions},
     leptos::{UpdatePfpAction, UserResource},
 };
 use util::escape_regex;
+
+use crate::admin::PERMISSIONS;
 use wasm_bindgen::JsCast;
 use web_sys::{Event, FormData, HtmlFormElement, SubmitEvent};
 
@@ -93,6 +98,17 @@ pub fn Dashboard() -> impl IntoView {
                             "Manage"
                         </A>
                     </div>
+                    <h3>"SECURITY"</h3>
+                    <div>
+                        <h4>"Recovery Codes"</h4>
+                        <p>
+                            "Recovery codes let you set a new password if you forget your current one. "
+                            "Generating new codes invalidates all previous ones."
+                        </p>
+                        <A attr:class="button secondary" href="recovery">
+                            "Generate"
+                        </A>
+                    </div>
                     <h3>"MANAGEMENT"</h3>
                     <div>
                         <h4>"Log Out"</h4>
@@ -101,13 +117,32 @@ pub fn Dashboard() -> impl IntoView {
                             <input type="submit" class="button secondary" value="Log Out" />
                         </ActionForm>
                     </div>
+                    <div>
+                        <h4>"Sessions"</h4>
+                        <p>"See where your account is logged in and log out other devices."</p>
+                        <A attr:class="button secondary" href="sessions">
+                            "Manage"
+                        </A>
+                    </div>
+                    <div>
+                        <h4>"API Tokens"</h4>
+                        <p>
+                            "Personal access tokens let bots and tools like timers submit and manage "
+                            "runs on your behalf, limited to the permissions you give them."
+                        </p>
+                        <A attr:class="button secondary" href="tokens">
+                            "Manage"
+                        </A>
+                    </div>
                     <div>
                         <h4>"Account Removal"</h4>
                         <p>
                             "Disabling your account will anonymize your account and prevent "
                             "you from logging in. This action will not delete runs from the leaderboard."
                         </p>
-                        <button class="button danger">"Disable Account"</button>
+                        <A attr:class="button danger" href="disable">
+                            "Disable Account"
+                        </A>
                     </div>
                 </div>
                 <div class="section">
@@ -292,6 +327,382 @@ pub fn Password() -> impl IntoView {
     }
 }
 
+#[component]
+pub fn RecoveryCodes() -> impl IntoView {
+    let action = ServerAction::<GenerateRecoveryCodes>::new();
+    let result = Signal::derive(move || action.value().get());
+    view! {
+        <A attr:class="toner" href="../">
+            <div />
+        </A>
+        <section id="box">
+            <h1>"Recovery Codes"</h1>
+            <ErrorBoundary fallback=|e| {
+                view! {
+                    <span class="error">
+                        {move || {
+                            let e = e.get().into_iter().next().unwrap().1;
+                            if e.is::<ApiError>() {
+                                let e = e.downcast_ref::<ApiError>().unwrap();
+                                match e {
+                                    ApiError::InvalidCredentials => "🛈 Incorrect password",
+                                    ApiError::PasswordRequired => "🛈 Set a password first",
+                                    _ => "🛈 Something went wrong. Try again",
+                                }
+                            } else {
+                                "🛈 Something went wrong. Try again"
+                            }
+                        }}
+                    </span>
+                }
+            }>
+                {move || {
+                    result
+                        .get()
+                        .map(|res| {
+                            res.map(|codes| {
+                                view! {
+                                    <p>
+                                        "Store these codes somewhere safe. Each code can be used once "
+                                        "and they will not be shown again."
+                                    </p>
+                                    <ul class="codes">
+                                        {codes.into_iter().map(|c| view! { <li><code>{c}</code></li> }).collect_view()}
+                                    </ul>
+                                }
+                            })
+                        })
+                }}
+            </ErrorBoundary>
+            <Show when=move || !result.with(|r| matches!(r, Some(Ok(_))))>
+                <ActionForm action>
+                    <div class="input-box">
+                        <input type="password" name="password" id="password" required />
+                        <label for="password" class="placeholder">
+                            "Current Password"
+                        </label>
+                    </div>
+                    <div class="row">
+                        <A attr:class="button secondary" href="../">
+                            "Cancel"
+                        </A>
+                        <input type="submit" class="button primary" value="Generate" />
+                    </div>
+                </ActionForm>
+            </Show>
+        </section>
+    }
+}
+
+#[component]
+pub fn Sessions() -> impl IntoView {
+    let revoke = ServerAction::<RevokeSession>::new();
+    let logout_all = expect_context::<ServerAction<LogoutEverywhere>>();
+    let sessions = Resource::new(move || revoke.version().get(), |_| get_sessions());
+    view! {
+        <A attr:class="toner" href="../">
+            <div />
+        </A>
+        <section id="box">
+            <h1>"Sessions"</h1>
+            <Transition fallback=move || {
+                view! { <p>"Loading..."</p> }
+            }>
+                <ErrorBoundary fallback=|_| {
+                    view! { <span class="error">"🛈 Something went wrong. Try again"</span> }
+                }>
+                    <div class="hidden">{move || revoke.value().get()}</div>
+                    {move || {
+                        sessions
+                            .get()
+                            .map(|data| {
+                                data.map(|sessions| {
+                                    sessions
+                                        .into_iter()
+                                        .map(|session| {
+                                            view! {
+                                                <div class="session row">
+                                                    <div class="narrow">
+                                                        <h4>
+                                                            {session
+                                                                .user_agent
+                                                                .unwrap_or("Unknown device".into())}
+                                                        </h4>
+                                                        <p>
+                                                            {format!(
+                                                                "Signed in {}{}",
+                                                                session.created_at.format("%d/%m/%Y %H:%M"),
+                                                                if session.remember { ", remembered" } else { "" },
+                                                            )}
+                                                        </p>
+                                                        <p>
+                                                            {format!(
+                                                                "Last seen {}",
+                                                                session.last_seen.format("%d/%m/%Y %H:%M"),
+                                                            )}
+                                                        </p>
+                                                    </div>
+                                                    {if session.current {
+                                                        Either::Left(view! { <span class="current">"This device"</span> })
+                                                    } else {
+                                                        Either::Right(
+                                                            view! {
+                                                                <ActionForm action=revoke>
+                                                                    <input hidden type="text" name="id" value=session.id />
+                                                                    <input type="submit" class="button danger" value="Revoke" />
+                                                                </ActionForm>
+                                                            },
+                                                        )
+                                                    }}
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()
+                                })
+                            })
+                    }}
+                </ErrorBoundary>
+            </Transition>
+            <div class="row">
+                <A attr:class="button secondary" href="../">
+                    "Back"
+                </A>
+                <ActionForm action=logout_all>
+                    <input type="submit" class="button danger" value="Log Out Everywhere" />
+                </ActionForm>
+            </div>
+        </section>
+    }
+}
+
+#[component]
+pub fn Tokens() -> impl IntoView {
+    let user = expect_context::<UserResource>();
+    let create = ServerAction::<CreateToken>::new();
+    let revoke = ServerAction::<RevokeToken>::new();
+    let tokens = Resource::new(
+        move || (create.version().get(), revoke.version().get()),
+        |_| get_tokens(),
+    );
+    let (name, set_name) = signal(String::new());
+    let scopes = RwSignal::new(Vec::<Permissions>::new());
+    let created = Signal::derive(move || create.value().get());
+    view! {
+        <A attr:class="toner" href="../">
+            <div />
+        </A>
+        <section id="box">
+            <h1>"API Tokens"</h1>
+            <p>"Send a token as an " <code>"Authorization: Bearer"</code> " header to use it."</p>
+            <ErrorBoundary fallback=|e| {
+                view! {
+                    <span class="error">
+                        {move || {
+                            let e = e.get().into_iter().next().unwrap().1;
+                            if e.is::<ApiError>() {
+                                let e = e.downcast_ref::<ApiError>().unwrap();
+                                match e {
+                                    ApiError::InvalidInput => "🛈 Name the token and pick at least one scope",
+                                    ApiError::Unauthorized => "🛈 You can only grant permissions you have",
+                                    _ => "🛈 Something went wrong. Try again",
+                                }
+                            } else {
+                                "🛈 Something went wrong. Try again"
+                            }
+                        }}
+                    </span>
+                }
+            }>
+                {move || {
+                    created
+                        .get()
+                        .map(|res| {
+                            res.map(|token| {
+                                view! {
+                                    <p>"Copy your new token now, it will not be shown again."</p>
+                                    <code class="token">{token}</code>
+                                }
+                            })
+                        })
+                }}
+            </ErrorBoundary>
+            <form on:submit=move |ev: SubmitEvent| {
+                ev.prevent_default();
+                create.dispatch(CreateToken {
+                    name: name.get(),
+                    scopes: scopes.get(),
+                });
+            }>
+                <div class="input-box">
+                    <input
+                        type="text"
+                        name="name"
+                        id="name"
+                        maxlength="64"
+                        required
+                        prop:value=name
+                        on:input=move |e| set_name(event_target_value::<Event>(&e))
+                    />
+                    <label for="name" class="placeholder">
+                        "Token Name"
+                    </label>
+                </div>
+                <div class="scopes">
+                    {move || {
+                        let permissions = user.get().and_then(|u| u.ok()).map(|u| u.permissions).unwrap_or_default();
+                        PERMISSIONS
+                            .iter()
+                            .filter(|p| {
+                                permissions.contains(p) || permissions.contains(&Permissions::Administrator)
+                            })
+                            .map(|p| {
+                                let p = p.clone();
+                                let id = format!("scope-{p}");
+                                view! {
+                                    <div class="remember">
+                                        <input
+                                            type="checkbox"
+                                            id=id.clone()
+                                            prop:checked={
+                                                let p = p.clone();
+                                                move || scopes.with(|s| s.contains(&p))
+                                            }
+                                            on:change={
+                                                let p = p.clone();
+                                                move |_| {
+                                                    scopes
+                                                        .update(|s| {
+                                                            if let Some(i) = s.iter().position(|s| *s == p) {
+                                                                s.remove(i);
+                                                            } else {
+                                                                s.push(p.clone());
+                                                            }
+                                                        })
+                                                }
+                                            }
+                                        />
+                                        <label for=id>{p.to_string()}</label>
+                                    </div>
+                                }
+                            })
+                            .collect_view()
+                    }}
+                </div>
+                <input type="submit" class="button primary" value="Create Token" />
+            </form>
+            <Transition fallback=move || {
+                view! { <p>"Loading..."</p> }
+            }>
+                <ErrorBoundary fallback=|_| {
+                    view! { <span class="error">"🛈 Something went wrong. Try again"</span> }
+                }>
+                    <div class="hidden">{move || revoke.value().get()}</div>
+                    {move || {
+                        tokens
+                            .get()
+                            .map(|data| {
+                                data.map(|tokens| {
+                                    tokens
+                                        .into_iter()
+                                        .map(|token| {
+                                            view! {
+                                                <div class="session row">
+                                                    <div class="narrow">
+                                                        <h4>{token.name}</h4>
+                                                        <p>
+                                                            {token
+                                                                .scopes
+                                                                .iter()
+                                                                .map(|s| s.to_string())
+                                                                .collect::<Vec<_>>()
+                                                                .join(", ")}
+                                                        </p>
+                                                        <p>
+                                                            {format!(
+                                                                "Created {}, {}",
+                                                                token.created_at.format("%d/%m/%Y %H:%M"),
+                                                                token
+                                                                    .last_used_at
+                                                                    .map(|t| format!("last used {}", t.format("%d/%m/%Y %H:%M")))
+                                                                    .unwrap_or("never used".into()),
+                                                            )}
+                                                        </p>
+                                                    </div>
+                                                    <ActionForm action=revoke>
+                                                        <input hidden type="text" name="id" value=token.id />
+                                                        <input type="submit" class="button danger" value="Revoke" />
+                                                    </ActionForm>
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()
+                                })
+                            })
+                    }}
+                </ErrorBoundary>
+            </Transition>
+            <div class="row">
+                <A attr:class="button secondary" href="../">
+                    "Back"
+                </A>
+            </div>
+        </section>
+    }
+}
+
+#[component]
+pub fn Disable() -> impl IntoView {
+    let action = expect_context::<ServerAction<DisableAccount>>();
+    let result = Signal::derive(move || action.value().get());
+    view! {
+        <A attr:class="toner" href="../">
+            <div />
+        </A>
+        <section id="box">
+            <h1>"Disable Account"</h1>
+            <p>
+                "Your name, bio, avatar and Discord connections will be removed and you will be logged out "
+                "everywhere. Your runs stay on the leaderboard under an anonymous name. This cannot be undone."
+            </p>
+            <ErrorBoundary fallback=|e| {
+                view! {
+                    <span class="error">
+                        {move || {
+                            let e = e.get().into_iter().next().unwrap().1;
+                            if e.is::<ApiError>() {
+                                let e = e.downcast_ref::<ApiError>().unwrap();
+                                match e {
+                                    ApiError::InvalidCredentials => "🛈 Incorrect password",
+                                    ApiError::PasswordRequired => "🛈 Set a password first",
+                                    _ => "🛈 Something went wrong. Try again",
+                                }
+                            } else {
+                                "🛈 Something went wrong. Try again"
+                            }
+                        }}
+                    </span>
+                }
+            }>
+                <div class="hidden">{result}</div>
+            </ErrorBoundary>
+            <ActionForm action>
+                <div class="input-box">
+                    <input type="password" name="password" id="password" required />
+                    <label for="password" class="placeholder">
+                        "Current Password"
+                    </label>
+                </div>
+                <div class="row">
+                    <A attr:class="button secondary" href="../">
+                        "Cancel"
+                    </A>
+                    <input type="submit" class="button danger" value="Disable" />
+                </div>
+            </ActionForm>
+        </section>
+    }
+}
+
 #[component]
 pub fn Bio() -> impl IntoView {
     let action = expect_context::<ServerAction<UpdateBio>>();
@@ -86,12 +86,14 @@ pub fn FAQ() -> impl IntoView {
                         " is a shorthand for " <strong>"Layout"</strong>", "
                         <strong>"Category"</strong>", and " <strong>"Map"</strong>
                         " which will be autofilled when typing in the code. The "
-                        <strong>"Video ID"</strong>
-                        " field are the 11 characters indentifying the proof video on YouTube:"
+                        <strong>"Proof Link"</strong>
+                        " field takes a link to the proof video on YouTube, a Twitch clip or Streamable:"
                     </p>
                     <ul>
-                        <li>"https://www.youtube.com/watch?v="<strong>"dQw4w9WgXcQ"</strong></li>
-                        <li>"https://youtu.be/"<strong>"dQw4w9WgXcQ"</strong></li>
+                        <li>"https://www.youtube.com/watch?v=dQw4w9WgXcQ"</li>
+                        <li>"https://youtu.be/dQw4w9WgXcQ"</li>
+                        <li>"https://clips.twitch.tv/ExampleClipSlug"</li>
+                        <li>"https://streamable.com/abc123"</li>
                     </ul>
                     <div class="spacing"></div>
                     <h5>"Submit rules"</h5>
@@ -101,6 +103,9 @@ pub fn FAQ() -> impl IntoView {
                         <li>
                             "uploaded to "<a href="https://youtube.com/." target="_blank">
                                 "YouTube"
+                            </a>", Twitch as a clip, or "
+                            <a href="https://streamable.com/" target="_blank">
+                                "Streamable"
                             </a>
                         </li>
                         <li>"trimmed to only include one attempt (that being the run itself)"</li>
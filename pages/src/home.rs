@@ -1,4 +1,5 @@
 use chrono::Local;
+use components::on_live_event;
 use leptos::{
     either::{Either, EitherOf3},
     prelude::*,
@@ -7,13 +8,35 @@ use leptos_meta::Title;
 use leptos_router::components::A;
 
 use server::api::{get_activity, get_rand_user, get_runs};
-use types::api::{ActivityFilters, RunFilters};
+use types::{
+    api::{ActivityFilters, LiveEvent, PageQuery, RunFilters},
+    leptos::PatchResource,
+};
 
 #[component]
 pub fn HomePage() -> impl IntoView {
-    let runs = OnceResource::new(get_runs(RunFilters::default(), 0));
-    let rankings = OnceResource::new(get_activity(ActivityFilters::default(), 0));
+    let recent = PageQuery {
+        limit: Some(5),
+        ..Default::default()
+    };
+    let runs = Resource::new(|| (), {
+        let recent = recent.clone();
+        move |_| get_runs(RunFilters::default(), recent.clone())
+    });
+    let rankings = Resource::new(|| (), move |_| get_activity(ActivityFilters::default(), recent.clone()));
+    on_live_event(move |event| match event {
+        LiveEvent::Submit(_) | LiveEvent::Change(_) => runs.refetch(),
+        LiveEvent::Activity(_) => rankings.refetch(),
+    });
     let potd = OnceResource::new(get_rand_user());
+    let patches = expect_context::<PatchResource>();
+    let current = Signal::derive(move || {
+        patches
+            .get()
+            .and_then(Result::ok)
+            .and_then(|ps| ps.into_iter().find(|p| p.current))
+            .map(|p| p.name)
+    });
 
     view! {
         <Title text="Home" />
@@ -46,7 +69,7 @@ pub fn HomePage() -> impl IntoView {
             <div class="row">
                 <div class="users">
                     <h3>"Rankings"</h3>
-                    <Suspense fallback=|| {
+                    <Transition fallback=|| {
                         "Loading..."
                     }>
                         {move || {
@@ -55,6 +78,7 @@ pub fn HomePage() -> impl IntoView {
                                 .map(|res| {
                                     res.map(|rankings| {
                                         rankings
+                                            .data
                                             .into_iter()
                                             .take(5)
                                             .map(|act| {
@@ -157,21 +181,22 @@ pub fn HomePage() -> impl IntoView {
                                     })
                                 })
                         }}
-                    </Suspense>
+                    </Transition>
                     <A href="/activity" attr:class="button secondary">
                         "Show More"
                     </A>
                 </div>
                 <div class="runs">
                     <h3>"Submissions"</h3>
-                    <Suspense fallback=|| {
+                    <Transition fallback=|| {
                         "Loading..."
                     }>
                         {move || {
                             runs.get()
                                 .map(|res| {
                                     res.map(|runs| {
-                                        runs.into_iter()
+                                        runs.data
+                                            .into_iter()
                                             .take(5)
                                             .map(|run| {
                                                 let diff = Local::now() - run.created_at;
@@ -216,7 +241,7 @@ pub fn HomePage() -> impl IntoView {
                                     })
                                 })
                         }}
-                    </Suspense>
+                    </Transition>
                     <A href="/runs" attr:class="button secondary">
                         "Show More"
                     </A>
@@ -236,7 +261,7 @@ pub fn HomePage() -> impl IntoView {
                                             .ranks
                                             .iter()
                                             .filter(|r| {
-                                                r.patch == String::from("2.13") && r.layout.is_none()
+                                                current.with(|c| c.as_ref() == Some(&r.patch)) && r.layout.is_none()
                                             })
                                             .next();
                                         view! {
@@ -278,7 +303,7 @@ pub fn HomePage() -> impl IntoView {
                                             u.ranks
                                                 .iter()
                                                 .filter(|r| {
-                                                    r.patch == String::from("2.13")
+                                                    current.with(|c| c.as_ref() == Some(&r.patch))
                                                         && r.category == Some(String::from("Standard"))
                                                 })
                                                 .map(|r| {
@@ -313,7 +338,7 @@ pub fn HomePage() -> impl IntoView {
                                             u.ranks
                                                 .iter()
                                                 .filter(|r| {
-                                                    r.patch == String::from("2.13")
+                                                    current.with(|c| c.as_ref() == Some(&r.patch))
                                                         && r.category == Some(String::from("Gravspeed"))
                                                 })
                                                 .map(|r| {
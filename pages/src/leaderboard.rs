@@ -1,17 +1,16 @@
-use components::{Collapsible, Filter, Header, ListElements, Player, Select};
+use components::{Collapsible, Filter, Header, ListElements, Player, Select, on_live_event};
 use leptos::{either::*, prelude::*};
 use leptos_meta::Title;
 use leptos_router::{
     components::A,
     hooks::{use_params_map, use_query_map},
 };
-use rust_decimal::Decimal;
-use std::{cmp::Ordering, collections::HashMap};
 
 use server::api::get_runs_category;
 use types::{
-    api::{PartialRun, SectionRuns},
+    api::{LeaderboardFilters, LiveEvent, PartialRun, SectionRuns},
     internal::Proof,
+    leptos::PatchResource,
 };
 
 #[component]
@@ -21,6 +20,7 @@ pub fn Section(
     #[prop(into)] category: Signal<String>,
 ) -> impl IntoView {
     let query = use_query_map();
+    let patches = expect_context::<PatchResource>();
 
     view! {
         <Title text="Leaderboard" />
@@ -77,34 +77,38 @@ pub fn Section(
         </Header>
         <Collapsible id="filter" class="filter" header=|| "Filters">
             <Filter attr:class="filter">
-                <div>
-                    <label for="patch" class="indicator">
-                        "Patch"
-                    </label>
-                    <A href="../../../1.00/1/standard" attr:id="patch" attr:class="select">
-                        "1.00"
-                    </A>
-                </div>
-                <div>
-                    <A href="../../../1.41/1/standard" attr:class="select">
-                        "1.41"
-                    </A>
-                </div>
-                <div>
-                    <A href="../../../1.50/1/standard" attr:class="select">
-                        "1.50"
-                    </A>
-                </div>
-                <div>
-                    <A href="../../../2.00/1/standard" attr:class="select">
-                        "2.00"
-                    </A>
-                </div>
-                <div>
-                    <A href="../../../2.13/1/standard" attr:class="select">
-                        "Current"
-                    </A>
-                </div>
+                <Transition>
+                    {move || {
+                        patches
+                            .get()
+                            .map(|res| {
+                                res.map(|patches| {
+                                    patches
+                                        .into_iter()
+                                        .enumerate()
+                                        .map(|(i, p)| {
+                                            view! {
+                                                <div class="input-box">
+                                                    <Show when=move || i == 0>
+                                                        <label for="patch" class="indicator">
+                                                            "Patch"
+                                                        </label>
+                                                    </Show>
+                                                    <A
+                                                        href=format!("../../../{}", p.leaderboard_path())
+                                                        attr:id=(i == 0).then_some("patch")
+                                                        attr:class="select"
+                                                    >
+                                                        {if p.current { "Current".into() } else { p.name }}
+                                                    </A>
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()
+                                })
+                            })
+                    }}
+                </Transition>
                 <Select
                     name="sort"
                     indicator="Sort By"
@@ -129,9 +133,29 @@ pub fn Section(
 
 #[component]
 pub fn Leaderboard(patch: Signal<String>, layout: Signal<String>, category: Signal<String>) -> impl IntoView {
-    let selection = Signal::derive(move || (patch.get(), layout.get(), category.get()));
+    let query = use_query_map();
+    let params = use_params_map();
+    let filters = Signal::derive(move || LeaderboardFilters {
+        filter: query.read().get("filter"),
+        sort: query.read().get("sort"),
+        user: params.read().get("id").map(|id| id.parse().unwrap_or(-1)),
+    });
+    let selection = Signal::derive(move || (patch.get(), layout.get(), category.get(), filters.get()));
     let maps = Resource::new(selection, |mut s| {
-        get_runs_category(s.0, s.1, format!("{}{}", s.2.remove(0).to_uppercase(), s.2))
+        get_runs_category(s.0, s.1, format!("{}{}", s.2.remove(0).to_uppercase(), s.2), s.3)
+    });
+    on_live_event(move |event| {
+        let (p, l, c) = match &event {
+            LiveEvent::Submit(run) => (&run.patch, &run.layout, &run.category),
+            LiveEvent::Change(section) => (&section.patch, &section.layout, &section.category),
+            LiveEvent::Activity(_) => return,
+        };
+        if *p == patch.get_untracked()
+            && *l == layout.get_untracked()
+            && c.eq_ignore_ascii_case(&category.get_untracked())
+        {
+            maps.refetch();
+        }
     });
 
     view! {
@@ -163,20 +187,14 @@ pub fn Leaderboard(patch: Signal<String>, layout: Signal<String>, category: Sign
 
 #[component]
 pub fn LeaderboardEntry(map: SectionRuns) -> impl IntoView {
-    let filter_key = Memo::new(|_| use_query_map().read().get("filter"));
     let sort_key = Memo::new(|_| use_query_map().read().get("sort"));
-    let user = Memo::new(|_| use_params_map().read().get("id"));
+    // Already filtered and sorted by the server
     let runs = Signal::derive(move || {
-        let mut old_time = Decimal::new(999999, 3);
-        let mut old_times = HashMap::<i64, Decimal>::new();
-        let r = map.runs.clone();
-        let mut runs: Vec<PartialRun> = r
+        map.runs
+            .clone()
             .into_iter()
-            .filter(|r| user().is_none() || r.user_id == user.get().unwrap().parse::<i64>().unwrap_or(-1))
-            .filter(|r| filter(r, filter_key.get(), &mut old_time, &mut old_times))
-            .collect();
-        runs.sort_unstable_by(sort(sort_key.get()));
-        runs.into_iter().enumerate().collect::<Vec<(usize, PartialRun)>>()
+            .enumerate()
+            .collect::<Vec<(usize, PartialRun)>>()
     });
     let map_name = map.map.clone();
     let (top_run, set_top_run) = signal::<Option<PartialRun>>(None);
@@ -184,6 +202,7 @@ pub fn LeaderboardEntry(map: SectionRuns) -> impl IntoView {
     let proof = Signal::derive(move || {
         sel_run.get().map(|r| Proof {
             yt_id: r.yt_id,
+            host: r.proof_host,
             url: r.proof,
         })
     });
@@ -260,67 +279,3 @@ pub fn LeaderboardEntry(map: SectionRuns) -> impl IntoView {
         </div>
     }
 }
-
-pub fn filter<'a>(r: &'a PartialRun, f: Option<String>, t: &'a mut Decimal, ts: &'a mut HashMap<i64, Decimal>) -> bool {
-    match f {
-        Some(f) => match f.as_str() {
-            "verified" => r.verified,
-            "was_pb" => {
-                if &r.time < ts.get(&r.user_id).unwrap_or(&Decimal::new(999999, 3)) {
-                    ts.insert(r.user_id, r.time);
-                    true
-                } else {
-                    false
-                }
-            }
-            "is_pb" => r.is_pb,
-            "was_wr" => {
-                if r.time < *t {
-                    *t = r.time;
-                    true
-                } else {
-                    false
-                }
-            }
-            "is_wr" => r.is_wr,
-            _ => true,
-        },
-        None => r.is_pb,
-    }
-}
-
-pub fn sort(s: Option<String>) -> impl Fn(&PartialRun, &PartialRun) -> Ordering {
-    match s {
-        Some(s) => match s.as_str() {
-            "date" => move |r1: &PartialRun, r2: &PartialRun| -> Ordering {
-                if r1.created_at > r2.created_at {
-                    Ordering::Less
-                } else {
-                    Ordering::Greater
-                }
-            },
-            _ => move |r1: &PartialRun, r2: &PartialRun| -> Ordering {
-                if r1.time == r2.time {
-                    if r1.created_at < r2.created_at {
-                        Ordering::Less
-                    } else {
-                        Ordering::Greater
-                    }
-                } else {
-                    if r1.time < r2.time {
-                        Ordering::Less
-                    } else {
-                        Ordering::Greater
-                    }
-                }
-            },
-        },
-        None => move |r1: &PartialRun, r2: &PartialRun| -> Ordering {
-            if r1.time < r2.time {
-                Ordering::Less
-            } else {
-                Ordering::Greater
-            }
-        },
-    }
-}
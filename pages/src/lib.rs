@@ -1,5 +1,8 @@
 pub use activity::Activity;
+pub use admin::AuditLog;
+pub use admin::ManageUsers;
 pub use auth::Login;
+pub use auth::Recover;
 pub use auth::Register;
 pub use auth::Submit;
 pub use dash::Dashboard;
@@ -8,12 +11,14 @@ pub use faq::FAQ;
 pub use home::HomePage;
 pub use leaderboard::Leaderboard;
 pub use map::Map;
+pub use moderation::Queue;
 pub use ranking::ComboRanking;
 pub use ranking::UserRanking;
 pub use submits::Submits;
 pub use user::ManageRuns;
 pub use user::Profile;
 pub mod activity;
+pub mod admin;
 pub mod auth;
 pub mod dash;
 pub mod error_template;
@@ -21,6 +26,7 @@ pub mod faq;
 pub mod home;
 pub mod leaderboard;
 pub mod map;
+pub mod moderation;
 pub mod ranking;
 pub mod submits;
 pub mod user;
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap};
 
 use charming::{
     component::{Axis, DataZoom, FilterMode, Grid, Legend},
@@ -7,7 +7,7 @@ use charming::{
     series::Line,
 };
 use chrono::Local;
-use components::{Chart, Collapsible, Player};
+use components::{Chart, Collapsible, Player, on_live_event};
 use leptos::{either::Either, prelude::*};
 use leptos_router::{
     components::A,
@@ -18,13 +18,25 @@ use rust_decimal::{
     prelude::{FromPrimitive, ToPrimitive},
 };
 
-use crate::leaderboard::{filter, sort};
 use server::api::get_runs_id;
-use types::{api::PartialRun, internal::Proof};
+use types::{
+    api::{LiveEvent, PartialRun},
+    internal::Proof,
+};
 
 #[component]
 pub fn Map(id: Signal<i32>) -> impl IntoView {
     let map = Resource::new(id, |s| get_runs_id(s));
+    on_live_event(move |event| {
+        let section_id = match event {
+            LiveEvent::Submit(run) => run.section_id,
+            LiveEvent::Change(section) => section.id,
+            LiveEvent::Activity(_) => return,
+        };
+        if section_id == id.get_untracked() {
+            map.refetch();
+        }
+    });
 
     view! {
         <section id="map">
@@ -235,6 +247,7 @@ fn MapRunList(map: String, runs: Vec<PartialRun>) -> impl IntoView {
                                             <Player
                                                 proof=Proof {
                                                     yt_id: r.yt_id,
+                                                    host: r.proof_host,
                                                     url: r.proof.clone(),
                                                 }
                                                     .into()
@@ -301,3 +314,67 @@ fn MapRunList(map: String, runs: Vec<PartialRun>) -> impl IntoView {
         }}
     }
 }
+
+fn filter<'a>(r: &'a PartialRun, f: Option<String>, t: &'a mut Decimal, ts: &'a mut HashMap<i64, Decimal>) -> bool {
+    match f {
+        Some(f) => match f.as_str() {
+            "verified" => r.verified,
+            "was_pb" => {
+                if &r.time < ts.get(&r.user_id).unwrap_or(&Decimal::new(999999, 3)) {
+                    ts.insert(r.user_id, r.time);
+                    true
+                } else {
+                    false
+                }
+            }
+            "is_pb" => r.is_pb,
+            "was_wr" => {
+                if r.time < *t {
+                    *t = r.time;
+                    true
+                } else {
+                    false
+                }
+            }
+            "is_wr" => r.is_wr,
+            _ => true,
+        },
+        None => r.is_pb,
+    }
+}
+
+fn sort(s: Option<String>) -> impl Fn(&PartialRun, &PartialRun) -> Ordering {
+    match s {
+        Some(s) => match s.as_str() {
+            "date" => move |r1: &PartialRun, r2: &PartialRun| -> Ordering {
+                if r1.created_at > r2.created_at {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            },
+            _ => move |r1: &PartialRun, r2: &PartialRun| -> Ordering {
+                if r1.time == r2.time {
+                    if r1.created_at < r2.created_at {
+                        Ordering::Less
+                    } else {
+                        Ordering::Greater
+                    }
+                } else {
+                    if r1.time < r2.time {
+                        Ordering::Less
+                    } else {
+                        Ordering::Greater
+                    }
+                }
+            },
+        },
+        None => move |r1: &PartialRun, r2: &PartialRun| -> Ordering {
+            if r1.time < r2.time {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        },
+    }
+}
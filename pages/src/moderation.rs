@@ -0,0 +1,176 @@
+use components::Player;
+use leptos::prelude::*;
+use leptos_meta::Title;
+use leptos_router::{components::A, hooks::use_query_map};
+use server::auth::{Review, get_queue};
+use types::{api::ApiError, internal::Proof};
+
+#[component]
+pub fn Queue() -> impl IntoView {
+    let params = use_query_map();
+    let offset = Signal::derive(move || {
+        params
+            .get()
+            .get("page")
+            .unwrap_or(String::from("0"))
+            .parse::<i32>()
+            .unwrap_or(0)
+    });
+    let action = ServerAction::<Review>::new();
+    let runs = Resource::new(
+        move || (action.version().get(), offset.get()),
+        move |(_, offset)| get_queue(offset * 50),
+    );
+    let last = Signal::derive(move || {
+        let mut last = true;
+        runs.map(|res| {
+            let _ = res.as_ref().inspect(|v| last = v.len() < 50);
+        });
+        last
+    });
+
+    view! {
+        <Title text="Verification Queue" />
+        <section id="filter-list" class="queue">
+            <h1>"Verification Queue"</h1>
+            <ErrorBoundary fallback=|e| {
+                view! {
+                    <span class="error">
+                        {move || {
+                            let e = e.get().into_iter().next().unwrap().1;
+                            if e.is::<ApiError>() {
+                                let e = e.downcast_ref::<ApiError>().unwrap();
+                                match e {
+                                    ApiError::Unauthenticated => "🛈 Please log in",
+                                    ApiError::Unauthorized => "🛈 Missing permission",
+                                    ApiError::NotFound => "🛈 Run was already reviewed",
+                                    ApiError::InvalidInput => "🛈 Rejections need a reason of at most 512 characters",
+                                    _ => "🛈 Something went wrong. Try again",
+                                }
+                            } else {
+                                "🛈 Something went wrong. Try again"
+                            }
+                        }}
+                    </span>
+                }
+            }>
+                <div class="hidden">{move || action.value().get()}</div>
+            </ErrorBoundary>
+            <Transition fallback=|| view! { <p>"Loading..."</p> }>
+                <ErrorBoundary fallback=|_| {
+                    view! { <div class="error-display">"You are not allowed to verify runs"</div> }
+                }>
+                    {move || {
+                        runs.get()
+                            .map(|res| {
+                                res.map(|runs| {
+                                    if runs.is_empty() {
+                                        return view! { <p>"No runs waiting for verification"</p> }.into_any();
+                                    }
+                                    runs.into_iter()
+                                        .map(|r| {
+                                            let map = r.map.clone();
+                                            view! {
+                                                <div class="entry">
+                                                    <div class="header">
+                                                        <A href=format!("/leaderboard/map/{}", r.section_id)>
+                                                            <h3>{map}</h3>
+                                                        </A>
+                                                        <h5>
+                                                            {format!("{} / Layout {} / {}", r.patch, r.layout, r.category)}
+                                                        </h5>
+                                                    </div>
+                                                    <div class="row">
+                                                        <Player
+                                                            proof=Proof {
+                                                                yt_id: r.yt_id,
+                                                                host: r.proof_host,
+                                                                url: r.proof.clone(),
+                                                            }
+                                                                .into()
+                                                            cover=r.map
+                                                        />
+                                                        <div class="review">
+                                                            <p>
+                                                                <A href=format!(
+                                                                    "/user/{}/leaderboard",
+                                                                    r.user_id,
+                                                                )>{r.username}</A>
+                                                                " in "
+                                                                <b>{r.time.to_string()} " sec"</b>
+                                                            </p>
+                                                            <p>
+                                                                {r.created_at.format("%a %d %b %Y %k:%M:%S").to_string()}
+                                                            </p>
+                                                            <p>
+                                                                <a href=r.proof target="_blank">
+                                                                    "Open proof"
+                                                                </a>
+                                                            </p>
+                                                            <ActionForm action>
+                                                                <input type="text" name="id" hidden value=r.id />
+                                                                <div class="input-box">
+                                                                    <select name="approve" required>
+                                                                        <option value="true">"Approve"</option>
+                                                                        <option value="false">"Reject"</option>
+                                                                    </select>
+                                                                </div>
+                                                                <div class="input-box">
+                                                                    <textarea
+                                                                        name="reason"
+                                                                        maxlength="512"
+                                                                        placeholder="Reason, required when rejecting"
+                                                                    ></textarea>
+                                                                </div>
+                                                                <input type="submit" class="button" value="Submit" />
+                                                            </ActionForm>
+                                                        </div>
+                                                    </div>
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()
+                                        .into_any()
+                                })
+                            })
+                    }}
+                </ErrorBoundary>
+            </Transition>
+            <div class="pages row">
+                <Show
+                    when=move || offset.read() != 0
+                    fallback=|| view! { <div class="arrow disabled">"<"</div> }
+                >
+                    <A
+                        class:arrow=true
+                        href=move || {
+                            let mut map = params.get();
+                            map.replace("page", (offset.get() - 1).to_string());
+                            map.to_query_string()
+                        }
+                    >
+                        "<"
+                    </A>
+                </Show>
+                <div class="page">{move || offset.get() + 1}</div>
+                <Suspense fallback=|| view! { <div class="arrow disabled">">"</div> }>
+                    <Show
+                        when=move || !*last.read()
+                        fallback=|| view! { <div class="arrow disabled">">"</div> }
+                    >
+                        <A
+                            class:arrow=true
+                            href=move || {
+                                let mut map = params.get();
+                                map.replace("page", (offset.get() + 1).to_string());
+                                map.to_query_string()
+                            }
+                        >
+                            ">"
+                        </A>
+                    </Show>
+                </Suspense>
+            </div>
+        </section>
+    }
+}
@@ -50,11 +50,10 @@ pub fn ComboRanking(
             Signal::<String>::derive(move || match patch.get().as_str() {
                 "1.00" | "1.41" | "1.50" => "300 or more rating points".into(),
                 "2.00" => "150 or more rating points".into(),
-                "2.13" => format!(
+                _ => format!(
                     "{} or more rating points",
                     if layout.get().is_none() { 300 } else { 500 }
                 ),
-                _ => "Unknown patch selected".into(),
             }),
         ),
         (
@@ -62,11 +61,10 @@ pub fn ComboRanking(
             Signal::<String>::derive(move || match patch.get().as_str() {
                 "1.00" | "1.41" | "1.50" => "1000 or more rating points".into(),
                 "2.00" => "500 or more rating points".into(),
-                "2.13" => format!(
+                _ => format!(
                     "{} or more rating points",
                     if layout.get().is_none() { 600 } else { 1500 }
                 ),
-                _ => "Unknown patch selected".into(),
             }),
         ),
         (
@@ -74,11 +72,10 @@ pub fn ComboRanking(
             Signal::<String>::derive(move || match patch.get().as_str() {
                 "1.00" | "1.41" | "1.50" => "2000 or more rating points".into(),
                 "2.00" => "1000 or more rating points".into(),
-                "2.13" => format!(
+                _ => format!(
                     "{} or more rating points",
                     if layout.get().is_none() { 1000 } else { 3000 }
                 ),
-                _ => "Unknown patch selected".into(),
             }),
         ),
         (
@@ -86,11 +83,10 @@ pub fn ComboRanking(
             Signal::<String>::derive(move || match patch.get().as_str() {
                 "1.00" | "1.41" | "1.50" => "4000 or more rating points".into(),
                 "2.00" => "2000 or more rating points".into(),
-                "2.13" => format!(
+                _ => format!(
                     "{} or more rating points",
                     if layout.get().is_none() { 3000 } else { 6000 }
                 ),
-                _ => "Unknown patch selected".into(),
             }),
         ),
         (
@@ -98,11 +94,10 @@ pub fn ComboRanking(
             Signal::<String>::derive(move || match patch.get().as_str() {
                 "1.00" | "1.41" | "1.50" => "5500 or more rating points".into(),
                 "2.00" => "2750 or more rating points".into(),
-                "2.13" => format!(
+                _ => format!(
                     "{} or more rating points",
                     if layout.get().is_none() { 5000 } else { 8000 }
                 ),
-                _ => "Unknown patch selected".into(),
             }),
         ),
         (Title::TopOne, "Most points in the ranking".into()),
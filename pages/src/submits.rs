@@ -1,13 +1,17 @@
 use chrono::{Local, NaiveDateTime, TimeZone};
-use components::{Collapsible, Filter, Select};
+use components::{Collapsible, Filter, Pager, Select};
 use leptos::{either::Either, prelude::*};
-use leptos_router::{components::A, hooks::use_query_map};
+use leptos_router::hooks::use_query_map;
 use server::api::{get_maps, get_runs};
-use types::api::RunFilters;
+use types::{
+    api::{PageQuery, RunFilters},
+    leptos::PatchResource,
+};
 
 #[component]
 pub fn Submits() -> impl IntoView {
     let params = use_query_map();
+    let patches = expect_context::<PatchResource>();
     let filters = Signal::derive(move || {
         params.with(|p| RunFilters {
             user: p.get("user").map(|v| v.parse::<i64>().ok()).flatten(),
@@ -37,25 +41,32 @@ pub fn Submits() -> impl IntoView {
             ascending: !p.get("order").filter(|v| !v.is_empty()).is_none_or(|s| s == "desc"),
         })
     });
-    let offset = Signal::derive(move || {
+    let limit = Signal::derive(move || {
         params
-            .get()
-            .get("page")
-            .unwrap_or(String::from("0"))
-            .parse::<i32>()
-            .unwrap()
+            .read()
+            .get("limit")
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(50)
+            .clamp(1, 200)
+    });
+    let page = Signal::derive(move || PageQuery {
+        cursor: params.read().get("cursor"),
+        limit: Some(limit.get()),
+        total: true,
     });
     let runs = Resource::new(
-        move || (filters.get(), offset.get()),
-        move |f| async move { get_runs(f.0, f.1 * 50).await },
+        move || (filters.get(), page.get()),
+        move |f| async move { get_runs(f.0, f.1).await },
     );
-    let last = Signal::derive(move || {
-        let mut last = true;
-        runs.map(|res| {
-            let _ = res.as_ref().inspect(|v| last = v.len() < 50);
-        });
-        last
+    let next = Signal::derive(move || {
+        runs.map(|res| res.as_ref().ok().and_then(|p| p.next_cursor.clone()))
+            .flatten()
+    });
+    let prev = Signal::derive(move || {
+        runs.map(|res| res.as_ref().ok().and_then(|p| p.prev_cursor.clone()))
+            .flatten()
     });
+    let total = Signal::derive(move || runs.map(|res| res.as_ref().ok().and_then(|p| p.total)).flatten());
 
     view! {
         <section id="filter-list" class="runs">
@@ -72,6 +83,11 @@ pub fn Submits() -> impl IntoView {
                         selected=1
                         options=[("asc", "Ascending"), ("desc", "Descending")]
                     />
+                    <Select
+                        name="limit"
+                        indicator="Per Page"
+                        options=[("50", "50"), ("25", "25"), ("100", "100")]
+                    />
                     <div>
                         <label for="before" class="indicator">
                             "Before"
@@ -123,18 +139,35 @@ pub fn Submits() -> impl IntoView {
                             step="1"
                         />
                     </div>
-                    <Select
-                        name="patch"
-                        indicator="Patch"
-                        options=[
-                            ("", "All"),
-                            ("1.00", "1.00"),
-                            ("1.41", "1.41"),
-                            ("1.50", "1.50"),
-                            ("2.00", "2.00"),
-                            ("2.13", "Current"),
-                        ]
-                    />
+                    <div>
+                        <label for="patch" class="indicator">
+                            "Patch"
+                        </label>
+                        <select class="select" name="patch" id="patch">
+                            <option value="">"All"</option>
+                            <Transition>
+                                {move || {
+                                    patches
+                                        .get()
+                                        .map(|res| {
+                                            res.map(|patches| {
+                                                patches
+                                                    .into_iter()
+                                                    .map(|p| {
+                                                        let value = p.name.clone();
+                                                        view! {
+                                                            <option value=value>
+                                                                {if p.current { "Current".into() } else { p.name }}
+                                                            </option>
+                                                        }
+                                                    })
+                                                    .collect_view()
+                                            })
+                                        })
+                                }}
+                            </Transition>
+                        </select>
+                    </div>
                     <Select
                         name="layout"
                         indicator="Layout"
@@ -197,7 +230,7 @@ pub fn Submits() -> impl IntoView {
                             runs.get()
                                 .map(|res| {
                                     res.map(|runs| {
-                                        runs.into_iter()
+                                        runs.data.into_iter()
                                             .map(|r| {
                                                 view! {
                                                     <span>
@@ -222,41 +255,7 @@ pub fn Submits() -> impl IntoView {
                     </ErrorBoundary>
                 </Suspense>
             </div>
-            <div class="pages row">
-                <Show
-                    when=move || offset.read() != 0
-                    fallback=|| view! { <div class="arrow disabled">"<"</div> }
-                >
-                    <A
-                        class:arrow=true
-                        href=move || {
-                            let mut map = params.get();
-                            map.replace("page", (offset.get() - 1).to_string());
-                            map.to_query_string()
-                        }
-                    >
-                        "<"
-                    </A>
-                </Show>
-                <div class="page">{move || offset.get() + 1}</div>
-                <Suspense fallback=|| view! { <div class="arrow disabled">">"</div> }>
-                    <Show
-                        when=move || !*last.read()
-                        fallback=|| view! { <div class="arrow disabled">">"</div> }
-                    >
-                        <A
-                            class:arrow=true
-                            href=move || {
-                                let mut map = params.get();
-                                map.replace("page", (offset.get() + 1).to_string());
-                                map.to_query_string()
-                            }
-                        >
-                            ">"
-                        </A>
-                    </Show>
-                </Suspense>
-            </div>
+            <Pager next prev total limit />
         </section>
     }
 }
@@ -1,5 +1,5 @@
 use chrono::{Local, NaiveDateTime, TimeZone};
-use components::{Collapsible, Filter, Select};
+use components::{Collapsible, Filter, Pager, Select};
 use leptos::{either::Either, prelude::*};
 use leptos_router::{
     components::{A, Outlet},
@@ -7,12 +7,13 @@ use leptos_router::{
 };
 use server::{
     api::{get_maps, get_runs, get_user},
-    auth::Delete,
+    auth::{Delete, EditRun},
 };
 use types::{
-    api::{ApiError, RunFilters},
-    leptos::UserResource,
+    api::{ApiError, PageQuery, RunFilters},
+    leptos::{PatchResource, UserResource},
 };
+use web_sys::Event;
 
 #[component]
 pub fn Profile(id: Signal<i64>) -> impl IntoView {
@@ -56,7 +57,7 @@ pub fn ManageRuns() -> impl IntoView {
     let filters = Signal::derive(move || {
         params.with(|p| RunFilters {
             user: None,
-            patch: Some("2.13".into()),
+            patch: None,
             layout: p.get("layout").filter(|v| !v.is_empty()),
             category: p.get("category").filter(|v| !v.is_empty()),
             map: p.get("map").filter(|v| !v.is_empty()),
@@ -82,32 +83,43 @@ pub fn ManageRuns() -> impl IntoView {
             ascending: !p.get("order").filter(|v| !v.is_empty()).is_none_or(|s| s == "desc"),
         })
     });
-    let offset = Signal::derive(move || {
+    let limit = Signal::derive(move || {
         params
-            .get()
-            .get("page")
-            .unwrap_or(String::from("0"))
-            .parse::<i32>()
-            .unwrap()
+            .read()
+            .get("limit")
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(50)
+            .clamp(1, 200)
+    });
+    let page = Signal::derive(move || PageQuery {
+        cursor: params.read().get("cursor"),
+        limit: Some(limit.get()),
+        total: true,
     });
     let delete = ServerAction::<Delete>::new();
+    let edit = ServerAction::<EditRun>::new();
     provide_context(delete);
+    provide_context(edit);
     let user = expect_context::<UserResource>();
+    let patches = expect_context::<PatchResource>();
     let runs = Resource::new(
-        move || (filters.get(), delete.version().get(), offset.get()),
+        move || (filters.get(), delete.version().get(), edit.version().get(), page.get()),
         move |mut f| async move {
             let user = user.await?;
             f.0.user = Some(user.id);
-            get_runs(f.0, f.2 * 50).await
+            f.0.patch = patches.await?.into_iter().find(|p| p.current).map(|p| p.name);
+            get_runs(f.0, f.3).await
         },
     );
-    let last = Signal::derive(move || {
-        let mut last = true;
-        runs.map(|res| {
-            let _ = res.as_ref().inspect(|v| last = v.len() < 50);
-        });
-        last
+    let next = Signal::derive(move || {
+        runs.map(|res| res.as_ref().ok().and_then(|p| p.next_cursor.clone()))
+            .flatten()
+    });
+    let prev = Signal::derive(move || {
+        runs.map(|res| res.as_ref().ok().and_then(|p| p.prev_cursor.clone()))
+            .flatten()
     });
+    let total = Signal::derive(move || runs.map(|res| res.as_ref().ok().and_then(|p| p.total)).flatten());
 
     view! {
         <section id="filter-list" class="manage">
@@ -125,6 +137,11 @@ pub fn ManageRuns() -> impl IntoView {
                         selected=1
                         options=[("asc", "Ascending"), ("desc", "Descending")]
                     />
+                    <Select
+                        name="limit"
+                        indicator="Per Page"
+                        options=[("50", "50"), ("25", "25"), ("100", "100")]
+                    />
                     <div>
                         <label for="before" class="indicator">
                             "Before"
@@ -215,6 +232,8 @@ pub fn ManageRuns() -> impl IntoView {
                 <span class="heading">"map"</span>
                 <span class="heading">"proof"</span>
                 <span class="heading">"time"</span>
+                <span class="heading">"status"</span>
+                <span></span>
                 <span></span>
                 <div class="divider header"></div>
                 <Suspense fallback=|| { "Fetching Runs" }>
@@ -225,7 +244,7 @@ pub fn ManageRuns() -> impl IntoView {
                             runs.get()
                                 .map(|res| {
                                     res.map(|runs| {
-                                        runs.into_iter()
+                                        runs.data.into_iter()
                                             .map(|r| {
                                                 view! {
                                                     <span>{r.id}</span>
@@ -239,6 +258,28 @@ pub fn ManageRuns() -> impl IntoView {
                                                         <a href=r.proof>"link"</a>
                                                     </span>
                                                     <span>{r.time.to_string()} " sec"</span>
+                                                    <span class:rejected=r.rejected title=r.reason.clone()>
+                                                        {if r.rejected {
+                                                            "Rejected"
+                                                        } else if r.verified {
+                                                            "Verified"
+                                                        } else {
+                                                            "Pending"
+                                                        }}
+                                                        {r.reason.clone().map(|reason| view! { <br /><small>{reason}</small> })}
+                                                    </span>
+                                                    <A
+                                                        attr:class="edit"
+                                                        href=move || {
+                                                            format!(
+                                                                "{}/edit{}",
+                                                                r.id,
+                                                                params.get().to_query_string(),
+                                                            )
+                                                        }
+                                                    >
+                                                        <div></div>
+                                                    </A>
                                                     <A
                                                         attr:class="delete"
                                                         href=move || {
@@ -261,41 +302,7 @@ pub fn ManageRuns() -> impl IntoView {
                     </ErrorBoundary>
                 </Suspense>
             </div>
-            <div class="pages row">
-                <Show
-                    when=move || offset.read() != 0
-                    fallback=|| view! { <div class="arrow disabled">"<"</div> }
-                >
-                    <A
-                        class:arrow=true
-                        href=move || {
-                            let mut map = params.get();
-                            map.replace("page", (offset.get() - 1).to_string());
-                            map.to_query_string()
-                        }
-                    >
-                        "<"
-                    </A>
-                </Show>
-                <div class="page">{move || offset.get() + 1}</div>
-                <Suspense fallback=|| view! { <div class="arrow disabled">">"</div> }>
-                    <Show
-                        when=move || !*last.read()
-                        fallback=|| view! { <div class="arrow disabled">">"</div> }
-                    >
-                        <A
-                            class:arrow=true
-                            href=move || {
-                                let mut map = params.get();
-                                map.replace("page", (offset.get() + 1).to_string());
-                                map.to_query_string()
-                            }
-                        >
-                            ">"
-                        </A>
-                    </Show>
-                </Suspense>
-            </div>
+            <Pager next prev total limit />
         </section>
     }
 }
@@ -355,3 +362,93 @@ pub fn Delete() -> impl IntoView {
         </section>
     }
 }
+
+#[component]
+pub fn Edit() -> impl IntoView {
+    let id = Signal::derive(|| use_params_map().get().get("id").unwrap().parse::<i32>().unwrap_or(-1));
+    let action = expect_context::<ServerAction<EditRun>>();
+    let result = Signal::derive(move || action.value().get());
+    let (time, set_time) = signal(String::new());
+    let (proof, set_proof) = signal(String::new());
+    view! {
+        <A attr:class="toner" href="/user/@me/manage">
+            <div />
+        </A>
+        <section id="box">
+            <h1>"Edit Run"</h1>
+            <p>"Runs can be corrected for an hour after submitting them. Run " {id}</p>
+            <ErrorBoundary fallback=|e| {
+                view! {
+                    <span class="error">
+                        {move || {
+                            let e = e.get().into_iter().next().unwrap().1;
+                            if e.is::<ApiError>() {
+                                let e = e.downcast_ref::<ApiError>().unwrap();
+                                match e {
+                                    ApiError::Unauthenticated => "🛈 Please log in",
+                                    ApiError::Unauthorized => "🛈 Missing permission",
+                                    ApiError::NotFound => "🛈 Invalid run",
+                                    ApiError::InvalidInput => "🛈 Invalid time",
+                                    ApiError::InvalidProof => "🛈 Proof video does not exist",
+                                    ApiError::EditWindowExpired => "🛈 This run can no longer be edited",
+                                    _ => "🛈 Something went wrong. Try again",
+                                }
+                            } else {
+                                "🛈 Something went wrong. Try again"
+                            }
+                        }}
+                    </span>
+                }
+            }>
+                <div class="hidden">{result}</div>
+            </ErrorBoundary>
+            <ActionForm action>
+                <input
+                    type="text"
+                    name="redirect"
+                    hidden
+                    value=|| format!("user/@me/manage{}", use_query_map().get().to_query_string())
+                />
+                <input type="text" name="id" hidden value=id />
+                <div class="input-box">
+                    <input
+                        required
+                        type="number"
+                        name="time"
+                        id="time"
+                        min="0"
+                        step="0.001"
+                        value=time
+                        on:input=move |e| set_time(event_target_value::<Event>(&e))
+                    />
+                    <label for="time" class="placeholder">
+                        "Time"
+                    </label>
+                </div>
+                <div class="input-box">
+                    <input
+                        type="text"
+                        name="proof"
+                        id="proof"
+                        required
+                        maxlength="256"
+                        value=proof
+                        on:input=move |e| set_proof(event_target_value::<Event>(&e))
+                    />
+                    <label for="proof" class="placeholder">
+                        "Proof Link"
+                    </label>
+                </div>
+                <div class="row">
+                    <A
+                        attr:class="button secondary"
+                        href=|| format!("/user/@me/manage{}", use_query_map().get().to_query_string())
+                    >
+                        "Cancel"
+                    </A>
+                    <input type="submit" class="button primary" value="Save" />
+                </div>
+            </ActionForm>
+        </section>
+    }
+}
@@ -0,0 +1,4 @@
+// Embedded migrations are only re-read when the build script reruns.
+fn main() {
+    println!("cargo:rerun-if-changed=../migrations");
+}
@@ -0,0 +1,135 @@
+use leptos::prelude::{server, server_fn::codec::PostUrl};
+use types::api::*;
+
+#[server(GetAuditLog, prefix="/api", endpoint="admin/audit", input=PostUrl)]
+pub async fn get_audit_log(
+    actor: Option<i64>,
+    action: Option<AuditAction>,
+    offset: i32,
+) -> Result<Vec<AuditEntry>, ApiError> {
+    use crate::auth::ssr::*;
+    use sqlx::{Postgres, QueryBuilder};
+
+    let auth = auth()?;
+    let pool = pool()?;
+
+    let u = auth.current_user.ok_or(ApiError::Unauthenticated)?;
+    if !u.has(&Permissions::Administrator) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"SELECT a.id, a.actor_id, u."name", a.action, a.target, a.before, a.after,
+            host(a.ip) AS ip, a.created_at
+        FROM audit_log a
+        LEFT JOIN "user" u ON a.actor_id = u.id
+        WHERE 1 = 1"#,
+    );
+    if let Some(actor) = actor {
+        query.push(" AND a.actor_id = ").push_bind(actor);
+    }
+    if let Some(action) = action {
+        query.push(" AND a.action = ").push_bind(action);
+    }
+    query
+        .push(" ORDER BY a.created_at DESC, a.id DESC LIMIT 50 OFFSET ")
+        .push_bind(offset)
+        .push(";");
+    query
+        .build_query_as::<AuditEntry>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+}
+
+#[server(GetUsers, prefix="/api", endpoint="admin/users", input=PostUrl)]
+pub async fn get_users(search: Option<String>, offset: i32) -> Result<Vec<ManagedUser>, ApiError> {
+    use crate::auth::ssr::*;
+    use sqlx::{Postgres, QueryBuilder};
+
+    let auth = auth()?;
+    let pool = pool()?;
+
+    let u = auth.current_user.ok_or(ApiError::Unauthenticated)?;
+    if !u.has(&Permissions::ManageUsers) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"SELECT u.id, u."name", u.pfp, u.created_at,
+            array_remove(array_agg(p.token ORDER BY p.token), NULL) AS permissions
+        FROM "user" u
+        LEFT JOIN permission p ON p.user_id = u.id
+        WHERE 1 = 1"#,
+    );
+    if let Some(search) = search.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()) {
+        query
+            .push(r#" AND (u."name" ILIKE '%' || "#)
+            .push_bind(search.clone())
+            .push(" || '%' OR u.id::text = ")
+            .push_bind(search)
+            .push(")");
+    }
+    query
+        .push(" GROUP BY u.id ORDER BY u.id ASC LIMIT 50 OFFSET ")
+        .push_bind(offset)
+        .push(";");
+    query
+        .build_query_as::<ManagedUser>()
+        .fetch_all(&pool)
+        .await
+        .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+}
+
+/// Grants or revokes a single permission token. `ManageUsers` and `Administrator`
+/// can only be handed out by administrators, and nobody can change their own tokens.
+#[server(SetPermission, prefix="/api", endpoint="admin/users/permission", input=PostUrl)]
+pub async fn set_permission(id: i64, token: Permissions, grant: bool) -> Result<(), ApiError> {
+    use crate::auth::ssr::*;
+
+    let auth = auth()?;
+    let pool = pool()?;
+
+    let u = auth.current_user.clone().ok_or(ApiError::Unauthenticated)?;
+    if !u.has(&Permissions::ManageUsers) || u.id == id {
+        return Err(ApiError::Unauthorized);
+    }
+    if matches!(token, Permissions::ManageUsers | Permissions::Administrator) && !u.has(&Permissions::Administrator) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    let changed = if grant {
+        sqlx::query(
+            r#"INSERT INTO permission (user_id, token)
+            SELECT id, $2 FROM "user" WHERE id = $1
+            ON CONFLICT ON CONSTRAINT unique_permission DO NOTHING;"#,
+        )
+    } else {
+        sqlx::query("DELETE FROM permission WHERE user_id = $1 AND token = $2;")
+    }
+    .bind(id)
+    .bind(&token)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?
+    .rows_affected();
+
+    if changed == 0 {
+        return Ok(());
+    }
+    let (action, before, after) = if grant {
+        (AuditAction::GrantPermission, None, Some(json!({ "token": token })))
+    } else {
+        (AuditAction::RevokePermission, Some(json!({ "token": token })), None)
+    };
+    audit(&mut *tx, u.id, action, format!("user:{id}"), before, after).await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    auth.cache_clear_user(id);
+    Ok(())
+}
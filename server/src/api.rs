@@ -2,118 +2,357 @@ use http::{HeaderValue, header::CACHE_CONTROL};
 use leptos::prelude::{expect_context, server, server_fn::codec::GetUrl};
 use types::api::*;
 
+#[cfg(feature = "ssr")]
+pub mod ssr {
+    use sqlx::{PgPool, Postgres, QueryBuilder};
+    use types::api::*;
+
+    /// Select of `Run`, finish it with `push_run_filters`.
+    pub const RUNS: &str = r#"SELECT run.id, run.created_at, section_id, patch, layout, category, map, user_id,
+            "name", time, proof, yt_id, proof_host, verified, rejected, reason, is_pb, is_wr
+        FROM run
+        INNER JOIN section s ON section_id = s.id
+        INNER JOIN "user" u ON user_id = u.id
+        WHERE 1 = 1"#;
+
+    /// Select of `Activity`, finish it with `push_activity_filters`.
+    pub const ACTIVITY: &str = r#"SELECT a.id, a.user_id, name, rank_id, patch, layout, category,
+            title_old, title_new, rank_old, rank_new, a.created_at
+        FROM activity a
+        INNER JOIN "user" u ON a.user_id = u.id
+        LEFT JOIN rank r ON rank_id = r.id
+        WHERE 1 = 1"#;
+
+    /// Select of `Ranking`, followed by the conditions.
+    pub const RANKINGS: &str = r#"SELECT r.id, r.patch, r.layout, r.category, r.user_id,
+            u.name, r.title, r.rank, r.rating, r.created_at, r.updated_at, r.percentage, r.points
+        FROM rank r
+        JOIN "user" u ON user_id = u.id
+        WHERE "#;
+
+    /// Adds the conditions of `filter` to a query started with `RUNS`, ignoring its sort order.
+    pub fn push_run_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &RunFilters) {
+        if let Some(user) = filter.user {
+            query.push(" AND user_id = ").push_bind(user);
+        }
+        if let Some(before) = filter.before {
+            query.push(" AND run.created_at <= ").push_bind(before);
+        }
+        if let Some(after) = filter.after {
+            query.push(" AND run.created_at >= ").push_bind(after);
+        }
+        if let Some(patch) = &filter.patch {
+            query.push(" AND patch = ").push_bind(patch.clone());
+        }
+        if let Some(layout) = &filter.layout {
+            query.push(" AND layout = ").push_bind(layout.clone());
+        }
+        if let Some(category) = &filter.category {
+            query.push(" AND category = ").push_bind(category.clone());
+        }
+        if let Some(map) = &filter.map {
+            query.push(" AND map = ").push_bind(map.clone());
+        }
+        if let Some(faster) = filter.faster {
+            query.push(" AND time <= ").push_bind(faster);
+        }
+        if let Some(slower) = filter.slower {
+            query.push(" AND time >= ").push_bind(slower);
+        }
+    }
+
+    /// Adds the conditions of `filter` to a query started with `ACTIVITY`, ignoring its sort order.
+    pub fn push_activity_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &ActivityFilters) {
+        if let Some(event) = &filter.event {
+            match event.as_str() {
+                "join" => query.push(" AND rank_id IS NULL"),
+                "rank" => query.push(" AND rank_new IS NOT NULL"),
+                "title" => query.push(" AND title_new IS NOT NULL"),
+                _ => query,
+            };
+        }
+        if let Some(user) = filter.user {
+            query.push(" AND a.user_id = ").push_bind(user);
+        }
+        if let Some(patch) = &filter.patch {
+            query.push(" AND patch = ").push_bind(patch.clone());
+        }
+        if let Some(layout) = &filter.layout {
+            query.push(" AND layout = ").push_bind(layout.clone());
+        }
+        if let Some(category) = &filter.category {
+            query.push(" AND category = ").push_bind(category.clone());
+        }
+        if let Some(before) = filter.before {
+            query.push(" AND a.created_at <= ").push_bind(before);
+        }
+        if let Some(after) = filter.after {
+            query.push(" AND a.created_at >= ").push_bind(after);
+        }
+    }
+
+    /// Sort keys of runs for `Keyset`, by `RunFilters::sort`.
+    pub fn run_keyset(sort: &str) -> &'static [(&'static str, &'static str)] {
+        match sort {
+            "section" => &[
+                ("patch", "varchar"),
+                ("layout", "varchar"),
+                ("category", "varchar"),
+                ("map", "varchar"),
+                ("run.id", "integer"),
+            ],
+            "time" => &[("time", "numeric"), ("run.id", "integer")],
+            _ => &[("run.created_at", "timestamptz"), ("run.id", "integer")],
+        }
+    }
+
+    pub fn run_key(sort: &str, run: &Run) -> Vec<String> {
+        match sort {
+            "section" => vec![
+                run.patch.clone(),
+                run.layout.clone(),
+                run.category.clone(),
+                run.map.clone(),
+                run.id.to_string(),
+            ],
+            "time" => vec![run.time.to_string(), run.id.to_string()],
+            _ => vec![run.created_at.to_rfc3339(), run.id.to_string()],
+        }
+    }
+
+    /// Sort keys of activity for `Keyset`, by `ActivityFilters::sort`. Joins have no section
+    /// and go after all others like NULLs would.
+    pub fn activity_keyset(sort: &str) -> &'static [(&'static str, &'static str)] {
+        match sort {
+            "section" => &[
+                ("r.patch IS NULL", "boolean"),
+                ("COALESCE(r.patch, '')", "varchar"),
+                ("COALESCE(r.layout, '')", "varchar"),
+                ("COALESCE(r.category, '')", "varchar"),
+                ("a.id", "integer"),
+            ],
+            _ => &[("a.created_at", "timestamptz"), ("a.id", "integer")],
+        }
+    }
+
+    pub fn activity_key(sort: &str, activity: &Activity) -> Vec<String> {
+        match sort {
+            "section" => vec![
+                activity.patch.is_none().to_string(),
+                activity.patch.clone().unwrap_or_default(),
+                activity.layout.clone().unwrap_or_default(),
+                activity.category.clone().unwrap_or_default(),
+                activity.id.to_string(),
+            ],
+            _ => vec![activity.created_at.to_rfc3339(), activity.id.to_string()],
+        }
+    }
+
+    /// All patches with their layouts and categories, oldest first.
+    pub async fn patches(pool: &PgPool) -> Result<Vec<Patch>, ApiError> {
+        sqlx::query_as::<_, Patch>(
+            r#"SELECT p.name, p.display_name, p.released_at, p.current,
+                ARRAY(SELECT layout FROM section WHERE patch = p.name
+                    GROUP BY layout ORDER BY layout) AS layouts,
+                ARRAY(SELECT category FROM section WHERE patch = p.name
+                    GROUP BY category ORDER BY MIN(id)) AS categories
+            FROM patch p
+            ORDER BY p.released_at ASC, p.name ASC;"#,
+        )
+        .fetch_all(pool)
+        .await
+        .or(Err(ApiError::ServerError("Database lookup failed".to_string())))
+    }
+
+    /// A section with all its runs that weren't rejected, oldest first.
+    pub async fn section_runs(pool: &PgPool, id: i32) -> Result<SectionRuns, ApiError> {
+        sqlx::query_as::<_, SectionRuns>(
+            r#"SELECT s.id, s.patch, s.layout, s.category, s.map,
+                COALESCE(NULLIF(ARRAY_AGG((r.id, r.section_id, u.id, u."name", r.time,
+                    r.proof, r.yt_id, r.proof_host, r.verified, r.is_pb, r.is_wr, r.created_at)
+                ORDER BY r.created_at ASC)
+                FILTER(WHERE r.id IS NOT NULL), '{NULL}'), '{}') AS runs
+            FROM section s
+            LEFT JOIN run r ON section_id = s.id AND NOT r.rejected
+            LEFT JOIN "user" u ON user_id = u.id
+            WHERE s.id = $1
+            GROUP BY s.id, patch, layout, category, map;"#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .or(Err(ApiError::InvalidSection))
+    }
+
+    /// The runs of every map of a leaderboard picked and sorted by `filters`. Whether a run was
+    /// a personal best or record when it was submitted is worked out from the runs before it,
+    /// a run tying the best time before it wasn't one.
+    pub async fn category_runs(
+        pool: &PgPool,
+        patch: String,
+        layout: String,
+        category: String,
+        filters: &LeaderboardFilters,
+    ) -> Result<Vec<SectionRuns>, ApiError> {
+        let condition = match filters.filter.as_deref() {
+            Some("none") => "TRUE",
+            Some("verified") => "r.verified",
+            Some("is_wr") => "r.is_wr",
+            Some("was_pb") => "r.was_pb",
+            Some("was_wr") => "r.was_wr",
+            _ => "r.is_pb",
+        };
+        let order = match filters.sort.as_deref() {
+            Some("date") => "r.created_at DESC",
+            _ => "r.time ASC, r.created_at ASC",
+        };
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"WITH runs AS (
+                SELECT r.id, r.section_id, r.user_id, u."name", r.time, r.proof, r.yt_id, r.proof_host,
+                    r.verified, r.is_pb, r.is_wr, r.created_at,
+                    COALESCE(r.time < MIN(r.time) OVER pb, TRUE) AS was_pb,
+                    COALESCE(r.time < MIN(r.time) OVER wr, TRUE) AS was_wr
+                FROM run r
+                INNER JOIN section s ON r.section_id = s.id
+                INNER JOIN "user" u ON r.user_id = u.id
+                WHERE NOT r.rejected AND s.patch = "#,
+        );
+        query
+            .push_bind(patch.clone())
+            .push(" AND s.layout = ")
+            .push_bind(layout.clone())
+            .push(" AND s.category = ")
+            .push_bind(category.clone())
+            .push(
+                r#"
+                WINDOW pb AS (PARTITION BY r.section_id, r.user_id ORDER BY r.created_at, r.id
+                        ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING),
+                    wr AS (PARTITION BY r.section_id ORDER BY r.created_at, r.id
+                        ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING)
+            )
+            SELECT s.id, s.patch, s.layout, s.category, s.map,
+                COALESCE(NULLIF(ARRAY_AGG((r.id, r.section_id, r.user_id, r."name", r.time,
+                    r.proof, r.yt_id, r.proof_host, r.verified, r.is_pb, r.is_wr, r.created_at)
+                ORDER BY "#,
+            )
+            .push(order)
+            .push(
+                r#")
+                FILTER(WHERE r.id IS NOT NULL), '{NULL}'), '{}') AS runs
+            FROM section s
+            LEFT JOIN runs r ON r.section_id = s.id AND "#,
+            )
+            .push(condition);
+        if let Some(user) = filters.user {
+            query.push(" AND r.user_id = ").push_bind(user);
+        }
+        query
+            .push(" WHERE s.patch = ")
+            .push_bind(patch)
+            .push(" AND s.layout = ")
+            .push_bind(layout)
+            .push(" AND s.category = ")
+            .push_bind(category)
+            .push(
+                r#"
+            GROUP BY s.id, s.patch, s.layout, s.category, s.map
+            ORDER BY s.map;"#,
+            )
+            .build_query_as::<SectionRuns>()
+            .fetch_all(pool)
+            .await
+            .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+    }
+
+    /// All rankings of a user, across patches and leaderboards.
+    pub async fn user_rankings(pool: &PgPool, id: i64) -> Result<Vec<Ranking>, ApiError> {
+        QueryBuilder::<Postgres>::new(RANKINGS)
+            .push("user_id = ")
+            .push_bind(id)
+            .push(";")
+            .build_query_as::<Ranking>()
+            .fetch_all(pool)
+            .await
+            .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+    }
+}
+
 #[server(GetRunsId, prefix="/api", endpoint="runs/id", input=GetUrl)]
 pub async fn get_runs_id(id: i32) -> Result<SectionRuns, ApiError> {
     let pool = crate::auth::ssr::pool()?;
     let res_opts = expect_context::<leptos_axum::ResponseOptions>();
-    let runs = sqlx::query_as::<_, SectionRuns>(
-        r#"SELECT s.id, s.patch, s.layout, s.category, s.map,
-            COALESCE(NULLIF(ARRAY_AGG((r.id, r.section_id, u.id, u."name", r.time,
-                r.proof, r.yt_id, r.verified, r.is_pb, r.is_wr, r.created_at)
-            ORDER BY r.created_at ASC)
-            FILTER(WHERE r.id IS NOT NULL), '{NULL}'), '{}') AS runs
-        FROM section s
-        LEFT JOIN run r ON section_id = s.id
-        LEFT JOIN "user" u ON user_id = u.id
-        WHERE s.id = $1
-        GROUP BY s.id, patch, layout, category, map;"#,
-    )
-    .bind(id)
-    .fetch_one(&pool)
-    .await
-    .or(Err(ApiError::InvalidSection))?;
+    let runs = self::ssr::section_runs(&pool, id).await?;
 
-    res_opts.append_header(CACHE_CONTROL, HeaderValue::from_static("max-age=900"));
+    // Refetched on live updates, which a cached response would hide
+    res_opts.append_header(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
     Ok(runs)
 }
 
+/// The runs of every map of a leaderboard, see `ssr::category_runs`.
 #[server(GetRunsCategory, prefix="/api", endpoint="runs/category", input=GetUrl)]
-pub async fn get_runs_category(patch: String, layout: String, category: String) -> Result<Vec<SectionRuns>, ApiError> {
+pub async fn get_runs_category(
+    patch: String,
+    layout: String,
+    category: String,
+    filters: LeaderboardFilters,
+) -> Result<Vec<SectionRuns>, ApiError> {
     let pool = crate::auth::ssr::pool()?;
     let res_opts = expect_context::<leptos_axum::ResponseOptions>();
-    let runs = sqlx::query_as::<_, SectionRuns>(
-        r#"SELECT s.id, patch, layout, category, map,
-            COALESCE(NULLIF(ARRAY_AGG((r.id, r.section_id, r.user_id, u."name", r.time,
-                r.proof, r.yt_id, r.verified, r.is_pb, r.is_wr, r.created_at)
-            ORDER BY r.created_at ASC) 
-            FILTER(WHERE r.id IS NOT NULL), '{NULL}'), '{}') AS runs
-        FROM section s
-        LEFT JOIN run r ON section_id = s.id
-        LEFT JOIN "user" u ON user_id = u.id
-        WHERE patch = $1 AND layout = $2 AND category = $3
-        GROUP BY s.id, patch, layout, category, map
-        ORDER BY map;"#,
-    )
-    .bind(patch)
-    .bind(layout)
-    .bind(category)
-    .fetch_all(&pool)
-    .await
-    .or(Err(ApiError::ServerError("Database lookup failed".into())))?;
+    let runs = self::ssr::category_runs(&pool, patch, layout, category, &filters).await?;
 
-    res_opts.append_header(CACHE_CONTROL, HeaderValue::from_static("max-age=900"));
+    res_opts.append_header(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
     Ok(runs)
 }
 
+/// A page of runs, see `Keyset` for how `page` moves through them.
 #[server(GetRuns, prefix="/api", endpoint="runs/user", input=GetUrl)]
-pub async fn get_runs(filter: RunFilters, offset: i32) -> Result<Vec<Run>, ApiError> {
+pub async fn get_runs(filter: RunFilters, page: PageQuery) -> Result<Page<Run>, ApiError> {
+    use crate::pagination::Keyset;
     use sqlx::{Postgres, QueryBuilder};
 
     let pool = crate::auth::ssr::pool()?;
-    let mut query = QueryBuilder::<Postgres>::new(
-        r#"SELECT run.id, run.created_at, section_id, patch, layout, 
-            category, map, user_id, "name", time, proof, yt_id, verified, is_pb, is_wr
-        FROM run
-        INNER JOIN section s ON section_id = s.id
-        INNER JOIN "user" u ON user_id = u.id 
-        WHERE 1 = 1"#,
-    );
-    if let Some(user) = filter.user {
-        query.push(" AND user_id = ").push_bind(user);
-    }
-    if let Some(before) = filter.before {
-        query.push(" AND run.created_at <= ").push_bind(before);
-    }
-    if let Some(after) = filter.after {
-        query.push(" AND run.created_at >= ").push_bind(after);
-    }
-    if let Some(patch) = filter.patch {
-        query.push(" AND patch = ").push_bind(patch);
-    }
-    if let Some(layout) = filter.layout {
-        query.push(" AND layout = ").push_bind(layout);
-    }
-    if let Some(category) = filter.category {
-        query.push(" AND category = ").push_bind(category);
-    }
-    if let Some(map) = filter.map {
-        query.push(" AND map = ").push_bind(map);
-    }
-    if let Some(faster) = filter.faster {
-        query.push(" AND time <= ").push_bind(faster);
-    }
-    if let Some(slower) = filter.slower {
-        query.push(" AND time >= ").push_bind(slower);
-    }
-    let asc = match filter.ascending {
-        true => "ASC",
-        false => "DESC",
+    // Rejected runs are only listed to their submitter
+    let own = crate::auth::ssr::auth()
+        .ok()
+        .and_then(|a| a.current_user)
+        .is_some_and(|u| filter.user == Some(u.id));
+    let push_filters = |query: &mut QueryBuilder<'_, Postgres>| {
+        if !own {
+            query.push(" AND NOT rejected");
+        }
+        self::ssr::push_run_filters(query, &filter);
     };
-    query
-        .push(" ORDER BY ")
-        .push(match filter.sort.as_str() {
-            "section" => format!("patch {asc}, layout {asc}, category {asc}, map {asc}"),
-            "time" => format!("time {asc}"),
-            _ => format!("run.created_at {asc}"),
-        })
-        .push(" LIMIT 50 OFFSET ")
-        .push_bind(offset)
-        .push(";");
-    query
+    let keyset = Keyset::new(
+        self::ssr::run_keyset(&filter.sort),
+        filter.ascending,
+        page.cursor.as_deref(),
+        page.limit,
+    )?;
+
+    let mut query = QueryBuilder::<Postgres>::new(self::ssr::RUNS);
+    push_filters(&mut query);
+    keyset.push(&mut query);
+    let runs = query
+        .push(";")
         .build_query_as::<Run>()
         .fetch_all(&pool)
         .await
-        .or(Err(ApiError::ServerError("Database lookup failed".to_string())))
+        .or(Err(ApiError::ServerError("Database lookup failed".to_string())))?;
+    let mut runs = keyset.page(runs, |r| self::ssr::run_key(&filter.sort, r));
+
+    if page.total {
+        let mut query = QueryBuilder::<Postgres>::new(format!("SELECT COUNT(*) FROM ({}", self::ssr::RUNS));
+        push_filters(&mut query);
+        let total = query
+            .push(") AS runs;")
+            .build_query_scalar::<i64>()
+            .fetch_one(&pool)
+            .await
+            .or(Err(ApiError::ServerError("Database lookup failed".to_string())))?;
+        runs.total = Some(total);
+    }
+    Ok(runs)
 }
 
 #[server(GetMaps, prefix="/api", endpoint="maps", input=GetUrl)]
@@ -122,8 +361,9 @@ pub async fn get_maps() -> Result<Vec<Map>, ApiError> {
     let res_opts = expect_context::<leptos_axum::ResponseOptions>();
     let maps = sqlx::query_as::<_, Map>(
         r#"SELECT map, code
-        FROM section
-        WHERE patch='2.13' AND layout='1' AND category='Standard'
+        FROM section s
+        INNER JOIN patch p ON s.patch = p.name
+        WHERE p.current AND layout='1' AND category='Standard'
         ORDER BY map ASC;"#,
     )
     .fetch_all(&pool)
@@ -134,6 +374,16 @@ pub async fn get_maps() -> Result<Vec<Map>, ApiError> {
     Ok(maps)
 }
 
+#[server(GetPatches, prefix="/api", endpoint="patches", input=GetUrl)]
+pub async fn get_patches() -> Result<Vec<Patch>, ApiError> {
+    let pool = crate::auth::ssr::pool()?;
+    let res_opts = expect_context::<leptos_axum::ResponseOptions>();
+    let patches = self::ssr::patches(&pool).await?;
+
+    res_opts.append_header(CACHE_CONTROL, HeaderValue::from_static("max-age=900"));
+    Ok(patches)
+}
+
 #[server(GetUser, prefix="/api", endpoint="user/get", input=GetUrl)]
 pub async fn get_user(id: i64) -> Result<User, ApiError> {
     use crate::auth::ssr::*;
@@ -175,18 +425,7 @@ pub async fn get_rankings(
 #[server(GetRankingsUser, prefix="/api", endpoint="ranking/user", input=GetUrl)]
 pub async fn get_rankings_user(id: i64) -> Result<Vec<Ranking>, ApiError> {
     let pool = crate::auth::ssr::pool()?;
-
-    sqlx::query_as::<_, Ranking>(
-        r#"SELECT r.id, r.patch, r.layout, r.category, r.user_id, 
-            u.name, r.title, r.rank, r.rating, r.created_at, r.updated_at, r.percentage, r.points
-        FROM rank r
-        JOIN "user" u ON user_id = u.id
-        WHERE user_id = $1;"#,
-    )
-    .bind(id)
-    .fetch_all(&pool)
-    .await
-    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+    self::ssr::user_rankings(&pool, id).await
 }
 
 #[server(GetRandUser, prefix="/api", endpoint="user/get/random", input=GetUrl)]
@@ -207,62 +446,41 @@ pub async fn get_rand_user() -> Result<User, ApiError> {
     User::get(id.id, &pool).await.ok_or(ApiError::NotFound)
 }
 
+/// A page of activity, see `Keyset` for how `page` moves through it.
 #[server(GetActivity, prefix="/api", endpoint="activity/get", input=GetUrl)]
-pub async fn get_activity(filter: ActivityFilters, offset: i32) -> Result<Vec<Activity>, ApiError> {
+pub async fn get_activity(filter: ActivityFilters, page: PageQuery) -> Result<Page<Activity>, ApiError> {
+    use crate::pagination::Keyset;
     use sqlx::{Postgres, QueryBuilder};
 
     let pool = crate::auth::ssr::pool()?;
-    let mut query = QueryBuilder::<Postgres>::new(
-        r#"SELECT a.id, a.user_id, name, rank_id, patch, layout, category, 
-                    title_old, title_new, rank_old, rank_new, a.created_at
-                FROM activity a
-                INNER JOIN "user" u ON a.user_id = u.id
-                LEFT JOIN rank r ON rank_id = r.id
-                WHERE 1 = 1"#,
-    );
-    if let Some(event) = filter.event {
-        match event.as_str() {
-            "join" => query.push(" AND rank_id IS NULL"),
-            "rank" => query.push(" AND rank_new IS NOT NULL"),
-            "title" => query.push(" AND title_new IS NOT NULL"),
-            _ => &mut query,
-        };
-    }
-    if let Some(user) = filter.user {
-        query.push(" AND a.user_id = ").push_bind(user);
-    }
-    if let Some(patch) = filter.patch {
-        query.push(" AND patch = ").push_bind(patch);
-    }
-    if let Some(layout) = filter.layout {
-        query.push(" AND layout = ").push_bind(layout);
-    }
-    if let Some(category) = filter.category {
-        query.push(" AND category = ").push_bind(category);
-    }
-    if let Some(before) = filter.before {
-        query.push(" AND a.created_at <= ").push_bind(before);
-    }
-    if let Some(after) = filter.after {
-        query.push(" AND a.created_at >= ").push_bind(after);
-    }
-    let asc = match filter.ascending {
-        true => " ASC",
-        false => " DESC",
-    };
-    query
-        .push(" ORDER BY ")
-        .push(match filter.sort.as_str() {
-            "section" => format!("patch {asc}, layout {asc}, category {asc}"),
-            _ => format!("a.created_at {asc}"),
-        })
-        .push(" LIMIT 50 OFFSET ")
-        .push_bind(offset)
-        .push(";");
-
-    query
+    let keyset = Keyset::new(
+        self::ssr::activity_keyset(&filter.sort),
+        filter.ascending,
+        page.cursor.as_deref(),
+        page.limit,
+    )?;
+
+    let mut query = QueryBuilder::<Postgres>::new(self::ssr::ACTIVITY);
+    self::ssr::push_activity_filters(&mut query, &filter);
+    keyset.push(&mut query);
+    let activity = query
+        .push(";")
         .build_query_as::<Activity>()
         .fetch_all(&pool)
         .await
-        .or(Err(ApiError::ServerError("Database lookup failed".to_string())))
+        .or(Err(ApiError::ServerError("Database lookup failed".to_string())))?;
+    let mut activity = keyset.page(activity, |a| self::ssr::activity_key(&filter.sort, a));
+
+    if page.total {
+        let mut query = QueryBuilder::<Postgres>::new(format!("SELECT COUNT(*) FROM ({}", self::ssr::ACTIVITY));
+        self::ssr::push_activity_filters(&mut query, &filter);
+        let total = query
+            .push(") AS activity;")
+            .build_query_scalar::<i64>()
+            .fetch_one(&pool)
+            .await
+            .or(Err(ApiError::ServerError("Database lookup failed".to_string())))?;
+        activity.total = Some(total);
+    }
+    Ok(activity)
 }
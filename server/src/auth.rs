@@ -7,6 +7,8 @@ use types::api::*;
 
 #[cfg(feature = "ssr")]
 pub mod ssr {
+    pub use crate::proof::ProofValidators;
+    pub use crate::ranking::recalculate_section;
     pub use argon2::{
         Argon2, PasswordHash, PasswordVerifier,
         password_hash::{PasswordHasher, SaltString, rand_core::OsRng},
@@ -15,11 +17,14 @@ pub mod ssr {
     pub use axum_session_sqlx::SessionPgPool;
     pub use leptos::prelude::{server, use_context};
     use oauth2::basic::BasicClient;
+    pub use serde_json::{Value, json};
     pub use sqlx::{
-        PgPool,
+        PgConnection, PgExecutor, PgPool,
+        migrate::{Migrate, Migrator},
         postgres::{PgConnectOptions, PgPoolOptions},
     };
     pub use std::env;
+    use std::net::{IpAddr, SocketAddr};
     pub use types::{api::*, internal::ssr::*, leptos::AuthSession};
 
     pub async fn connect_to_database() -> PgPool {
@@ -37,6 +42,39 @@ pub mod ssr {
             .unwrap()
     }
 
+    /// Schema migrations embedded from the workspace `migrations` directory.
+    pub static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+    /// Brings the database schema up to date, creating it from scratch on an empty database.
+    pub async fn migrate_database(pool: &PgPool) {
+        MIGRATOR.run(pool).await.expect("Failed to apply database migrations!");
+    }
+
+    /// Panics unless every embedded migration has been applied unchanged.
+    /// For services that share the database but don't own its schema.
+    pub async fn verify_database(pool: &PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let applied = conn
+            .list_applied_migrations()
+            .await
+            .expect("Missing migrations table, start the site first!");
+        for migration in MIGRATOR.iter() {
+            match applied.iter().find(|a| a.version == migration.version) {
+                Some(a) if a.checksum == migration.checksum => {}
+                Some(_) => panic!("Migration {} was modified after being applied!", migration.version),
+                None => panic!("Migration {} is not applied!", migration.version),
+            }
+        }
+    }
+
+    /// Name of the patch currently accepting submissions.
+    pub async fn current_patch(pool: &PgPool) -> Result<String, ApiError> {
+        sqlx::query_scalar::<_, String>("SELECT name FROM patch WHERE current;")
+            .fetch_one(pool)
+            .await
+            .map_err(|_| ApiError::ServerError("No current patch".into()))
+    }
+
     pub fn pool() -> Result<PgPool, ApiError> {
         use_context::<PgPool>().ok_or(ApiError::ServerError("Pool missing.".into()))
     }
@@ -49,6 +87,407 @@ pub mod ssr {
         use_context::<BasicClient>().ok_or(ApiError::ServerError("OAuth client missing.".into()))
     }
 
+    pub fn proof_validators() -> Result<ProofValidators, ApiError> {
+        use_context::<ProofValidators>().ok_or(ApiError::ServerError("Proof validators missing.".into()))
+    }
+
+    /// Address of the client of the current request. Requests from loopback are
+    /// assumed to come through the reverse proxy, which appends the client to `X-Forwarded-For`.
+    pub fn client_ip() -> Option<IpAddr> {
+        let peer = use_context::<SocketAddr>()?.ip();
+        if !peer.is_loopback() {
+            return Some(peer);
+        }
+        use_context::<http::request::Parts>()
+            .and_then(|parts| {
+                let forwarded = parts.headers.get("x-forwarded-for")?.to_str().ok()?;
+                forwarded.rsplit(',').next()?.trim().parse().ok()
+            })
+            .or(Some(peer))
+    }
+
+    /// Creates a user with the default set of permissions and returns its id. An empty
+    /// `passhash` creates an account that can only log in through Discord.
+    pub async fn create_user(conn: &mut PgConnection, username: &str, passhash: &str) -> Result<i64, ApiError> {
+        let id = sqlx::query_scalar::<_, i64>(
+            r#"INSERT INTO "user" (name, password) VALUES ($1, $2)
+            ON CONFLICT ON CONSTRAINT unique_user DO NOTHING
+            RETURNING id;"#,
+        )
+        .bind(username)
+        .bind(passhash)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?
+        .ok_or(ApiError::AlreadyExists)?;
+        audit(
+            &mut *conn,
+            id,
+            AuditAction::Register,
+            format!("user:{id}"),
+            None,
+            Some(json!({ "name": username })),
+        )
+        .await?;
+
+        sqlx::query(
+            r#"INSERT INTO permission (user_id, token) 
+            VALUES ($1, $2), ($1, $3), ($1, $4), ($1, $5);"#,
+        )
+        .bind(id)
+        .bind(Permissions::View)
+        .bind(Permissions::Submit)
+        .bind(Permissions::Trusted)
+        .bind(Permissions::Delete)
+        .execute(&mut *conn)
+        .await
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+        Ok(id)
+    }
+
+    /// Registers a user for a Discord account that is not linked yet. The Discord name is
+    /// used as username, with a random suffix if it is already taken.
+    pub async fn register_discord_user(conn: &mut PgConnection, discord_name: &str) -> Result<i64, ApiError> {
+        use rand::{Rng, thread_rng};
+
+        let mut base = discord_name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-' || *c == '.')
+            .take(27)
+            .collect::<String>();
+        if base.len() < 2 {
+            base = "player".into();
+        }
+        let mut username = base.clone();
+        for _ in 0..5 {
+            if check_username(&username) {
+                match create_user(conn, &username, "").await {
+                    Err(ApiError::AlreadyExists) => {}
+                    res => return res,
+                }
+            }
+            username = format!("{base}-{:04}", thread_rng().gen_range(0..10000));
+        }
+        Err(ApiError::AlreadyExists)
+    }
+
+    /// The user a Discord account signs in as, the first one that linked it and is not disabled.
+    pub async fn discord_user<'e>(executor: impl PgExecutor<'e>, snowflake: &str) -> Result<Option<i64>, ApiError> {
+        sqlx::query_scalar::<_, i64>(
+            r#"SELECT d.user_id
+            FROM discord d
+            INNER JOIN "user" u ON d.user_id = u.id
+            WHERE d.snowflake = $1 AND u.disabled_at IS NULL
+            ORDER BY d.created_at ASC
+            LIMIT 1;"#,
+        )
+        .bind(snowflake)
+        .fetch_optional(executor)
+        .await
+        .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+    }
+
+    /// Sends the user to Discord to authorize the site. Discord redirects back to `discord_auth`.
+    pub fn discord_authorize(auth: &AuthSession) -> Result<(), ApiError> {
+        use oauth2::{CsrfToken, Scope};
+
+        let (auth_url, csrf_token) = oauth()?
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("identify".into()))
+            .add_scope(Scope::new("role_connections.write".into()))
+            .url();
+
+        auth.session.set("csrf", csrf_token);
+        leptos_axum::redirect(auth_url.as_ref());
+        Ok(())
+    }
+
+    /// Records a mutating action of `actor` on `target` in the audit log.
+    pub async fn audit<'e>(
+        executor: impl PgExecutor<'e>,
+        actor: i64,
+        action: AuditAction,
+        target: impl ToString,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) -> Result<(), ApiError> {
+        sqlx::query(
+            r#"INSERT INTO audit_log (actor_id, action, target, before, after, ip)
+            VALUES ($1, $2, $3, $4, $5, $6::inet);"#,
+        )
+        .bind(actor)
+        .bind(action)
+        .bind(target.to_string())
+        .bind(before)
+        .bind(after)
+        .bind(client_ip().map(|ip| ip.to_string()))
+        .execute(executor)
+        .await
+        .map_err(|_| ApiError::ServerError("Audit log insert failed".into()))?;
+        Ok(())
+    }
+
+    /// Failed attempts before a username or an IP is locked out. IPs get more room since
+    /// many players can share one.
+    const USERNAME_ATTEMPTS: i32 = 5;
+    const IP_ATTEMPTS: i32 = 20;
+
+    /// Keys that failed logins are tracked under for `username` and the current client.
+    pub fn attempt_keys(username: &str) -> Vec<(String, i32)> {
+        let mut keys = vec![(format!("user:{}", username.to_lowercase()), USERNAME_ATTEMPTS)];
+        if let Some(ip) = client_ip() {
+            keys.push((format!("ip:{ip}"), IP_ATTEMPTS));
+        }
+        keys
+    }
+
+    /// Fails with the remaining wait time if any of the keys is locked out.
+    pub async fn check_lockout(pool: &PgPool, keys: &[(String, i32)]) -> Result<(), ApiError> {
+        let wait = sqlx::query_scalar::<_, Option<i64>>(
+            r#"SELECT ceil(max(EXTRACT(EPOCH FROM locked_until - now())))::bigint
+            FROM login_attempt
+            WHERE key = ANY($1) AND locked_until > now();"#,
+        )
+        .bind(keys.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>())
+        .fetch_one(pool)
+        .await
+        .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+        match wait {
+            Some(secs) => Err(ApiError::TooManyAttempts(secs)),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts a failed attempt for every key. Failures are forgotten after an hour without
+    /// attempts, past the threshold every failure doubles the lockout, up to an hour.
+    pub async fn record_failure(pool: &PgPool, keys: &[(String, i32)]) -> Result<(), ApiError> {
+        for (key, threshold) in keys {
+            let failures = sqlx::query_scalar::<_, i32>(
+                r#"INSERT INTO login_attempt AS a (key, failures) VALUES ($1, 1)
+                ON CONFLICT (key) DO UPDATE SET
+                    failures = CASE WHEN a.updated_at < now() - interval '1 hour' THEN 1 ELSE a.failures + 1 END,
+                    updated_at = now()
+                RETURNING failures;"#,
+            )
+            .bind(key)
+            .fetch_one(pool)
+            .await
+            .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+            if failures >= *threshold {
+                let secs = (30i64 << (failures - threshold).min(7)).min(3600);
+                sqlx::query(
+                    "UPDATE login_attempt SET locked_until = now() + make_interval(secs => $2) WHERE key = $1;",
+                )
+                .bind(key)
+                .bind(secs as f64)
+                .execute(pool)
+                .await
+                .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the failed attempts for a username after a successful login.
+    pub async fn clear_failures(pool: &PgPool, username: &str) -> Result<(), ApiError> {
+        sqlx::query("DELETE FROM login_attempt WHERE key = $1;")
+            .bind(format!("user:{}", username.to_lowercase()))
+            .execute(pool)
+            .await
+            .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+        Ok(())
+    }
+
+    /// Minutes after submitting in which the owner of a run may still edit it.
+    pub const EDIT_GRACE_MINUTES: i32 = 60;
+
+    /// Removes every stored session of `user_id` except `keep`, logging the user out on all
+    /// other devices. Sessions still cached in memory are logged out by `track_session`.
+    pub async fn remove_sessions<'e>(
+        executor: impl PgExecutor<'e>,
+        user_id: i64,
+        keep: Option<String>,
+    ) -> Result<u64, ApiError> {
+        sqlx::query(
+            r#"WITH revoked AS (
+                UPDATE user_session
+                SET revoked = TRUE
+                WHERE user_id = $1 AND session_id IS DISTINCT FROM $2 AND NOT revoked
+                RETURNING session_id
+            )
+            DELETE FROM session
+            WHERE id IS DISTINCT FROM $2
+                AND (id IN (SELECT session_id FROM revoked) OR session::jsonb -> 'data' ->> 'user_id' = $1::text);"#,
+        )
+        .bind(user_id)
+        .bind(keep)
+        .execute(executor)
+        .await
+        .map(|res| res.rows_affected())
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))
+    }
+
+    /// Logs `user_id` in on the current session and starts tracking it for the session list.
+    pub async fn start_session(
+        auth: &AuthSession,
+        pool: &PgPool,
+        user_id: i64,
+        remember: bool,
+    ) -> Result<(), ApiError> {
+        auth.login_user(user_id);
+        auth.remember_user(remember);
+        let user_agent = use_context::<http::request::Parts>().and_then(|parts| {
+            parts
+                .headers
+                .get(http::header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.chars().take(512).collect::<String>())
+        });
+        sqlx::query(
+            r#"INSERT INTO user_session (session_id, user_id, user_agent, remember)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT ON CONSTRAINT unique_session DO UPDATE
+            SET user_id = $2, user_agent = $3, remember = $4, revoked = FALSE, created_at = now(), last_seen = now();"#,
+        )
+        .bind(auth.session.get_session_id().to_string())
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(remember)
+        .execute(pool)
+        .await
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+        Ok(())
+    }
+
+    /// Updates when the session of a logged in user was last seen. Sessions revoked from
+    /// another device are logged out before the request is handled.
+    pub async fn track_session(mut auth: AuthSession, pool: &PgPool, user_agent: Option<&str>) -> AuthSession {
+        let Some(user_id) = auth.current_user.as_ref().map(|u| u.id) else {
+            return auth;
+        };
+        let session_id = auth.session.get_session_id().to_string();
+        // Most requests only read, `last_seen` is written at most once a minute
+        let known = sqlx::query_as::<_, (bool, bool)>(
+            r#"SELECT revoked OR user_id <> $2, last_seen < now() - interval '1 minute'
+            FROM user_session
+            WHERE session_id = $1;"#,
+        )
+        .bind(&session_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .unwrap_or(None);
+        let revoked = match known {
+            Some((revoked, false)) => revoked,
+            _ => sqlx::query_scalar::<_, bool>(
+                r#"INSERT INTO user_session (session_id, user_id, user_agent)
+                VALUES ($1, $2, $3)
+                ON CONFLICT ON CONSTRAINT unique_session DO UPDATE SET last_seen = now()
+                RETURNING revoked OR user_session.user_id <> $2;"#,
+            )
+            .bind(&session_id)
+            .bind(user_id)
+            .bind(user_agent.map(|v| v.chars().take(512).collect::<String>()))
+            .fetch_one(pool)
+            .await
+            .unwrap_or(false),
+        };
+        if revoked {
+            auth.logout_user();
+            auth.current_user = None;
+        }
+        auth
+    }
+
+    /// Deletes the metadata of sessions that expired or were removed from the session store,
+    /// once an hour in the background.
+    pub fn prune_sessions(pool: PgPool) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+            loop {
+                interval.tick().await;
+                // New sessions are only stored at the end of their first request
+                let res = sqlx::query(
+                    r#"DELETE FROM user_session us
+                    WHERE us.last_seen < now() - interval '1 hour'
+                    AND NOT EXISTS (
+                        SELECT 1 FROM session s
+                        WHERE s.id = us.session_id AND (s.expires IS NULL OR s.expires > extract(epoch FROM now()))
+                    );"#,
+                )
+                .execute(&pool)
+                .await;
+                match res {
+                    Ok(res) if res.rows_affected() > 0 => log::info!("Pruned {} sessions", res.rows_affected()),
+                    Ok(_) => {}
+                    Err(e) => log::error!("Failed to prune sessions: {e}"),
+                }
+            }
+        });
+    }
+
+    /// Marks a request authenticated with a personal access token instead of a session.
+    #[derive(Clone, Copy, Debug)]
+    pub struct TokenAuth {
+        pub id: i32,
+    }
+
+    /// Tokens are long and random, so an unsalted hash is enough to look them up by.
+    pub fn hash_token(token: &str) -> Vec<u8> {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(token.as_bytes()).to_vec()
+    }
+
+    /// The user a personal access token belongs to, keeping only the permissions in the
+    /// token's scopes. `None` for unknown tokens and disabled users.
+    pub async fn token_user(pool: &PgPool, token: &str) -> Option<(User, TokenAuth)> {
+        let (id, user_id, scopes) = sqlx::query_as::<_, (i32, i64, Vec<Permissions>)>(
+            r#"UPDATE api_token
+            SET last_used_at = now()
+            WHERE hash = $1
+            RETURNING id, user_id, scopes;"#,
+        )
+        .bind(hash_token(token))
+        .fetch_optional(pool)
+        .await
+        .ok()??;
+        if is_disabled(user_id, pool).await {
+            return None;
+        }
+        let mut user = User::get(user_id, pool).await?;
+        let permissions = scopes.into_iter().filter(|s| user.has(s)).collect();
+        user.permissions = permissions;
+        Some((user, TokenAuth { id }))
+    }
+
+    /// Authenticates a request from its `Authorization: Bearer` token. The session is never
+    /// used for these requests.
+    pub async fn authenticate_token(
+        mut auth: AuthSession,
+        pool: &PgPool,
+        token: &str,
+    ) -> (AuthSession, Option<TokenAuth>) {
+        let (user, token) = token_user(pool, token).await.unzip();
+        auth.current_user = user;
+        (auth, token)
+    }
+
+    /// Account settings, sessions and tokens themselves can only be managed from a session.
+    pub fn session_only() -> Result<(), ApiError> {
+        match use_context::<TokenAuth>() {
+            Some(_) => Err(ApiError::Unauthorized),
+            None => Ok(()),
+        }
+    }
+
+    /// Recovery codes are compared case insensitively and without the separating dash.
+    pub fn normalize_recovery_code(code: &str) -> String {
+        code.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    }
+
     pub fn hash_password(password: &String) -> Result<String, ApiError> {
         let salt = SaltString::generate(&mut OsRng);
         let argon2 = Argon2::default();
@@ -59,6 +498,10 @@ pub mod ssr {
     }
 
     pub fn verify_password(pass_hash: &String, password: &String) -> Result<(), ApiError> {
+        // Accounts created through Discord have no password until they set one.
+        if pass_hash.is_empty() {
+            return Err(ApiError::InvalidCredentials);
+        }
         let pwd_parsed = PasswordHash::new(pass_hash)
             .map_err(|_| ApiError::ServerError("Login failed: Failed to hash password".into()))?;
 
@@ -67,6 +510,15 @@ pub mod ssr {
             .or(Err(ApiError::InvalidCredentials))
     }
 
+    /// Re-authenticates a logged in user before a sensitive action. A session alone is not
+    /// enough, so users registered through Discord have to set a password first.
+    pub fn verify_current_password(pass_hash: &String, password: &String) -> Result<(), ApiError> {
+        if pass_hash.is_empty() {
+            return Err(ApiError::PasswordRequired);
+        }
+        verify_password(pass_hash, password)
+    }
+
     pub fn check_password(password: &String) -> bool {
         password.len() >= 8 && password.len() <= 256
     }
@@ -78,6 +530,72 @@ pub mod ssr {
                 .chars()
                 .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
     }
+
+    /// Id of the section of the current patch runs are submitted to.
+    pub async fn current_section(pool: &PgPool, layout: &str, category: &str, map: &str) -> Result<i32, ApiError> {
+        sqlx::query_scalar::<_, i32>(
+            r#"SELECT s.id
+            FROM section s
+            INNER JOIN patch p ON s.patch = p.name
+            WHERE p.current AND layout=$1 AND category=$2 AND map=$3;"#,
+        )
+        .bind(layout)
+        .bind(category)
+        .bind(map)
+        .fetch_one(pool)
+        .await
+        .or(Err(ApiError::InvalidSection))
+    }
+
+    /// Submits a run of `user`, from the submit form or the Discord `/submit` command.
+    /// Returns the id of the run.
+    pub async fn submit_run(
+        pool: &PgPool,
+        validators: &ProofValidators,
+        user: &User,
+        section_id: i32,
+        time: rust_decimal::Decimal,
+        proof: &str,
+    ) -> Result<i32, ApiError> {
+        if !user.has(&Permissions::Submit) {
+            return Err(ApiError::Unauthorized);
+        }
+        let proof = validators.validate(proof).await?;
+        let verified = user.has(&Permissions::Trusted);
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+        let id = sqlx::query_scalar::<_, i32>(
+            r#"INSERT INTO run (section_id, user_id, time, proof, yt_id, proof_host, verified)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING id;"#,
+        )
+        .bind(section_id)
+        .bind(user.id)
+        .bind(time)
+        .bind(&proof.url)
+        .bind(&proof.id)
+        .bind(proof.host)
+        .bind(verified)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+        recalculate_section(&mut tx, section_id).await?;
+        audit(
+            &mut *tx,
+            user.id,
+            AuditAction::Submit,
+            format!("run:{id}"),
+            None,
+            Some(json!({ "section_id": section_id, "time": time, "proof": proof.url, "verified": verified })),
+        )
+        .await?;
+        tx.commit()
+            .await
+            .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+        Ok(id)
+    }
 }
 
 #[server(GetCurrentUser, prefix="/api", endpoint="user/@me/get", input=PostUrl)]
@@ -103,35 +621,21 @@ pub async fn register(
     }
 
     let pool = pool()?;
+    session_only()?;
     let auth = auth()?;
 
     let pwd_hash = hash_password(&password)?;
 
-    sqlx::query("INSERT INTO \"user\" (name, password) VALUES ($1, $2);")
-        .bind(username.clone())
-        .bind(pwd_hash)
-        .execute(&pool)
+    let mut tx = pool
+        .begin()
         .await
-        .map_err(|_| ApiError::AlreadyExists)?;
-
-    let user = User::get_from_username(username, &pool)
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+    let id = create_user(&mut tx, &username, &pwd_hash).await?;
+    tx.commit()
         .await
-        .ok_or_else(|| ApiError::ServerError("Signup failed: User does not exist.".into()))?;
-
-    let _ = sqlx::query(
-        r#"INSERT INTO permission (user_id, token) 
-            VALUES ($1, $2), ($1, $3), ($1, $4), ($1, $5);"#,
-    )
-    .bind(user.id)
-    .bind(Permissions::View)
-    .bind(Permissions::Submit)
-    .bind(Permissions::Trusted)
-    .bind(Permissions::Delete)
-    .execute(&pool)
-    .await;
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
 
-    auth.login_user(user.id);
-    auth.remember_user(remember.is_some());
+    start_session(&auth, &pool, id, remember.is_some()).await?;
 
     leptos_axum::redirect("/");
 
@@ -148,15 +652,27 @@ pub async fn login(
     use self::ssr::*;
 
     let pool = pool()?;
+    session_only()?;
     let auth = auth()?;
 
-    let (user, UserPasshash(expected_passhash)) = User::get_from_username_with_passhash(username, &pool)
-        .await
-        .ok_or(ApiError::InvalidCredentials)?;
-    verify_password(&expected_passhash, &password)?;
+    let keys = attempt_keys(&username);
+    check_lockout(&pool, &keys).await?;
 
-    auth.login_user(user.id);
-    auth.remember_user(remember.is_some());
+    let verified = match User::get_from_username_with_passhash(username.clone(), &pool).await {
+        Some((user, _)) if is_disabled(user.id, &pool).await => Err(ApiError::InvalidCredentials),
+        Some((user, UserPasshash(expected_passhash))) => verify_password(&expected_passhash, &password).map(|_| user),
+        None => Err(ApiError::InvalidCredentials),
+    };
+    let user = match verified {
+        Ok(user) => user,
+        Err(ApiError::InvalidCredentials) => {
+            record_failure(&pool, &keys).await?;
+            return Err(ApiError::InvalidCredentials);
+        }
+        Err(e) => return Err(e),
+    };
+    clear_failures(&pool, &username).await?;
+    start_session(&auth, &pool, user.id, remember.is_some()).await?;
     match HeaderValue::from_str(&format!("/{}", redirect.unwrap_or(String::new()))) {
         Ok(r) => leptos_axum::redirect(r.to_str().unwrap_or("/")),
         Err(_) => leptos_axum::redirect("/"),
@@ -172,6 +688,7 @@ pub async fn update_creds(
 ) -> Result<(), ApiError> {
     use self::ssr::*;
 
+    session_only()?;
     let auth = auth()?;
     if !auth.current_user.is_some() {
         Err(ApiError::Unauthenticated)?;
@@ -183,16 +700,32 @@ pub async fn update_creds(
         if !check_username(&name) {
             Err(ApiError::InvalidCredentials)?;
         }
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
         sqlx::query(
             r#"UPDATE "user"
             SET name = $1
             WHERE id = $2;"#,
         )
-        .bind(name)
+        .bind(&name)
         .bind(curr_user.id)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .or(Err(ApiError::AlreadyExists))?;
+        audit(
+            &mut *tx,
+            curr_user.id,
+            AuditAction::UpdateUsername,
+            format!("user:{}", curr_user.id),
+            Some(json!({ "name": curr_user.username })),
+            Some(json!({ "name": name })),
+        )
+        .await?;
+        tx.commit()
+            .await
+            .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
         auth.cache_clear_user(curr_user.id);
     }
     if let Some(pw) = password {
@@ -200,12 +733,19 @@ pub async fn update_creds(
             User::get_from_username_with_passhash(curr_user.username.clone(), &pool)
                 .await
                 .ok_or(ApiError::InvalidCredentials)?;
-        verify_password(&expected_passhash, &pw.old)?;
+        // Users registered through Discord set their first password without an old one
+        if !expected_passhash.is_empty() {
+            verify_password(&expected_passhash, &pw.old)?;
+        }
         if !check_password(&pw.new) {
             Err(ApiError::InvalidCredentials)?;
         }
         let pwd_hash = hash_password(&pw.new)?;
 
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
         sqlx::query(
             r#"UPDATE "user"
             SET password = $1
@@ -213,9 +753,22 @@ pub async fn update_creds(
         )
         .bind(pwd_hash)
         .bind(curr_user.id)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+        remove_sessions(&mut *tx, curr_user.id, Some(auth.session.get_session_id().to_string())).await?;
+        audit(
+            &mut *tx,
+            curr_user.id,
+            AuditAction::UpdatePassword,
+            format!("user:{}", curr_user.id),
+            None,
+            None,
+        )
+        .await?;
+        tx.commit()
+            .await
+            .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
         auth.cache_clear_user(curr_user.id);
     }
     if let Some(red) = redirect {
@@ -230,6 +783,7 @@ pub async fn update_creds(
 pub async fn update_bio(bio: Option<String>, redirect: Option<String>) -> Result<(), ApiError> {
     use self::ssr::*;
 
+    session_only()?;
     let auth = auth()?;
     if !auth.current_user.is_some() {
         Err(ApiError::Unauthenticated)?;
@@ -240,16 +794,32 @@ pub async fn update_bio(bio: Option<String>, redirect: Option<String>) -> Result
     if bio.clone().is_some_and(|b| b.len() > 512) {
         Err(ApiError::InvalidCredentials)?;
     }
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
     sqlx::query(
         r#"UPDATE "user"
         SET bio = $1
         WHERE id = $2;"#,
     )
-    .bind(bio)
+    .bind(&bio)
     .bind(curr_user.id)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await
     .or(Err(ApiError::InvalidCredentials))?;
+    audit(
+        &mut *tx,
+        curr_user.id,
+        AuditAction::UpdateBio,
+        format!("user:{}", curr_user.id),
+        Some(json!({ "bio": curr_user.bio })),
+        Some(json!({ "bio": bio })),
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
     auth.cache_clear_user(curr_user.id);
 
     if let Some(red) = redirect {
@@ -262,11 +832,12 @@ pub async fn update_bio(bio: Option<String>, redirect: Option<String>) -> Result
 
 #[server(UpdatePfp, prefix="/api", endpoint="user/update/avatar", input=MultipartFormData)]
 pub async fn update_pfp(data: MultipartData) -> Result<(), ApiError> {
-    use crate::auth::ssr::{auth, pool};
+    use crate::auth::ssr::{audit, auth, json, pool, session_only};
     use rand::{Rng, distributions::Alphanumeric, thread_rng};
     use std::fs::{File, remove_file};
     use std::io::{BufWriter, Write};
 
+    session_only()?;
     let auth = auth()?;
     let pool = pool()?;
 
@@ -315,6 +886,10 @@ pub async fn update_pfp(data: MultipartData) -> Result<(), ApiError> {
                 writer
                     .flush()
                     .map_err(|_| ApiError::ServerError("Failed to save file".into()))?;
+                let mut tx = pool
+                    .begin()
+                    .await
+                    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
                 sqlx::query(
                     r#"UPDATE "user"
                     SET pfp = $1
@@ -322,9 +897,21 @@ pub async fn update_pfp(data: MultipartData) -> Result<(), ApiError> {
                 )
                 .bind(name.clone())
                 .bind(user.id)
-                .execute(&pool)
+                .execute(&mut *tx)
                 .await
                 .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+                audit(
+                    &mut *tx,
+                    user.id,
+                    AuditAction::UpdateAvatar,
+                    format!("user:{}", user.id),
+                    Some(json!({ "pfp": user.pfp })),
+                    Some(json!({ "pfp": name })),
+                )
+                .await?;
+                tx.commit()
+                    .await
+                    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
 
                 auth.cache_clear_user(user.id);
                 Ok(())
@@ -350,17 +937,467 @@ pub async fn update_pfp(data: MultipartData) -> Result<(), ApiError> {
 pub async fn logout() -> Result<(), ApiError> {
     use self::ssr::*;
 
+    session_only()?;
+    let auth = auth()?;
+
+    auth.logout_user();
+    leptos_axum::redirect("/");
+
+    Ok(())
+}
+
+/// Anonymizes the current user after checking their password. Runs and ranks are kept
+/// under the anonymized name, everything tying the account to a person is removed.
+#[server(DisableAccount, prefix="/api", endpoint="user/disable", input=PostUrl)]
+pub async fn disable_account(password: String) -> Result<(), ApiError> {
+    use self::ssr::*;
+    use std::fs::remove_file;
+
+    session_only()?;
     let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.clone().ok_or(ApiError::Unauthenticated)?;
+
+    let (_, UserPasshash(expected_passhash)) = User::get_with_passhash(user.id, &pool)
+        .await
+        .ok_or(ApiError::Unauthenticated)?;
+    verify_current_password(&expected_passhash, &password)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    let name = sqlx::query_scalar::<_, String>(
+        r#"UPDATE "user"
+        SET name = 'Deleted #' || id, bio = NULL, pfp = 'default', password = '', disabled_at = now()
+        WHERE id = $1
+        RETURNING name;"#,
+    )
+    .bind(user.id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    for query in [
+        "DELETE FROM discord WHERE user_id = $1;",
+        "DELETE FROM permission WHERE user_id = $1;",
+        "DELETE FROM recovery_code WHERE user_id = $1;",
+        "DELETE FROM api_token WHERE user_id = $1;",
+    ] {
+        sqlx::query(query)
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    }
+    remove_sessions(&mut *tx, user.id, None).await?;
+    audit(
+        &mut *tx,
+        user.id,
+        AuditAction::Disable,
+        format!("user:{}", user.id),
+        None,
+        Some(json!({ "name": name })),
+    )
+    .await?;
+    // Earlier entries still hold the old names, bio, linked accounts and addresses
+    sqlx::query(
+        r#"UPDATE audit_log
+        SET before = NULL, after = NULL, target = 'discord'
+        WHERE actor_id = $1 AND target LIKE 'discord:%';"#,
+    )
+    .bind(user.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    sqlx::query(
+        r#"UPDATE audit_log
+        SET before = NULL, after = NULL
+        WHERE target = $1 AND action <> 'Disable';"#,
+    )
+    .bind(format!("user:{}", user.id))
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    sqlx::query("UPDATE audit_log SET ip = NULL WHERE actor_id = $1;")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
 
+    if user.pfp != "default" {
+        let _ = remove_file(format!("../cdn/users/{}.jpg", user.pfp));
+    }
+    auth.cache_clear_user(user.id);
     auth.logout_user();
     leptos_axum::redirect("/");
+    Ok(())
+}
+
+/// Replaces all recovery codes of the current user with a fresh set. The plain codes
+/// are only ever returned here, the database keeps their hashes.
+#[server(GenerateRecoveryCodes, prefix="/api", endpoint="user/recovery/generate", input=PostUrl)]
+pub async fn generate_recovery_codes(password: String) -> Result<Vec<String>, ApiError> {
+    use self::ssr::*;
+    use rand::{Rng, distributions::Alphanumeric, thread_rng};
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.ok_or(ApiError::Unauthenticated)?;
+
+    let (_, UserPasshash(expected_passhash)) = User::get_with_passhash(user.id, &pool)
+        .await
+        .ok_or(ApiError::Unauthenticated)?;
+    verify_current_password(&expected_passhash, &password)?;
+
+    let codes = (0..10)
+        .map(|_| {
+            let code = normalize_recovery_code(
+                &thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(10)
+                    .map(char::from)
+                    .collect::<String>(),
+            );
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect::<Vec<_>>();
+    let hashes = codes
+        .iter()
+        .map(|c| hash_password(&normalize_recovery_code(c)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    sqlx::query("DELETE FROM recovery_code WHERE user_id = $1;")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    sqlx::query("INSERT INTO recovery_code (user_id, code) SELECT $1, * FROM UNNEST($2::varchar[]);")
+        .bind(user.id)
+        .bind(&hashes)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+    audit(
+        &mut *tx,
+        user.id,
+        AuditAction::GenerateRecoveryCodes,
+        format!("user:{}", user.id),
+        None,
+        Some(json!({ "count": codes.len() })),
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+
+    Ok(codes)
+}
+
+/// Sets a new password using one of the recovery codes. The code is used up and every
+/// existing session of the account is removed before logging in this one.
+#[server(Recover, prefix="/api", endpoint="user/recover", input=PostUrl)]
+pub async fn recover(username: String, code: String, password: String) -> Result<(), ApiError> {
+    use self::ssr::*;
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+
+    if !check_password(&password) {
+        return Err(ApiError::InvalidInput);
+    }
+    let keys = attempt_keys(&username);
+    check_lockout(&pool, &keys).await?;
+    let Some(user) = User::get_from_username(username.clone(), &pool).await else {
+        record_failure(&pool, &keys).await?;
+        return Err(ApiError::InvalidCredentials);
+    };
+    if is_disabled(user.id, &pool).await {
+        record_failure(&pool, &keys).await?;
+        return Err(ApiError::InvalidCredentials);
+    }
 
+    let code = normalize_recovery_code(&code);
+    let hashes = sqlx::query_as::<_, (i32, String)>("SELECT id, code FROM recovery_code WHERE user_id = $1;")
+        .bind(user.id)
+        .fetch_all(&pool)
+        .await
+        .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+    let Some(id) = hashes
+        .into_iter()
+        .find(|(_, hash)| verify_password(hash, &code).is_ok())
+        .map(|(id, _)| id)
+    else {
+        record_failure(&pool, &keys).await?;
+        return Err(ApiError::InvalidCredentials);
+    };
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    let used = sqlx::query("DELETE FROM recovery_code WHERE id = $1;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?
+        .rows_affected();
+    if used == 0 {
+        return Err(ApiError::InvalidCredentials);
+    }
+    sqlx::query(r#"UPDATE "user" SET password = $1 WHERE id = $2;"#)
+        .bind(hash_password(&password)?)
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    remove_sessions(&mut *tx, user.id, None).await?;
+    audit(
+        &mut *tx,
+        user.id,
+        AuditAction::Recover,
+        format!("user:{}", user.id),
+        None,
+        None,
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+
+    clear_failures(&pool, &username).await?;
+    auth.cache_clear_user(user.id);
+    start_session(&auth, &pool, user.id, false).await?;
+    leptos_axum::redirect("/user/@me/dashboard");
     Ok(())
 }
 
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
-struct SectionId {
-    id: i32,
+#[server(GetSessions, prefix="/api", endpoint="user/sessions", input=PostUrl)]
+pub async fn get_sessions() -> Result<Vec<SessionInfo>, ApiError> {
+    use self::ssr::*;
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.as_ref().ok_or(ApiError::Unauthenticated)?;
+
+    sqlx::query_as::<_, SessionInfo>(
+        r#"SELECT us.id, us.user_agent, us.remember, us.created_at, us.last_seen, us.session_id = $2 AS current
+        FROM user_session us
+        JOIN session s ON s.id = us.session_id
+        WHERE us.user_id = $1 AND NOT us.revoked
+        ORDER BY current DESC, us.last_seen DESC;"#,
+    )
+    .bind(user.id)
+    .bind(auth.session.get_session_id().to_string())
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+}
+
+/// Logs out one of the current user's other sessions.
+#[server(RevokeSession, prefix="/api", endpoint="user/sessions/revoke", input=PostUrl)]
+pub async fn revoke_session(id: i32) -> Result<(), ApiError> {
+    use self::ssr::*;
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.as_ref().ok_or(ApiError::Unauthenticated)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    // Sessions only cached in memory have no row to delete yet, `track_session` logs them out
+    let revoked = sqlx::query_scalar::<_, String>(
+        r#"WITH revoked AS (
+            UPDATE user_session
+            SET revoked = TRUE
+            WHERE id = $1 AND user_id = $2 AND session_id <> $3 AND NOT revoked
+            RETURNING session_id
+        ), deleted AS (
+            DELETE FROM session
+            WHERE id IN (SELECT session_id FROM revoked)
+        )
+        SELECT session_id FROM revoked;"#,
+    )
+    .bind(id)
+    .bind(user.id)
+    .bind(auth.session.get_session_id().to_string())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    if revoked.is_none() {
+        return Err(ApiError::NotFound);
+    }
+    audit(
+        &mut *tx,
+        user.id,
+        AuditAction::RevokeSessions,
+        format!("user:{}", user.id),
+        None,
+        Some(json!({ "session": id })),
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    Ok(())
+}
+
+/// Logs out every session of the current user, including this one.
+#[server(LogoutEverywhere, prefix="/api", endpoint="user/sessions/revoke/all", input=PostUrl)]
+pub async fn logout_everywhere() -> Result<(), ApiError> {
+    use self::ssr::*;
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.as_ref().ok_or(ApiError::Unauthenticated)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    let count = remove_sessions(&mut *tx, user.id, None).await?;
+    audit(
+        &mut *tx,
+        user.id,
+        AuditAction::RevokeSessions,
+        format!("user:{}", user.id),
+        None,
+        Some(json!({ "count": count })),
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    auth.logout_user();
+    leptos_axum::redirect("/");
+    Ok(())
+}
+
+#[server(GetTokens, prefix="/api", endpoint="user/tokens", input=PostUrl)]
+pub async fn get_tokens() -> Result<Vec<ApiTokenInfo>, ApiError> {
+    use self::ssr::*;
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.as_ref().ok_or(ApiError::Unauthenticated)?;
+
+    sqlx::query_as::<_, ApiTokenInfo>(
+        r#"SELECT id, name, scopes, created_at, last_used_at
+        FROM api_token
+        WHERE user_id = $1
+        ORDER BY created_at DESC;"#,
+    )
+    .bind(user.id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+}
+
+/// Creates a personal access token limited to `scopes`, which the current user must have
+/// themselves. The plain token is only ever returned here, the database keeps its hash.
+#[server(CreateToken, prefix="/api", endpoint="user/tokens/create", input=PostUrl)]
+pub async fn create_token(name: String, scopes: Vec<Permissions>) -> Result<String, ApiError> {
+    use self::ssr::*;
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use rand::{Rng, thread_rng};
+    use std::collections::HashSet;
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.as_ref().ok_or(ApiError::Unauthenticated)?;
+
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 64 || scopes.is_empty() {
+        return Err(ApiError::InvalidInput);
+    }
+    if !scopes.iter().all(|s| user.has(s)) {
+        return Err(ApiError::Unauthorized);
+    }
+    let scopes = scopes
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let token = format!("lsl_{}", URL_SAFE_NO_PAD.encode(thread_rng().r#gen::<[u8; 32]>()));
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+    let id = sqlx::query_scalar::<_, i32>(
+        "INSERT INTO api_token (user_id, name, hash, scopes) VALUES ($1, $2, $3, $4) RETURNING id;",
+    )
+    .bind(user.id)
+    .bind(name)
+    .bind(hash_token(&token))
+    .bind(&scopes)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+    audit(
+        &mut *tx,
+        user.id,
+        AuditAction::CreateToken,
+        format!("user:{}", user.id),
+        None,
+        Some(json!({ "token": id, "name": name, "scopes": scopes })),
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+
+    Ok(token)
+}
+
+#[server(RevokeToken, prefix="/api", endpoint="user/tokens/revoke", input=PostUrl)]
+pub async fn revoke_token(id: i32) -> Result<(), ApiError> {
+    use self::ssr::*;
+
+    session_only()?;
+    let auth = auth()?;
+    let pool = pool()?;
+    let user = auth.current_user.as_ref().ok_or(ApiError::Unauthenticated)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    let name = sqlx::query_scalar::<_, String>("DELETE FROM api_token WHERE id = $1 AND user_id = $2 RETURNING name;")
+        .bind(id)
+        .bind(user.id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?
+        .ok_or(ApiError::NotFound)?;
+    audit(
+        &mut *tx,
+        user.id,
+        AuditAction::RevokeToken,
+        format!("user:{}", user.id),
+        Some(json!({ "token": id, "name": name })),
+        None,
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    Ok(())
 }
 
 #[server(Submit, prefix="/api", endpoint="runs/submit", input=PostUrl)]
@@ -369,7 +1406,7 @@ pub async fn submit(
     category: String,
     map: String,
     time: Decimal,
-    yt_id: String,
+    proof: String,
 ) -> Result<(), ApiError> {
     use self::ssr::*;
 
@@ -377,111 +1414,279 @@ pub async fn submit(
     let pool = pool()?;
 
     let u = auth.current_user.ok_or(ApiError::Unauthenticated)?;
-    if !u.has(&Permissions::Submit) {
+    let section_id = current_section(&pool, &layout, &category, &map).await?;
+    submit_run(&pool, &proof_validators()?, &u, section_id, time, &proof).await?;
+
+    leptos_axum::redirect(&format!("/leaderboard/map/{}", section_id));
+    Ok(())
+}
+
+#[server(GetQueue, prefix="/api", endpoint="runs/queue", input=PostUrl)]
+pub async fn get_queue(offset: i32) -> Result<Vec<Run>, ApiError> {
+    use self::ssr::*;
+
+    let auth = auth()?;
+    let pool = pool()?;
+
+    let u = auth.current_user.ok_or(ApiError::Unauthenticated)?;
+    if !u.has(&Permissions::Verify) {
         return Err(ApiError::Unauthorized);
     }
 
-    let section_id = sqlx::query_as::<_, SectionId>(
-        r#"SELECT id
-        FROM section
-        WHERE patch='2.13' AND layout=$1 AND category=$2 AND map=$3;"#,
+    sqlx::query_as::<_, Run>(
+        r#"SELECT r.id, r.created_at, r.section_id, s.patch, s.layout, s.category, s.map, r.user_id,
+            u."name", r.time, r.proof, r.yt_id, r.proof_host, r.verified, r.rejected, r.reason, r.is_pb, r.is_wr
+        FROM run r
+        INNER JOIN section s ON r.section_id = s.id
+        INNER JOIN "user" u ON r.user_id = u.id
+        WHERE NOT r.verified AND NOT r.rejected
+        ORDER BY r.created_at ASC
+        LIMIT 50 OFFSET $1;"#,
     )
-    .bind(&layout)
-    .bind(&category)
-    .bind(map)
-    .fetch_one(&pool)
+    .bind(offset)
+    .fetch_all(&pool)
     .await
-    .or(Err(ApiError::InvalidSection))?;
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))
+}
 
-    let r = reqwest::get(format!(
-        "https://www.googleapis.com/youtube/v3/videos?key={}&part=id&id={yt_id}",
-        env!("YT_KEY")
-    ))
-    .await
-    .map_err(|_| ApiError::ServerError("YT api request failed".into()))?;
+#[server(Review, prefix="/api", endpoint="runs/review", input=PostUrl)]
+pub async fn review(id: i32, approve: bool, reason: Option<String>) -> Result<(), ApiError> {
+    use self::ssr::*;
 
-    let v = r
-        .json::<YtJson>()
-        .await
-        .map_err(|_| ApiError::ServerError("Failed to parse yt api response".into()))?;
+    let auth = auth()?;
+    let pool = pool()?;
 
-    if v.page_info.total_results == 0 {
-        Err(ApiError::InvalidYtId)
-    } else {
-        let proof = format!("https://youtube.com/watch?v={yt_id}");
-        let _ = sqlx::query(
-            r#"INSERT INTO run (section_id, user_id, time, proof, yt_id, verified)
-                                    VALUES ($1, $2, $3, $4, $5, $6);"#,
-        )
-        .bind(section_id.id)
+    let u = auth.current_user.ok_or(ApiError::Unauthenticated)?;
+    if !u.has(&Permissions::Verify) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let reason = reason.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+    if reason.as_ref().is_some_and(|r| r.len() > 512) || (!approve && reason.is_none()) {
+        return Err(ApiError::InvalidInput);
+    }
+
+    let query = match approve {
+        true => {
+            r#"UPDATE run
+            SET verified = TRUE, reason = $2, reviewed_by = $3, reviewed_at = now()
+            WHERE id = $1 AND NOT verified AND NOT rejected
+            RETURNING section_id;"#
+        }
+        false => {
+            r#"UPDATE run
+            SET rejected = TRUE, reason = $2, reviewed_by = $3, reviewed_at = now()
+            WHERE id = $1 AND NOT verified AND NOT rejected
+            RETURNING section_id;"#
+        }
+    };
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    let section_id = sqlx::query_scalar::<_, i32>(query)
+        .bind(id)
+        .bind(&reason)
         .bind(u.id)
-        .bind(time)
-        .bind(proof)
-        .bind(yt_id)
-        .bind(u.has(&Permissions::Trusted))
-        .execute(&pool)
+        .fetch_optional(&mut *tx)
         .await
-        .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
-
-        leptos_axum::redirect(&format!("/leaderboard/map/{}", section_id.id));
-        Ok(())
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?
+        .ok_or(ApiError::NotFound)?;
+    // Rejected runs no longer count, so the pbs and rankings of the section change.
+    if !approve {
+        recalculate_section(&mut tx, section_id).await?;
     }
+    let (action, after) = match approve {
+        true => (AuditAction::Approve, json!({ "verified": true, "reason": reason })),
+        false => (AuditAction::Reject, json!({ "rejected": true, "reason": reason })),
+    };
+    audit(
+        &mut *tx,
+        u.id,
+        action,
+        format!("run:{id}"),
+        Some(json!({ "verified": false, "rejected": false })),
+        Some(after),
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))
 }
 
-#[server(Verify, prefix="/api", endpoint="runs/verify", input=PostUrl)]
-pub async fn verify(id: i32) -> Result<(), ApiError> {
+#[server(Delete, prefix="/api", endpoint="runs/delete", input=PostUrl)]
+pub async fn delete(id: i32, redirect: Option<String>) -> Result<(), ApiError> {
     use self::ssr::*;
 
     let auth = auth()?;
     let pool = pool()?;
 
     let u = auth.current_user.ok_or(ApiError::Unauthenticated)?;
-    if !u.has(&Permissions::Verify) {
+    if !u.has(&Permissions::Delete) {
         return Err(ApiError::Unauthorized);
     }
 
-    sqlx::query(
-        r#"UPDATE run
-        SET verified = TRUE
-        WHERE id = $1;"#,
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    let (section_id, before) = sqlx::query_as::<_, (i32, Value)>(
+        r#"DELETE FROM run
+        WHERE id = $1 AND user_id = $2 AND section_id IN (
+            SELECT s.id
+            FROM section s
+            INNER JOIN patch p ON s.patch = p.name
+            WHERE p.current)
+        RETURNING section_id, jsonb_build_object('section_id', section_id, 'time', time, 'proof', proof,
+            'verified', verified, 'is_pb', is_pb, 'is_wr', is_wr);"#,
     )
     .bind(id)
-    .execute(&pool)
+    .bind(u.id)
+    .fetch_optional(&mut *tx)
     .await
-    .or(Err(ApiError::NotFound))?;
+    .map_err(|_| ApiError::ServerError("Database delete failed".into()))?
+    .ok_or(ApiError::NotFound)?;
+    recalculate_section(&mut tx, section_id).await?;
+    audit(
+        &mut *tx,
+        u.id,
+        AuditAction::Delete,
+        format!("run:{id}"),
+        Some(before),
+        None,
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+
+    if let Some(re) = redirect.and_then(|red| HeaderValue::from_str(&format!("/{red}")).ok()) {
+        leptos_axum::redirect(re.to_str().unwrap_or("/"));
+    }
     Ok(())
 }
 
-#[server(Delete, prefix="/api", endpoint="runs/delete", input=PostUrl)]
-pub async fn delete(id: i32, redirect: Option<String>) -> Result<(), ApiError> {
+#[server(EditRun, prefix="/api", endpoint="runs/edit", input=PostUrl)]
+pub async fn edit_run(id: i32, time: Decimal, proof: String, redirect: Option<String>) -> Result<(), ApiError> {
     use self::ssr::*;
 
     let auth = auth()?;
     let pool = pool()?;
 
     let u = auth.current_user.ok_or(ApiError::Unauthenticated)?;
-    if !u.has(&Permissions::Delete) {
+    let moderator = u.has(&Permissions::ManageRuns);
+    if !moderator && !u.has(&Permissions::Submit) {
         return Err(ApiError::Unauthorized);
     }
+    if time <= Decimal::ZERO {
+        return Err(ApiError::InvalidInput);
+    }
 
-    let num = sqlx::query(
-        r#"DELETE FROM run
-        WHERE id = $1 AND user_id = $2 AND section_id >= 1093;"#,
+    // The host can take a while to answer, so the proof is checked before the run is locked.
+    // Unchanged proofs aren't validated again, moderators may fix runs with proofs that went private.
+    let (owner, checked_proof) = sqlx::query_as::<_, (i64, String)>("SELECT user_id, proof FROM run WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?
+        .ok_or(ApiError::NotFound)?;
+    if !moderator && owner != u.id {
+        return Err(ApiError::NotFound);
+    }
+    let validated = match proof.trim() == checked_proof {
+        true => None,
+        false => Some(proof_validators()?.validate(&proof).await?),
+    };
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    let (user_id, section_id, old_time, old_proof, old_yt_id, old_host, verified, rejected, in_grace) =
+        sqlx::query_as::<_, (i64, i32, Decimal, String, Option<String>, ProofHost, bool, bool, bool)>(
+            r#"SELECT user_id, section_id, time, proof, yt_id, proof_host, verified, rejected,
+                created_at > now() - make_interval(mins => $2)
+            FROM run
+            WHERE id = $1
+            FOR UPDATE;"#,
+        )
+        .bind(id)
+        .bind(EDIT_GRACE_MINUTES)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?
+        .ok_or(ApiError::NotFound)?;
+    if !moderator {
+        if user_id != u.id || rejected {
+            return Err(ApiError::NotFound);
+        }
+        if !in_grace {
+            return Err(ApiError::EditWindowExpired);
+        }
+    }
+
+    let (url, yt_id, host) = match validated {
+        _ if proof.trim() == old_proof => (old_proof.clone(), old_yt_id.clone(), old_host),
+        Some(proof) => (proof.url, Some(proof.id), proof.host),
+        None => {
+            return Err(ApiError::ClientError(
+                "The run changed while it was edited, try again".into(),
+            ));
+        }
+    };
+    // Owners have to be verified again, unless they are trusted.
+    let verified = match moderator {
+        true => verified,
+        false => u.has(&Permissions::Trusted),
+    };
+
+    sqlx::query(
+        r#"INSERT INTO run_history (run_id, section_id, edited_by, time, proof, yt_id, proof_host)
+        VALUES ($1, $2, $3, $4, $5, $6, $7);"#,
     )
     .bind(id)
+    .bind(section_id)
     .bind(u.id)
-    .execute(&pool)
-    .await;
-    if num.is_ok_and(|r| r.rows_affected() != 0) {
-        if let Some(red) = redirect {
-            if let Some(re) = HeaderValue::from_str(&format!("/{}", red)).ok() {
-                leptos_axum::redirect(re.to_str().unwrap_or("/"));
-            };
-        }
-        Ok(())
-    } else {
-        Err(ApiError::NotFound)
+    .bind(old_time)
+    .bind(&old_proof)
+    .bind(&old_yt_id)
+    .bind(old_host)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
+    sqlx::query(
+        r#"UPDATE run
+        SET time = $2, proof = $3, yt_id = $4, proof_host = $5, verified = $6
+        WHERE id = $1 AND section_id = $7;"#,
+    )
+    .bind(id)
+    .bind(time)
+    .bind(&url)
+    .bind(&yt_id)
+    .bind(host)
+    .bind(verified)
+    .bind(section_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    recalculate_section(&mut tx, section_id).await?;
+    audit(
+        &mut *tx,
+        u.id,
+        AuditAction::Edit,
+        format!("run:{id}"),
+        Some(json!({ "time": old_time, "proof": old_proof })),
+        Some(json!({ "time": time, "proof": url, "verified": verified })),
+    )
+    .await?;
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+
+    if let Some(re) = redirect.and_then(|red| HeaderValue::from_str(&format!("/{red}")).ok()) {
+        leptos_axum::redirect(re.to_str().unwrap_or("/"));
     }
+    Ok(())
 }
 
 #[server(DiscordList, prefix="/api", endpoint="user/discord/list", input=PostUrl)]
@@ -507,23 +1712,28 @@ pub async fn discord_list() -> Result<Vec<Discord>, ApiError> {
 #[server(DiscordAdd, prefix="/api", endpoint="user/discord/add", input=PostUrl)]
 pub async fn discord_add() -> Result<(), ApiError> {
     use self::ssr::*;
-    use oauth2::{CsrfToken, Scope};
 
+    session_only()?;
     let auth = auth()?;
     if auth.current_user.is_none() {
         return Err(ApiError::Unauthenticated);
     }
-    let oauth = oauth()?;
+    discord_authorize(&auth)
+}
 
-    let (auth_url, csrf_token) = oauth
-        .authorize_url(CsrfToken::new_random)
-        .add_scope(Scope::new("identify".into()))
-        .add_scope(Scope::new("role_connections.write".into()))
-        .url();
+/// Logs in through Discord instead of a password. The callback signs in the user the
+/// Discord account is linked to, or registers a new user named after it.
+#[server(DiscordLogin, prefix="/api", endpoint="user/discord/login", input=PostUrl)]
+pub async fn discord_login() -> Result<(), ApiError> {
+    use self::ssr::*;
 
-    auth.session.set("csrf", csrf_token);
-    leptos_axum::redirect(auth_url.as_ref());
-    Ok(())
+    session_only()?;
+    let auth = auth()?;
+    if auth.current_user.is_some() {
+        leptos_axum::redirect("/user/@me/dashboard");
+        return Ok(());
+    }
+    discord_authorize(&auth)
 }
 
 #[server(DiscordAuth, prefix="/api", endpoint="user/discord/auth", input=GetUrl)]
@@ -533,8 +1743,8 @@ pub async fn discord_auth(code: String, state: String) -> Result<(), ApiError> {
 
     leptos_axum::redirect("/user/@me/dashboard");
 
+    session_only()?;
     let auth = auth()?;
-    let user = auth.current_user.ok_or(ApiError::Unauthenticated)?;
     let oauth = oauth()?;
     let csrf = auth
         .session
@@ -566,21 +1776,44 @@ pub async fn discord_auth(code: String, state: String) -> Result<(), ApiError> {
 
     let pool = pool()?;
 
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    // Without a session this is a Discord login, find or create the linked user
+    let (user_id, login) = match auth.current_user.as_ref() {
+        Some(user) => (user.id, false),
+        None => match discord_user(&mut *tx, &snowflake).await? {
+            Some(id) => (id, true),
+            None => (register_discord_user(&mut tx, &name).await?, true),
+        },
+    };
+
     let discord = sqlx::query_as::<_, Discord>(
         r#"SELECT name, snowflake
         FROM discord
         WHERE user_id = $1
         LIMIT 5;"#,
     )
-    .bind(user.id)
-    .fetch_all(&pool)
+    .bind(user_id)
+    .fetch_all(&mut *tx)
     .await
     .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+    let linked = discord.iter().find(|d| d.snowflake == snowflake);
 
-    if discord.len() >= 5 {
+    if linked.is_none() && discord.len() >= 5 {
         return Err(ApiError::AlreadyExists)?;
     }
-    if discord.iter().any(|d| d.snowflake == snowflake) {
+    audit(
+        &mut *tx,
+        user_id,
+        AuditAction::DiscordLink,
+        format!("discord:{snowflake}"),
+        linked.map(|d| json!({ "name": d.name })),
+        Some(json!({ "name": name })),
+    )
+    .await?;
+    if linked.is_some() {
         sqlx::query(
             r#"UPDATE discord
             SET name = $1, access = $2, refresh = $3, expires_at = $4
@@ -590,9 +1823,9 @@ pub async fn discord_auth(code: String, state: String) -> Result<(), ApiError> {
         .bind(token.access_token().secret())
         .bind(token.refresh_token().unwrap().secret())
         .bind(Local::now() + TimeDelta::seconds(token.expires_in().unwrap().as_secs() as i64))
-        .bind(user.id)
+        .bind(user_id)
         .bind(snowflake)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
     } else {
@@ -600,16 +1833,24 @@ pub async fn discord_auth(code: String, state: String) -> Result<(), ApiError> {
             r#"INSERT INTO discord (user_id, name, snowflake, access, refresh, expires_at)
             VALUES ($1, $2, $3, $4, $5, $6);"#,
         )
-        .bind(user.id)
+        .bind(user_id)
         .bind(name)
         .bind(snowflake)
         .bind(token.access_token().secret())
         .bind(token.refresh_token().unwrap().secret())
         .bind(Local::now() + TimeDelta::seconds(token.expires_in().unwrap().as_secs() as i64))
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|_| ApiError::ServerError("Database insert failed".into()))?;
     }
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+
+    if login {
+        start_session(&auth, &pool, user_id, true).await?;
+        leptos_axum::redirect("/");
+    }
     Ok(())
 }
 
@@ -617,18 +1858,38 @@ pub async fn discord_auth(code: String, state: String) -> Result<(), ApiError> {
 pub async fn discord_delete(snowflake: String) -> Result<(), ApiError> {
     use self::ssr::*;
 
+    session_only()?;
     let auth = auth()?;
     let user = auth.current_user.ok_or(ApiError::Unauthenticated)?;
     let pool = pool()?;
 
-    let _ = sqlx::query(
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    let name = sqlx::query_scalar::<_, String>(
         r#"DELETE FROM discord
-        WHERE user_id = $1 AND snowflake = $2;"#,
+        WHERE user_id = $1 AND snowflake = $2
+        RETURNING name;"#,
     )
     .bind(user.id)
-    .bind(snowflake)
-    .execute(&pool)
+    .bind(&snowflake)
+    .fetch_optional(&mut *tx)
     .await
     .map_err(|_| ApiError::ServerError("Database delete failed".to_string()))?;
+    if let Some(name) = name {
+        audit(
+            &mut *tx,
+            user.id,
+            AuditAction::DiscordUnlink,
+            format!("discord:{snowflake}"),
+            Some(json!({ "name": name })),
+            None,
+        )
+        .await?;
+    }
+    tx.commit()
+        .await
+        .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
     Ok(())
 }
@@ -0,0 +1,504 @@
+//! Discord slash commands, received as HTTP interactions at `/api/discord/interactions`.
+//! Discord signs every request with the key of the application, requests that don't verify
+//! against `DISCORD_PUBLIC_KEY` are refused. Callers are identified by the Discord accounts
+//! users linked on their dashboard, so only linked accounts can look up their own runs and
+//! submit. The commands are registered with Discord when the site starts.
+
+use std::{env, sync::Arc};
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Local;
+use ring::signature::{ED25519, UnparsedPublicKey};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use types::{api::*, internal::ssr::*};
+
+use crate::{
+    api::ssr::{RUNS, push_run_filters, user_rankings},
+    auth::ssr::{current_patch, current_section, discord_user, submit_run},
+    proof::ProofValidators,
+};
+
+const DISCORD_API: &str = "https://discord.com/api/v10";
+
+// Interaction and response types
+const PING: u8 = 1;
+const APPLICATION_COMMAND: u8 = 2;
+const AUTOCOMPLETE: u8 = 4;
+const PONG: u8 = 1;
+const MESSAGE: u8 = 4;
+const DEFERRED_MESSAGE: u8 = 5;
+const AUTOCOMPLETE_RESULT: u8 = 8;
+/// Message flag of replies only the caller sees.
+const EPHEMERAL: u32 = 1 << 6;
+/// Seconds a signature timestamp may be off from now, older requests could be replays.
+const MAX_SKEW: i64 = 5 * 60;
+
+/// Keys and client for the interactions endpoint, only served when `DISCORD_PUBLIC_KEY`
+/// is set.
+#[derive(Clone, Debug)]
+pub struct Interactions {
+    key: Arc<UnparsedPublicKey<Vec<u8>>>,
+    application_id: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct Interaction {
+    #[serde(rename = "type")]
+    kind: u8,
+    token: String,
+    data: Option<CommandData>,
+    /// The caller in servers
+    member: Option<Member>,
+    /// The caller in DMs
+    user: Option<DiscordUser>,
+}
+
+#[derive(Deserialize)]
+struct Member {
+    user: DiscordUser,
+}
+
+#[derive(Deserialize)]
+struct DiscordUser {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct CommandData {
+    name: String,
+    #[serde(default)]
+    options: Vec<CommandOption>,
+}
+
+#[derive(Deserialize)]
+struct CommandOption {
+    name: String,
+    value: Value,
+    /// Set on the option being typed in autocomplete requests.
+    #[serde(default)]
+    focused: bool,
+}
+
+impl CommandData {
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|o| o.name == name)
+            .and_then(|o| o.value.as_str())
+    }
+}
+
+/// An embed for the channel, or an error only the caller sees.
+type Reply = Result<Value, String>;
+
+impl Interactions {
+    pub fn from_env() -> Option<Self> {
+        let key = env::var("DISCORD_PUBLIC_KEY").ok().filter(|k| !k.trim().is_empty())?;
+        let key = hex::decode(key.trim()).expect("DISCORD_PUBLIC_KEY isn't hex!");
+        Some(Self {
+            key: Arc::new(UnparsedPublicKey::new(&ED25519, key)),
+            application_id: env::var("DISCORD_ID").expect("Missing DISCORD_ID!"),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Whether Discord signed the timestamp and body of the request, and did so recently.
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> bool {
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+        let (Some(signature), Some(timestamp)) = (header("x-signature-ed25519"), header("x-signature-timestamp"))
+        else {
+            return false;
+        };
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        let Ok(sent) = timestamp.parse::<i64>() else {
+            return false;
+        };
+        if (Local::now().timestamp() - sent).abs() > MAX_SKEW {
+            return false;
+        }
+        let message = [timestamp.as_bytes(), body].concat();
+        self.key.verify(&message, &signature).is_ok()
+    }
+
+    /// Replaces the commands of the application with the ones served here. Authenticates
+    /// with the client credentials of the application, which needs no bot.
+    pub async fn register(&self) -> Result<(), reqwest::Error> {
+        #[derive(Deserialize)]
+        struct Token {
+            access_token: String,
+        }
+
+        let secret = env::var("DISCORD_SECRET").ok();
+        let token = self
+            .client
+            .post(format!("{DISCORD_API}/oauth2/token"))
+            .basic_auth(&self.application_id, secret)
+            .form(&[
+                ("grant_type", "client_credentials"),
+                ("scope", "applications.commands.update"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Token>()
+            .await?;
+        self.client
+            .put(format!("{DISCORD_API}/applications/{}/commands", self.application_id))
+            .bearer_auth(token.access_token)
+            .json(&commands())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Definitions of the commands, see
+/// <https://discord.com/developers/docs/interactions/application-commands>.
+fn commands() -> Value {
+    json!([
+        {
+            "name": "pb",
+            "description": "Your personal bests on a map in the current patch",
+            "options": [text("map", "Map of the runs", true)],
+        },
+        {
+            "name": "wr",
+            "description": "World records on a map in the current patch",
+            "options": [text("map", "Map of the runs", true)],
+        },
+        {
+            "name": "rank",
+            "description": "Ranks and titles in the current patch",
+            "options": [{ "type": 6, "name": "user", "description": "Whose ranks, yours by default" }],
+        },
+        {
+            "name": "submit",
+            "description": "Submit a run to the current patch",
+            "options": [
+                text("layout", "Layout of the run", true),
+                text("category", "Category of the run", true),
+                text("map", "Map of the run", true),
+                text("time", "Time in seconds, like 75.12", false),
+                text("video", "Link to the video of the run", false),
+            ],
+        },
+    ])
+}
+
+/// A required text option, `autocomplete` ones are completed from the sections.
+fn text(name: &str, description: &str, autocomplete: bool) -> Value {
+    json!({
+        "type": 3,
+        "name": name,
+        "description": description,
+        "required": true,
+        "autocomplete": autocomplete,
+    })
+}
+
+/// Handles an interaction, after checking that Discord sent it.
+pub async fn interactions(
+    State(pool): State<PgPool>,
+    State(validators): State<ProofValidators>,
+    State(interactions): State<Option<Interactions>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(interactions) = interactions else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !interactions.verify(&headers, &body) {
+        return (StatusCode::UNAUTHORIZED, "Invalid request signature").into_response();
+    }
+    let Ok(interaction) = serde_json::from_slice::<Interaction>(&body) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let caller = interaction
+        .member
+        .map(|m| m.user)
+        .or(interaction.user)
+        .map(|u| u.id)
+        .unwrap_or_default();
+    if interaction.kind == PING {
+        return Json(json!({ "type": PONG })).into_response();
+    }
+    let Some(data) = interaction.data else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if interaction.kind == AUTOCOMPLETE {
+        let choices = autocomplete(&pool, &data).await;
+        return Json(json!({ "type": AUTOCOMPLETE_RESULT, "data": { "choices": choices } })).into_response();
+    }
+    if interaction.kind != APPLICATION_COMMAND {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let reply = match data.name.as_str() {
+        "pb" => pb(&pool, &caller, &data).await,
+        "wr" => wr(&pool, &data).await,
+        "rank" => rank(&pool, data.option("user").unwrap_or(&caller)).await,
+        "submit" => {
+            // Checking the proof can take longer than Discord waits for the response,
+            // the result replaces the "thinking" message once it is done
+            tokio::spawn(async move {
+                let content = match submit(&pool, &validators, &caller, &data).await {
+                    Ok(content) | Err(content) => content,
+                };
+                let res = interactions
+                    .client
+                    .patch(format!(
+                        "{DISCORD_API}/webhooks/{}/{}/messages/@original",
+                        interactions.application_id, interaction.token
+                    ))
+                    .json(&json!({ "content": content }))
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status());
+                if let Err(e) = res {
+                    log::error!("Failed to answer /submit: {e}");
+                }
+            });
+            return Json(json!({ "type": DEFERRED_MESSAGE, "data": { "flags": EPHEMERAL } })).into_response();
+        }
+        _ => Err("Unknown command".into()),
+    };
+    Json(match reply {
+        Ok(embed) => json!({ "type": MESSAGE, "data": { "embeds": [embed] } }),
+        Err(content) => json!({ "type": MESSAGE, "data": { "content": content, "flags": EPHEMERAL } }),
+    })
+    .into_response()
+}
+
+/// The site account a Discord account acts as, the same one it signs in to on the site.
+async fn linked_user(pool: &PgPool, snowflake: &str) -> Result<i64, String> {
+    discord_user(pool, snowflake)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| {
+            format!("<@{snowflake}> hasn't linked a Lucio Surf League account, link one under Discord on the dashboard")
+        })
+}
+
+/// Personal bests or world records, by the conditions after `RUNS`, on the map of the
+/// command in the current patch.
+async fn runs(pool: &PgPool, user: Option<i64>, condition: &str, data: &CommandData) -> Result<Vec<Run>, String> {
+    let patch = current_patch(pool).await.map_err(|e| e.to_string())?;
+    let filter = RunFilters {
+        user,
+        patch: Some(patch),
+        map: data.option("map").map(String::from),
+        ..Default::default()
+    };
+    let mut query = QueryBuilder::<Postgres>::new(RUNS);
+    push_run_filters(&mut query, &filter);
+    query
+        .push(condition)
+        .push(" ORDER BY layout, category;")
+        .build_query_as::<Run>()
+        .fetch_all(pool)
+        .await
+        .map_err(|_| "Database lookup failed".into())
+}
+
+async fn pb(pool: &PgPool, caller: &str, data: &CommandData) -> Reply {
+    let user = linked_user(pool, caller).await?;
+    let runs = runs(pool, Some(user), " AND is_pb AND NOT rejected", data).await?;
+    let Some(first) = runs.first() else {
+        return Err(format!(
+            "No personal bests on {} yet",
+            data.option("map").unwrap_or_default()
+        ));
+    };
+    let lines: Vec<String> = runs
+        .iter()
+        .map(|r| {
+            let combo = combo(&r.layout, &r.category);
+            format!(
+                "{combo}: *{}* [proof]({}) <t:{}:R>",
+                r.time,
+                r.proof,
+                r.created_at.timestamp()
+            )
+        })
+        .collect();
+    Ok(json!({
+        "color": 16764928,
+        "title": format!("Personal bests of {} on {}", first.username, first.map),
+        "description": lines.join("\n"),
+        "footer": { "text": format!("Patch {}", first.patch) },
+    }))
+}
+
+async fn wr(pool: &PgPool, data: &CommandData) -> Reply {
+    let runs = runs(pool, None, " AND is_wr AND NOT rejected", data).await?;
+    let Some(first) = runs.first() else {
+        return Err(format!("No records on {} yet", data.option("map").unwrap_or_default()));
+    };
+    let lines: Vec<String> = runs
+        .iter()
+        .map(|r| {
+            let combo = combo(&r.layout, &r.category);
+            let date = r.created_at.timestamp();
+            format!(
+                "{combo}: *{}* by {} [proof]({}) <t:{date}:R>",
+                r.time, r.username, r.proof
+            )
+        })
+        .collect();
+    Ok(json!({
+        "color": 7798548,
+        "title": format!("World records on {}", first.map),
+        "description": lines.join("\n"),
+        "footer": { "text": format!("Patch {}", first.patch) },
+    }))
+}
+
+async fn rank(pool: &PgPool, snowflake: &str) -> Reply {
+    let user = linked_user(pool, snowflake).await?;
+    let patch = current_patch(pool).await.map_err(|e| e.to_string())?;
+    let mut rankings: Vec<Ranking> = user_rankings(pool, user)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|r| r.patch == patch)
+        .collect();
+    // Combined first, like the profile
+    rankings.sort_by(|a, b| (&a.layout, &a.category).cmp(&(&b.layout, &b.category)));
+    let Some(first) = rankings.first() else {
+        return Err(format!("<@{snowflake}> has no ranks in patch {patch} yet"));
+    };
+    let lines: Vec<String> = rankings
+        .iter()
+        .map(|r| {
+            let combo = match (&r.layout, &r.category) {
+                (Some(l), Some(c)) => combo(l, c),
+                _ => "Combined".into(),
+            };
+            format!("{combo}: **#{}** {} *({:.0} rating)*", r.rank, r.title, r.rating)
+        })
+        .collect();
+    Ok(json!({
+        "color": 1342207,
+        "title": format!("Ranks of {}", first.username),
+        "description": lines.join("\n"),
+        "footer": { "text": format!("Patch {patch}") },
+    }))
+}
+
+/// Submits the run of the command, the outcome is the reply either way.
+async fn submit(
+    pool: &PgPool,
+    validators: &ProofValidators,
+    caller: &str,
+    data: &CommandData,
+) -> Result<String, String> {
+    let user_id = linked_user(pool, caller).await?;
+    let user = User::get(user_id, pool).await.ok_or("Account not found")?;
+    let option = |name| data.option(name).unwrap_or_default();
+    let time = option("time")
+        .trim()
+        .parse::<Decimal>()
+        .ok()
+        .filter(|t| *t > Decimal::ZERO)
+        .ok_or("Time has to be a number of seconds, like 75.12")?;
+    let section_id = current_section(pool, option("layout"), option("category"), option("map"))
+        .await
+        .map_err(|e| e.to_string())?;
+    let id = submit_run(pool, validators, &user, section_id, time, option("video"))
+        .await
+        .map_err(|e| match e {
+            ApiError::Unauthorized => "Your account isn't allowed to submit runs".into(),
+            e => e.to_string(),
+        })?;
+    Ok(format!(
+        "Submitted run {id}: *{time}* on {} ({}){}",
+        option("map"),
+        combo(option("layout"), option("category")),
+        if user.has(&Permissions::Trusted) {
+            ""
+        } else {
+            ", it shows up once it is verified"
+        }
+    ))
+}
+
+/// Values of the current patch starting like the option being typed.
+async fn autocomplete(pool: &PgPool, data: &CommandData) -> Vec<Value> {
+    let Some(focused) = data.options.iter().find(|o| o.focused) else {
+        return vec![];
+    };
+    let column = match focused.name.as_str() {
+        "layout" => "layout",
+        "category" => "category",
+        _ => "map",
+    };
+    let typed = focused.value.as_str().unwrap_or_default();
+    let values = QueryBuilder::<Postgres>::new("SELECT DISTINCT s.")
+        .push(column)
+        .push(" FROM section s INNER JOIN patch p ON s.patch = p.name WHERE p.current AND s.")
+        .push(column)
+        .push(" ILIKE ")
+        .push_bind(format!("{}%", typed.replace('%', "\\%").replace('_', "\\_")))
+        .push(" ORDER BY 1 LIMIT 25;")
+        .build_query_scalar::<String>()
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default();
+    values.into_iter().map(|v| json!({ "name": v, "value": v })).collect()
+}
+
+fn combo(layout: &str, category: &str) -> String {
+    format!("Layout {layout} - {category}")
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{Ed25519KeyPair, KeyPair},
+    };
+
+    use super::*;
+
+    #[test]
+    fn verifies_signatures() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let interactions = Interactions {
+            key: Arc::new(UnparsedPublicKey::new(&ED25519, pair.public_key().as_ref().to_vec())),
+            application_id: "1".into(),
+            client: reqwest::Client::new(),
+        };
+        let body = br#"{"type":1}"#;
+        let headers = |timestamp: &str, signed: &[u8]| {
+            let mut headers = HeaderMap::new();
+            let signature = pair.sign(&[timestamp.as_bytes(), signed].concat());
+            headers.insert("x-signature-ed25519", hex::encode(signature).parse().unwrap());
+            headers.insert("x-signature-timestamp", timestamp.parse().unwrap());
+            headers
+        };
+
+        let now = Local::now().timestamp();
+        assert!(interactions.verify(&headers(&now.to_string(), body), body));
+        assert!(!interactions.verify(&headers(&now.to_string(), br#"{"type":2}"#), body));
+        assert!(!interactions.verify(&HeaderMap::new(), body));
+        // Replayed or from the future
+        assert!(interactions.verify(&headers(&(now - 60).to_string(), body), body));
+        assert!(!interactions.verify(&headers(&(now - MAX_SKEW - 60).to_string(), body), body));
+        assert!(!interactions.verify(&headers(&(now + MAX_SKEW + 60).to_string(), body), body));
+        assert!(!interactions.verify(&headers("yesterday", body), body));
+    }
+}
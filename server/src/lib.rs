@@ -1,2 +1,15 @@
+pub mod admin;
 pub mod api;
 pub mod auth;
+#[cfg(feature = "ssr")]
+pub mod interactions;
+#[cfg(feature = "ssr")]
+pub mod live;
+#[cfg(feature = "ssr")]
+pub mod pagination;
+#[cfg(feature = "ssr")]
+pub mod proof;
+#[cfg(feature = "ssr")]
+pub mod ranking;
+#[cfg(feature = "ssr")]
+pub mod rest;
@@ -0,0 +1,121 @@
+//! Live updates for open pages. The `submit`, `run_change` and `activity` NOTIFY channels
+//! are listened to once per site server and each notification is sent to every browser
+//! subscribed to `/api/live` as a server-sent `LiveEvent`.
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{Stream, stream};
+use sqlx::{
+    PgPool, Postgres, QueryBuilder,
+    postgres::{PgListener, PgNotification},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use types::api::{Activity, LiveEvent, Run, Section};
+
+use crate::api::ssr::{ACTIVITY, RUNS};
+
+/// Events of the last notifications, kept for subscribers that fall behind.
+const BACKLOG: usize = 64;
+
+/// Sends the events from `listen` to the open event streams.
+#[derive(Clone, Debug)]
+pub struct Live(broadcast::Sender<LiveEvent>);
+
+/// Starts listening to the NOTIFY channels in the background, reconnecting when the
+/// connection is lost.
+pub fn listen(pool: PgPool) -> Live {
+    let (sender, _) = broadcast::channel(BACKLOG);
+    let live = Live(sender.clone());
+    tokio::spawn(async move {
+        loop {
+            let mut listener = match PgListener::connect_with(&pool).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::error!("Failed to connect live listener: {e}");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            if let Err(e) = listener.listen_all(["submit", "run_change", "activity"]).await {
+                log::error!("Failed to listen for live updates: {e}");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+            loop {
+                match listener.recv().await {
+                    Ok(notification) => {
+                        // Nobody to tell when no page is open
+                        if sender.receiver_count() == 0 {
+                            continue;
+                        }
+                        match event(&pool, &notification).await {
+                            Ok(Some(event)) => {
+                                let _ = sender.send(event);
+                            }
+                            Ok(None) => {}
+                            Err(e) => log::error!("Failed to look up live update: {e}"),
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Lost live listener: {e}");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    live
+}
+
+/// The row a notification is about, `None` if it is gone or rejected already.
+async fn event(pool: &PgPool, notification: &PgNotification) -> Result<Option<LiveEvent>, sqlx::Error> {
+    let Ok(id) = notification.payload().parse::<i32>() else {
+        return Ok(None);
+    };
+    Ok(match notification.channel() {
+        "submit" => QueryBuilder::<Postgres>::new(RUNS)
+            .push(" AND NOT rejected AND run.id = ")
+            .push_bind(id)
+            .build_query_as::<Run>()
+            .fetch_optional(pool)
+            .await?
+            .map(LiveEvent::Submit),
+        "run_change" => {
+            sqlx::query_as::<_, Section>("SELECT id, patch, layout, category, map, code FROM section WHERE id = $1;")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?
+                .map(LiveEvent::Change)
+        }
+        "activity" => QueryBuilder::<Postgres>::new(ACTIVITY)
+            .push(" AND a.id = ")
+            .push_bind(id)
+            .build_query_as::<Activity>()
+            .fetch_optional(pool)
+            .await?
+            .map(LiveEvent::Activity),
+        _ => None,
+    })
+}
+
+/// Event stream of every `LiveEvent` from the time of the request on, as JSON.
+pub async fn events(State(live): State<Live>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = stream::unfold(live.0.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data = serde_json::to_string(&event).unwrap_or_default();
+                    return Some((Ok(Event::default().data(data)), receiver));
+                }
+                // Pages refetch on the next event, missing some in between is fine
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
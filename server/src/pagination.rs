@@ -0,0 +1,160 @@
+//! Keyset pagination shared by the server functions and the REST API. Lists are sorted by
+//! a few columns ending in a unique one, and a page starts right after the sort key of the
+//! last item of the page before it, so pages stay stable while new items are added.
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use sqlx::{Postgres, QueryBuilder};
+use types::api::{ApiError, Page};
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+
+/// The sort key of the item a page starts after, or ends before when `backward`. Cursors are
+/// base64 JSON so clients can't rely on their contents.
+fn encode_cursor(backward: bool, key: Vec<String>) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&(backward, key)).unwrap_or_default())
+}
+
+fn decode_cursor(cursor: &str) -> Result<(bool, Vec<String>), ApiError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|cursor| serde_json::from_slice(&cursor).ok())
+        .ok_or(ApiError::InvalidInput)
+}
+
+/// One page of a list sorted by `columns`, which pair an SQL expression with its type.
+pub struct Keyset {
+    columns: &'static [(&'static str, &'static str)],
+    ascending: bool,
+    backward: bool,
+    after: Option<Vec<String>>,
+    limit: i64,
+}
+
+impl Keyset {
+    /// Reads the page requested with `cursor` and `limit`, `None` being the first page and
+    /// `DEFAULT_LIMIT` items.
+    pub fn new(
+        columns: &'static [(&'static str, &'static str)],
+        ascending: bool,
+        cursor: Option<&str>,
+        limit: Option<i64>,
+    ) -> Result<Self, ApiError> {
+        let (backward, after) = match cursor {
+            Some(cursor) => {
+                let (backward, key) = decode_cursor(cursor)?;
+                if key.len() != columns.len() {
+                    return Err(ApiError::InvalidInput);
+                }
+                (backward, Some(key))
+            }
+            None => (false, None),
+        };
+        Ok(Self {
+            columns,
+            ascending,
+            backward,
+            after,
+            limit: limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        })
+    }
+
+    /// Finishes a query that ends in its conditions with the position of the page, its
+    /// order and limit. One item over the limit is fetched to tell if there are more.
+    pub fn push(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        let expressions = self.columns.iter().map(|(e, _)| *e).collect::<Vec<_>>().join(", ");
+        // Paging backward walks the list in reverse from the cursor, `page` turns it around.
+        let ascending = self.ascending != self.backward;
+        if let Some(after) = &self.after {
+            query.push(format!(" AND ({expressions}) {} (", if ascending { ">" } else { "<" }));
+            for (i, ((_, ty), value)) in self.columns.iter().zip(after).enumerate() {
+                if i > 0 {
+                    query.push(", ");
+                }
+                query.push_bind(value.clone()).push(format!("::{ty}"));
+            }
+            query.push(")");
+        }
+        let order = if ascending { "ASC" } else { "DESC" };
+        let order = self
+            .columns
+            .iter()
+            .map(|(e, _)| format!("{e} {order}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        query
+            .push(format!(" ORDER BY {order} LIMIT "))
+            .push_bind(self.limit + 1);
+    }
+
+    /// Cuts `data`, fetched with the query from `push`, down to the page and sets the cursors
+    /// around it from the sort keys `key` gives in the order of the columns.
+    pub fn page<T>(&self, mut data: Vec<T>, key: impl Fn(&T) -> Vec<String>) -> Page<T> {
+        let more = data.len() as i64 > self.limit;
+        data.truncate(self.limit as usize);
+        if self.backward {
+            data.reverse();
+        }
+        let (next, prev) = match (&self.after, self.backward) {
+            (None, _) => (more, false),
+            (Some(_), false) => (more, true),
+            (Some(_), true) => (true, more),
+        };
+        Page {
+            next_cursor: data.last().filter(|_| next).map(|last| encode_cursor(false, key(last))),
+            prev_cursor: data
+                .first()
+                .filter(|_| prev)
+                .map(|first| encode_cursor(true, key(first))),
+            data,
+            total: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: &[(&str, &str)] = &[("id", "integer")];
+
+    fn page(cursor: Option<&str>, data: Vec<i32>) -> Page<i32> {
+        Keyset::new(COLUMNS, true, cursor, Some(2))
+            .unwrap()
+            .page(data, |id| vec![id.to_string()])
+    }
+
+    #[test]
+    fn pages_forward_and_back() {
+        let first = page(None, vec![1, 2, 3]);
+        assert_eq!(first.data, vec![1, 2]);
+        assert_eq!(first.prev_cursor, None);
+
+        let second = page(first.next_cursor.as_deref(), vec![3, 4, 5]);
+        assert_eq!(second.data, vec![3, 4]);
+        assert_eq!(
+            decode_cursor(second.prev_cursor.as_deref().unwrap()).unwrap(),
+            (true, vec!["3".into()])
+        );
+
+        // Walking backward from 3 fetches 2 and 1 in reverse.
+        let back = page(second.prev_cursor.as_deref(), vec![2, 1]);
+        assert_eq!(back.data, vec![1, 2]);
+        assert_eq!(back.prev_cursor, None);
+        assert_eq!(
+            decode_cursor(back.next_cursor.as_deref().unwrap()).unwrap(),
+            (false, vec!["2".into()])
+        );
+
+        let last = page(second.next_cursor.as_deref(), vec![5]);
+        assert_eq!((last.data, last.next_cursor), (vec![5], None));
+    }
+
+    #[test]
+    fn rejects_foreign_cursors() {
+        assert!(Keyset::new(COLUMNS, true, Some("not a cursor"), None).is_err());
+        let two = encode_cursor(false, vec!["1".into(), "2".into()]);
+        assert!(Keyset::new(COLUMNS, true, Some(&two), None).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::{collections::HashSet, env, sync::Arc, time::Duration};
+use types::{
+    api::{ApiError, ProofHost},
+    internal::ssr::YtJson,
+};
+
+/// A proof video that was found on its host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatedProof {
+    pub host: ProofHost,
+    pub id: String,
+    pub url: String,
+}
+
+/// Checks submitted proof links for one video host.
+#[async_trait]
+pub trait ProofValidator: Send + Sync {
+    fn host(&self) -> ProofHost;
+
+    /// Video id of `input`, `None` if the link belongs to another host.
+    fn parse(&self, input: &str) -> Option<String>;
+
+    /// Canonical link stored as the proof of a run.
+    fn url(&self, id: &str) -> String;
+
+    /// Whether the video exists and is publicly available.
+    async fn exists(&self, id: &str) -> Result<bool, ApiError>;
+}
+
+/// The validators enabled for this server, tried in order for every submission.
+#[derive(Clone)]
+pub struct ProofValidators(Arc<Vec<Box<dyn ProofValidator>>>);
+
+impl std::fmt::Debug for ProofValidators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.0.iter().map(|v| v.host())).finish()
+    }
+}
+
+impl ProofValidators {
+    pub fn new(validators: Vec<Box<dyn ProofValidator>>) -> Self {
+        Self(Arc::new(validators))
+    }
+
+    /// Reads the validator configuration from the environment. `PROOF_VALIDATION=mock`
+    /// accepts every YouTube-style id without network access, for local development.
+    /// Twitch clips are only accepted if `TWITCH_CLIENT_ID` and `TWITCH_TOKEN` are set.
+    pub fn from_env() -> Self {
+        if env::var("PROOF_VALIDATION").is_ok_and(|v| v == "mock") {
+            return Self::new(vec![Box::new(MockValidator::accepting_all(ProofHost::YouTube))]);
+        }
+
+        let mut validators: Vec<Box<dyn ProofValidator>> = Vec::new();
+        match env::var("YT_KEY") {
+            Ok(key) => validators.push(Box::new(YouTube::new(
+                key,
+                env::var("YT_API_URL").unwrap_or("https://www.googleapis.com/youtube/v3".into()),
+            ))),
+            Err(_) => log::warn!("YT_KEY is not set, YouTube proofs are disabled"),
+        }
+        if let (Ok(client_id), Ok(token)) = (env::var("TWITCH_CLIENT_ID"), env::var("TWITCH_TOKEN")) {
+            validators.push(Box::new(TwitchClips::new(
+                client_id,
+                token,
+                env::var("TWITCH_API_URL").unwrap_or("https://api.twitch.tv/helix".into()),
+            )));
+        }
+        validators.push(Box::new(Streamable::new(
+            env::var("STREAMABLE_API_URL").unwrap_or("https://api.streamable.com".into()),
+        )));
+        Self::new(validators)
+    }
+
+    /// Finds the host of `input` and checks that the video exists there.
+    pub async fn validate(&self, input: &str) -> Result<ValidatedProof, ApiError> {
+        let input = input.trim();
+        for validator in self.0.iter() {
+            if let Some(id) = validator.parse(input) {
+                return if validator.exists(&id).await? {
+                    Ok(ValidatedProof {
+                        host: validator.host(),
+                        url: validator.url(&id),
+                        id,
+                    })
+                } else {
+                    Err(ApiError::InvalidProof)
+                };
+            }
+        }
+        Err(ApiError::InvalidProof)
+    }
+}
+
+/// Client for the host APIs. Submissions wait for the check, so a host that hangs fails
+/// the submission instead of holding it open.
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to build proof validation client")
+}
+
+/// Strips the scheme and `www.` from a link, leaving host and path.
+fn strip_link(input: &str) -> &str {
+    let input = input
+        .strip_prefix("https://")
+        .or(input.strip_prefix("http://"))
+        .unwrap_or(input);
+    input.strip_prefix("www.").unwrap_or(input)
+}
+
+/// First path segment of `rest`, without query or fragment.
+fn segment(rest: &str) -> Option<&str> {
+    rest.split(['/', '?', '#', '&']).next().filter(|s| !s.is_empty())
+}
+
+fn is_id(id: &str, len: std::ops::RangeInclusive<usize>) -> bool {
+    len.contains(&id.len()) && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn parse_youtube(input: &str) -> Option<String> {
+    let link = strip_link(input);
+    let link = link.strip_prefix("m.").unwrap_or(link);
+    let id = if let Some(rest) = link.strip_prefix("youtu.be/") {
+        segment(rest)
+    } else if let Some(rest) = link.strip_prefix("youtube.com/watch?") {
+        rest.split('&').find_map(|p| p.strip_prefix("v="))
+    } else if let Some(rest) = link
+        .strip_prefix("youtube.com/shorts/")
+        .or(link.strip_prefix("youtube.com/live/"))
+        .or(link.strip_prefix("youtube.com/embed/"))
+    {
+        segment(rest)
+    } else {
+        Some(link)
+    }?;
+    is_id(id, 11..=11).then(|| id.to_string())
+}
+
+fn parse_twitch(input: &str) -> Option<String> {
+    let link = strip_link(input);
+    let link = link.strip_prefix("m.").unwrap_or(link);
+    let id = if let Some(rest) = link.strip_prefix("clips.twitch.tv/") {
+        segment(rest)
+    } else if let Some(rest) = link.strip_prefix("twitch.tv/") {
+        let (_, rest) = rest.split_once("/clip/")?;
+        segment(rest)
+    } else {
+        None
+    }?;
+    is_id(id, 1..=100).then(|| id.to_string())
+}
+
+fn parse_streamable(input: &str) -> Option<String> {
+    let rest = strip_link(input).strip_prefix("streamable.com/")?;
+    let rest = rest.strip_prefix("e/").unwrap_or(rest);
+    let id = segment(rest)?;
+    is_id(id, 1..=16).then(|| id.to_string())
+}
+
+/// Videos on YouTube, checked with the Data API. Bare 11 character ids are accepted
+/// for compatibility with older submissions.
+pub struct YouTube {
+    key: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl YouTube {
+    pub fn new(key: String, base_url: String) -> Self {
+        Self {
+            key,
+            base_url,
+            client: client(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProofValidator for YouTube {
+    fn host(&self) -> ProofHost {
+        ProofHost::YouTube
+    }
+
+    fn parse(&self, input: &str) -> Option<String> {
+        parse_youtube(input)
+    }
+
+    fn url(&self, id: &str) -> String {
+        format!("https://youtube.com/watch?v={id}")
+    }
+
+    async fn exists(&self, id: &str) -> Result<bool, ApiError> {
+        let v = self
+            .client
+            .get(format!("{}/videos", self.base_url))
+            .query(&[("key", self.key.as_str()), ("part", "id"), ("id", id)])
+            .send()
+            .await
+            .map_err(|_| ApiError::ServerError("YT api request failed".into()))?
+            .json::<YtJson>()
+            .await
+            .map_err(|_| ApiError::ServerError("Failed to parse yt api response".into()))?;
+        Ok(v.page_info.total_results > 0)
+    }
+}
+
+/// Clips on Twitch, checked with the Helix API using an app access token.
+pub struct TwitchClips {
+    client_id: String,
+    token: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl TwitchClips {
+    pub fn new(client_id: String, token: String, base_url: String) -> Self {
+        Self {
+            client_id,
+            token,
+            base_url,
+            client: client(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProofValidator for TwitchClips {
+    fn host(&self) -> ProofHost {
+        ProofHost::Twitch
+    }
+
+    fn parse(&self, input: &str) -> Option<String> {
+        parse_twitch(input)
+    }
+
+    fn url(&self, id: &str) -> String {
+        format!("https://clips.twitch.tv/{id}")
+    }
+
+    async fn exists(&self, id: &str) -> Result<bool, ApiError> {
+        #[derive(Deserialize)]
+        struct Clips {
+            data: Vec<serde_json::Value>,
+        }
+
+        let clips = self
+            .client
+            .get(format!("{}/clips", self.base_url))
+            .query(&[("id", id)])
+            .header("Client-Id", &self.client_id)
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|_| ApiError::ServerError("Twitch api request failed".into()))?
+            .json::<Clips>()
+            .await
+            .map_err(|_| ApiError::ServerError("Failed to parse twitch api response".into()))?;
+        Ok(!clips.data.is_empty())
+    }
+}
+
+/// Videos on Streamable, checked with its public API.
+pub struct Streamable {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl Streamable {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            client: client(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProofValidator for Streamable {
+    fn host(&self) -> ProofHost {
+        ProofHost::Streamable
+    }
+
+    fn parse(&self, input: &str) -> Option<String> {
+        parse_streamable(input)
+    }
+
+    fn url(&self, id: &str) -> String {
+        format!("https://streamable.com/{id}")
+    }
+
+    async fn exists(&self, id: &str) -> Result<bool, ApiError> {
+        let res = self
+            .client
+            .get(format!("{}/videos/{id}", self.base_url))
+            .send()
+            .await
+            .map_err(|_| ApiError::ServerError("Streamable api request failed".into()))?;
+        Ok(res.status().is_success())
+    }
+}
+
+/// Validator without network access. Parses links like the real validator for its host,
+/// and treats a video as existing if it was added, or always when built with `accepting_all`.
+pub struct MockValidator {
+    host: ProofHost,
+    ids: Option<HashSet<String>>,
+}
+
+impl MockValidator {
+    pub fn new(host: ProofHost, ids: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            host,
+            ids: Some(ids.into_iter().collect()),
+        }
+    }
+
+    pub fn accepting_all(host: ProofHost) -> Self {
+        Self { host, ids: None }
+    }
+}
+
+#[async_trait]
+impl ProofValidator for MockValidator {
+    fn host(&self) -> ProofHost {
+        self.host
+    }
+
+    fn parse(&self, input: &str) -> Option<String> {
+        match self.host {
+            ProofHost::YouTube => parse_youtube(input),
+            ProofHost::Twitch => parse_twitch(input),
+            ProofHost::Streamable => parse_streamable(input),
+            ProofHost::Other => Some(input.to_string()),
+        }
+    }
+
+    fn url(&self, id: &str) -> String {
+        match self.host {
+            ProofHost::YouTube => format!("https://youtube.com/watch?v={id}"),
+            ProofHost::Twitch => format!("https://clips.twitch.tv/{id}"),
+            ProofHost::Streamable => format!("https://streamable.com/{id}"),
+            ProofHost::Other => id.to_string(),
+        }
+    }
+
+    async fn exists(&self, id: &str) -> Result<bool, ApiError> {
+        Ok(self.ids.as_ref().is_none_or(|ids| ids.contains(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_youtube_links() {
+        let id = Some("dQw4w9WgXcQ".to_string());
+        for link in [
+            "dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ?t=42",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/watch?feature=shared&v=dQw4w9WgXcQ&t=42s",
+            "http://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://youtube.com/shorts/dQw4w9WgXcQ?si=abc",
+            "https://www.youtube.com/live/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(parse_youtube(link), id, "{link}");
+        }
+        for link in [
+            "",
+            "dQw4w9WgXc",
+            "https://youtu.be/",
+            "https://youtube.com/watch?list=PL123",
+            "https://youtube.com/watch?v=dQw4w9WgXcQQ",
+            "https://youtube.com/shorts/dQw4w9WgX!Q",
+            "https://vimeo.com/123456789",
+            "https://streamable.com/abc123",
+        ] {
+            assert_eq!(parse_youtube(link), None, "{link}");
+        }
+    }
+
+    #[test]
+    fn parses_twitch_links() {
+        let id = Some("FunnyClip-AbC_123".to_string());
+        for link in [
+            "https://clips.twitch.tv/FunnyClip-AbC_123",
+            "clips.twitch.tv/FunnyClip-AbC_123?filter=clips",
+            "https://www.twitch.tv/streamer/clip/FunnyClip-AbC_123",
+            "https://m.twitch.tv/streamer/clip/FunnyClip-AbC_123?range=7d",
+        ] {
+            assert_eq!(parse_twitch(link), id, "{link}");
+        }
+        for link in [
+            "FunnyClip-AbC_123",
+            "https://clips.twitch.tv/",
+            "https://www.twitch.tv/streamer",
+            "https://www.twitch.tv/videos/123456789",
+            "https://clips.twitch.tv/Funny.Clip",
+            "https://youtu.be/dQw4w9WgXcQ",
+        ] {
+            assert_eq!(parse_twitch(link), None, "{link}");
+        }
+    }
+
+    #[test]
+    fn parses_streamable_links() {
+        let id = Some("abc123".to_string());
+        for link in [
+            "https://streamable.com/abc123",
+            "streamable.com/abc123?src=share",
+            "https://www.streamable.com/e/abc123",
+        ] {
+            assert_eq!(parse_streamable(link), id, "{link}");
+        }
+        for link in [
+            "abc123",
+            "https://streamable.com/",
+            "https://streamable.com/abcdefghijklmnopq",
+            "https://streamable.co/abc123",
+        ] {
+            assert_eq!(parse_streamable(link), None, "{link}");
+        }
+    }
+
+    #[test]
+    fn validates_with_the_matching_host() {
+        let validators = ProofValidators::new(vec![
+            Box::new(MockValidator::new(ProofHost::YouTube, ["dQw4w9WgXcQ".to_string()])),
+            Box::new(MockValidator::accepting_all(ProofHost::Streamable)),
+        ]);
+        let validate = |input| futures::executor::block_on(validators.validate(input));
+
+        assert_eq!(
+            validate(" https://youtu.be/dQw4w9WgXcQ ").unwrap(),
+            ValidatedProof {
+                host: ProofHost::YouTube,
+                id: "dQw4w9WgXcQ".into(),
+                url: "https://youtube.com/watch?v=dQw4w9WgXcQ".into(),
+            }
+        );
+        assert_eq!(
+            validate("https://streamable.com/e/abc123").unwrap().url,
+            "https://streamable.com/abc123"
+        );
+        // Parsed by YouTube but not an existing video
+        assert!(matches!(
+            validate("https://youtu.be/aaaaaaaaaaa"),
+            Err(ApiError::InvalidProof)
+        ));
+        assert!(matches!(
+            validate("https://clips.twitch.tv/Clip"),
+            Err(ApiError::InvalidProof)
+        ));
+    }
+}
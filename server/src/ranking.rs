@@ -0,0 +1,285 @@
+use chrono::{DateTime, Local};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use sqlx::{FromRow, PgConnection};
+use std::collections::HashSet;
+use types::{
+    api::{ApiError, Title},
+    rating::{RatingParams, points},
+};
+
+#[derive(FromRow)]
+struct Section {
+    patch: String,
+    layout: String,
+    category: String,
+}
+
+#[derive(FromRow)]
+struct SectionRun {
+    id: i32,
+    user_id: i64,
+    time: Decimal,
+}
+
+#[derive(FromRow)]
+struct UserTotals {
+    user_id: i64,
+    sections: i64,
+    points: f64,
+}
+
+#[derive(FromRow)]
+struct RankRow {
+    id: i32,
+    rating: f64,
+    updated_at: DateTime<Local>,
+}
+
+/// Re-derives everything that depends on the runs of a section: pb and wr flags, points,
+/// and the category and overall rankings of its patch. Call this in the same transaction
+/// after submitting, deleting, editing or rejecting runs. Rank and title changes are
+/// recorded as activity by the `rank_update` trigger.
+pub async fn recalculate_section(conn: &mut PgConnection, section_id: i32) -> Result<(), ApiError> {
+    let params = RatingParams::default();
+    // Locking the section makes concurrent changes to it wait, so they see each other's runs.
+    // Runs already hold a key share lock on it, which a stronger lock would deadlock with.
+    let section = sqlx::query_as::<_, Section>(
+        r#"SELECT patch, layout, category
+        FROM section
+        WHERE id = $1
+        FOR NO KEY UPDATE;"#,
+    )
+    .bind(section_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+
+    update_section(conn, section_id).await?;
+    lock_patch(conn, &section.patch).await?;
+    recalculate_ranks(
+        conn,
+        &params,
+        &section.patch,
+        Some(&section.layout),
+        Some(&section.category),
+    )
+    .await?;
+    recalculate_ranks(conn, &params, &section.patch, None, None).await
+}
+
+/// Recomputes a whole patch from its runs, or only one layout or category of it when
+/// given, then the overall ranking of the patch. Used after changing `params`, or to
+/// repair rankings after runs were changed by hand.
+pub async fn recompute_rankings(
+    conn: &mut PgConnection,
+    params: &RatingParams,
+    patch: &str,
+    layout: Option<&str>,
+    category: Option<&str>,
+) -> Result<(), ApiError> {
+    let sections = sqlx::query_as::<_, (i32, String, String)>(
+        r#"SELECT id, layout, category
+        FROM section
+        WHERE patch = $1 AND ($2::varchar IS NULL OR layout = $2) AND ($3::varchar IS NULL OR category = $3)
+        ORDER BY id
+        FOR NO KEY UPDATE;"#,
+    )
+    .bind(patch)
+    .bind(layout)
+    .bind(category)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+
+    let mut leaderboards = vec![];
+    for (id, layout, category) in sections {
+        update_section(conn, id).await?;
+        if !leaderboards.contains(&(layout.clone(), category.clone())) {
+            leaderboards.push((layout, category));
+        }
+    }
+    lock_patch(conn, patch).await?;
+    for (layout, category) in leaderboards {
+        recalculate_ranks(conn, params, patch, Some(&layout), Some(&category)).await?;
+    }
+    recalculate_ranks(conn, params, patch, None, None).await
+}
+
+/// Makes rank recalculations of `patch` wait for each other until the end of the transaction.
+/// Every section of a patch counts towards its overall ranking, so the section locks aren't
+/// enough. Sections are always locked first, so the two can't deadlock.
+async fn lock_patch(conn: &mut PgConnection, patch: &str) -> Result<(), ApiError> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('rank:' || $1));")
+        .bind(patch)
+        .execute(&mut *conn)
+        .await
+        .map(|_| ())
+        .map_err(|_| ApiError::ServerError("Database lock failed".into()))
+}
+
+/// Sets pb and wr flags and points of every run in a section.
+async fn update_section(conn: &mut PgConnection, section_id: i32) -> Result<(), ApiError> {
+    let runs = sqlx::query_as::<_, SectionRun>(
+        r#"SELECT id, user_id, time
+        FROM run
+        WHERE section_id = $1 AND NOT rejected
+        ORDER BY time ASC, created_at ASC, id ASC;"#,
+    )
+    .bind(section_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+
+    // Runs are ordered, so the first run is the wr and the first run of every user their pb.
+    let wr = runs.first().and_then(|r| r.time.to_f64()).unwrap_or_default();
+    let mut users = HashSet::new();
+    let (mut ids, mut pbs, mut wrs, mut run_points) = (vec![], vec![], vec![], vec![]);
+    for (i, run) in runs.iter().enumerate() {
+        let pb = users.insert(run.user_id);
+        ids.push(run.id);
+        pbs.push(pb);
+        wrs.push(i == 0);
+        run_points.push(pb.then(|| points(run.time.to_f64().unwrap_or_default(), wr) as f32));
+    }
+    sqlx::query(
+        r#"UPDATE run r
+        SET is_pb = n.is_pb, is_wr = n.is_wr, points = n.points
+        FROM UNNEST($2::integer[], $3::boolean[], $4::boolean[], $5::real[]) AS n(id, is_pb, is_wr, points)
+        WHERE r.section_id = $1 AND r.id = n.id;"#,
+    )
+    .bind(section_id)
+    .bind(ids)
+    .bind(pbs)
+    .bind(wrs)
+    .bind(run_points)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+    sqlx::query(
+        r#"UPDATE run
+        SET is_pb = FALSE, is_wr = FALSE, points = NULL
+        WHERE section_id = $1 AND rejected AND (is_pb OR is_wr OR points IS NOT NULL);"#,
+    )
+    .bind(section_id)
+    .execute(&mut *conn)
+    .await
+    .map(|_| ())
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))
+}
+
+/// Recalculates the rank rows of one leaderboard, `None` for layout and category being the
+/// overall leaderboard of the patch. Users without runs lose their rank, new users get one.
+async fn recalculate_ranks(
+    conn: &mut PgConnection,
+    params: &RatingParams,
+    patch: &str,
+    layout: Option<&str>,
+    category: Option<&str>,
+) -> Result<(), ApiError> {
+    let total = sqlx::query_scalar::<_, i64>(
+        r#"SELECT COUNT(id)
+        FROM section
+        WHERE patch = $1 AND ($2::varchar IS NULL OR layout = $2) AND ($3::varchar IS NULL OR category = $3);"#,
+    )
+    .bind(patch)
+    .bind(layout)
+    .bind(category)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+    let totals = sqlx::query_as::<_, UserTotals>(
+        r#"SELECT r.user_id, COUNT(DISTINCT r.section_id) AS sections, COALESCE(AVG(r.points), 0.0) AS points
+        FROM run r
+        INNER JOIN section s ON r.section_id = s.id
+        WHERE s.patch = $1 AND ($2::varchar IS NULL OR s.layout = $2) AND ($3::varchar IS NULL OR s.category = $3)
+            AND NOT r.rejected
+        GROUP BY r.user_id;"#,
+    )
+    .bind(patch)
+    .bind(layout)
+    .bind(category)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+
+    let (mut users, mut percentages, mut ratings, mut user_points) = (vec![], vec![], vec![], vec![]);
+    for t in totals {
+        let percentage = t.sections as f64 / total.max(1) as f64;
+        users.push(t.user_id);
+        percentages.push(percentage);
+        ratings.push(params.rating(percentage, t.points));
+        user_points.push(t.points);
+    }
+    sqlx::query(
+        r#"DELETE FROM rank
+        WHERE patch = $1 AND layout IS NOT DISTINCT FROM $2 AND category IS NOT DISTINCT FROM $3
+            AND user_id <> ALL($4);"#,
+    )
+    .bind(patch)
+    .bind(layout)
+    .bind(category)
+    .bind(&users)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database delete failed".into()))?;
+    // Ties in rating go to whoever reached it first, so `updated_at` only moves on changes.
+    sqlx::query(
+        r#"INSERT INTO rank (user_id, patch, layout, category, title, rank, rating, percentage, points)
+        SELECT n.user_id, $1, $2, $3, 'None'::title, (SELECT COUNT(id)
+                FROM rank
+                WHERE patch = $1 AND layout IS NOT DISTINCT FROM $2 AND category IS NOT DISTINCT FROM $3) + 1,
+            n.rating, n.percentage, n.points
+        FROM UNNEST($4::bigint[], $5::double precision[], $6::double precision[], $7::double precision[])
+            AS n(user_id, percentage, rating, points)
+        ON CONFLICT ON CONSTRAINT unique_title DO UPDATE
+        SET rating = EXCLUDED.rating, percentage = EXCLUDED.percentage, points = EXCLUDED.points,
+            updated_at = CASE WHEN abs(rank.rating - EXCLUDED.rating) > 1e-9 THEN now() ELSE rank.updated_at END;"#,
+    )
+    .bind(patch)
+    .bind(layout)
+    .bind(category)
+    .bind(&users)
+    .bind(percentages)
+    .bind(ratings)
+    .bind(user_points)
+    .execute(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))?;
+
+    let ranks = sqlx::query_as::<_, RankRow>(
+        r#"SELECT id, rating, updated_at
+        FROM rank
+        WHERE patch = $1 AND layout IS NOT DISTINCT FROM $2 AND category IS NOT DISTINCT FROM $3
+        ORDER BY rating DESC, updated_at ASC;"#,
+    )
+    .bind(patch)
+    .bind(layout)
+    .bind(category)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|_| ApiError::ServerError("Database lookup failed".into()))?;
+    let (mut ids, mut positions, mut titles) = (vec![], vec![], Vec::<Title>::new());
+    let mut position = 0;
+    for (i, r) in ranks.iter().enumerate() {
+        // Equal ratings reached at the same time share their rank.
+        if i == 0 || (r.rating, r.updated_at) != (ranks[i - 1].rating, ranks[i - 1].updated_at) {
+            position = i as i32 + 1;
+        }
+        ids.push(r.id);
+        positions.push(position);
+        titles.push(params.title(position, r.rating));
+    }
+    sqlx::query(
+        r#"UPDATE rank r
+        SET rank = n.rank, title = n.title
+        FROM UNNEST($1::integer[], $2::integer[], $3::title[]) AS n(id, rank, title)
+        WHERE r.id = n.id AND (r.rank <> n.rank OR r.title <> n.title);"#,
+    )
+    .bind(ids)
+    .bind(positions)
+    .bind(titles)
+    .execute(&mut *conn)
+    .await
+    .map(|_| ())
+    .map_err(|_| ApiError::ServerError("Database update failed".into()))
+}
@@ -0,0 +1,335 @@
+//! Versioned JSON API for bots and external tools, nested under `/api/v1` by the site.
+//! Unlike the server functions, routes and response shapes here only change with a new
+//! version. Lists are paginated with opaque cursors, the OpenAPI document is generated
+//! from the handlers and served at `/api/v1/openapi.json`.
+
+use axum::{
+    Json, Router,
+    extract::{FromRef, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::{DateTime, Local};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use types::{api::*, internal::ssr::GetUser};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{api::ssr::*, pagination::Keyset};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Lucio Surf League API", version = "1"),
+    servers((url = "/api/v1")),
+    paths(patches, sections, section, runs, run, rankings, user, user_rankings, activity)
+)]
+pub struct ApiDoc;
+
+/// Routes of the API, relative to `/api/v1`.
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    PgPool: FromRef<S>,
+{
+    Router::new()
+        .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+        .route("/patches", get(patches))
+        .route("/sections", get(sections))
+        .route("/sections/{id}", get(section))
+        .route("/runs", get(runs))
+        .route("/runs/{id}", get(run))
+        .route("/rankings", get(rankings))
+        .route("/users/{id}", get(user))
+        .route("/users/{id}/rankings", get(user_rankings))
+        .route("/activity", get(activity))
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    error: String,
+}
+
+/// `ApiError` as a status code and JSON body.
+pub struct RestError(ApiError);
+
+impl From<ApiError> for RestError {
+    fn from(value: ApiError) -> Self {
+        Self(value)
+    }
+}
+
+impl IntoResponse for RestError {
+    fn into_response(self) -> Response {
+        let status = match self.0 {
+            ApiError::Unauthenticated => StatusCode::UNAUTHORIZED,
+            ApiError::Unauthorized => StatusCode::FORBIDDEN,
+            ApiError::NotFound | ApiError::InvalidSection => StatusCode::NOT_FOUND,
+            ApiError::TooManyAttempts(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::ServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (
+            status,
+            Json(ErrorBody {
+                error: self.0.to_string(),
+            }),
+        )
+            .into_response()
+    }
+}
+
+type RestResult<T> = Result<Json<T>, RestError>;
+
+fn lookup_failed(_: sqlx::Error) -> ApiError {
+    ApiError::ServerError("Database lookup failed".into())
+}
+
+/// All patches, oldest first.
+#[utoipa::path(get, path = "/patches", responses((status = 200, body = Vec<Patch>)))]
+async fn patches(State(pool): State<PgPool>) -> RestResult<Vec<Patch>> {
+    Ok(Json(crate::api::ssr::patches(&pool).await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SectionsQuery {
+    patch: Option<String>,
+    layout: Option<String>,
+    category: Option<String>,
+    map: Option<String>,
+    /// `next_cursor` or `prev_cursor` of another page.
+    cursor: Option<String>,
+    /// Items per page, 50 by default and at most 200.
+    limit: Option<i64>,
+}
+
+/// Sections in the order they were created.
+#[utoipa::path(get, path = "/sections", params(SectionsQuery), responses(
+    (status = 200, body = Page<Section>),
+    (status = 400, body = ErrorBody),
+))]
+async fn sections(State(pool): State<PgPool>, Query(q): Query<SectionsQuery>) -> RestResult<Page<Section>> {
+    let keyset = Keyset::new(&[("id", "integer")], true, q.cursor.as_deref(), q.limit)?;
+    let mut query =
+        QueryBuilder::<Postgres>::new("SELECT id, patch, layout, category, map, code FROM section WHERE 1 = 1");
+    if let Some(patch) = q.patch {
+        query.push(" AND patch = ").push_bind(patch);
+    }
+    if let Some(layout) = q.layout {
+        query.push(" AND layout = ").push_bind(layout);
+    }
+    if let Some(category) = q.category {
+        query.push(" AND category = ").push_bind(category);
+    }
+    if let Some(map) = q.map {
+        query.push(" AND map = ").push_bind(map);
+    }
+    keyset.push(&mut query);
+    let sections = query
+        .build_query_as::<Section>()
+        .fetch_all(&pool)
+        .await
+        .map_err(lookup_failed)?;
+    Ok(Json(keyset.page(sections, |s| vec![s.id.to_string()])))
+}
+
+/// A section with all of its runs that weren't rejected, oldest first.
+#[utoipa::path(get, path = "/sections/{id}", responses(
+    (status = 200, body = SectionRuns),
+    (status = 404, body = ErrorBody),
+))]
+async fn section(State(pool): State<PgPool>, Path(id): Path<i32>) -> RestResult<SectionRuns> {
+    Ok(Json(section_runs(&pool, id).await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RunsQuery {
+    user: Option<i64>,
+    patch: Option<String>,
+    layout: Option<String>,
+    category: Option<String>,
+    map: Option<String>,
+    /// Only runs with this time or faster.
+    faster: Option<Decimal>,
+    /// Only runs with this time or slower.
+    slower: Option<Decimal>,
+    /// Only runs submitted at or before this time.
+    before: Option<DateTime<Local>>,
+    /// Only runs submitted at or after this time.
+    after: Option<DateTime<Local>>,
+    /// Oldest runs first instead of newest.
+    ascending: Option<bool>,
+    /// `next_cursor` or `prev_cursor` of another page.
+    cursor: Option<String>,
+    /// Items per page, 50 by default and at most 200.
+    limit: Option<i64>,
+}
+
+/// Runs that weren't rejected, newest first.
+#[utoipa::path(get, path = "/runs", params(RunsQuery), responses(
+    (status = 200, body = Page<Run>),
+    (status = 400, body = ErrorBody),
+))]
+async fn runs(State(pool): State<PgPool>, Query(q): Query<RunsQuery>) -> RestResult<Page<Run>> {
+    let filter = RunFilters {
+        user: q.user,
+        patch: q.patch,
+        layout: q.layout,
+        category: q.category,
+        map: q.map,
+        faster: q.faster,
+        slower: q.slower,
+        before: q.before,
+        after: q.after,
+        ascending: q.ascending.unwrap_or(false),
+        ..Default::default()
+    };
+    let keyset = Keyset::new(run_keyset(&filter.sort), filter.ascending, q.cursor.as_deref(), q.limit)?;
+    let mut query = QueryBuilder::<Postgres>::new(RUNS);
+    query.push(" AND NOT rejected");
+    push_run_filters(&mut query, &filter);
+    keyset.push(&mut query);
+    let runs = query
+        .build_query_as::<Run>()
+        .fetch_all(&pool)
+        .await
+        .map_err(lookup_failed)?;
+    Ok(Json(keyset.page(runs, |r| run_key(&filter.sort, r))))
+}
+
+/// A run that wasn't rejected.
+#[utoipa::path(get, path = "/runs/{id}", responses(
+    (status = 200, body = Run),
+    (status = 404, body = ErrorBody),
+))]
+async fn run(State(pool): State<PgPool>, Path(id): Path<i32>) -> RestResult<Run> {
+    QueryBuilder::<Postgres>::new(RUNS)
+        .push(" AND NOT rejected AND run.id = ")
+        .push_bind(id)
+        .build_query_as::<Run>()
+        .fetch_optional(&pool)
+        .await
+        .map_err(lookup_failed)?
+        .map(Json)
+        .ok_or(ApiError::NotFound.into())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RankingsQuery {
+    patch: String,
+    /// Layout of a category leaderboard, leave out with `category` for the overall ranking.
+    layout: Option<String>,
+    category: Option<String>,
+    /// `next_cursor` or `prev_cursor` of another page.
+    cursor: Option<String>,
+    /// Items per page, 50 by default and at most 200.
+    limit: Option<i64>,
+}
+
+/// The ranking of a leaderboard, best first.
+#[utoipa::path(get, path = "/rankings", params(RankingsQuery), responses(
+    (status = 200, body = Page<Ranking>),
+    (status = 400, body = ErrorBody),
+))]
+async fn rankings(State(pool): State<PgPool>, Query(q): Query<RankingsQuery>) -> RestResult<Page<Ranking>> {
+    if q.layout.is_some() != q.category.is_some() {
+        return Err(ApiError::InvalidInput.into());
+    }
+    let keyset = Keyset::new(
+        &[("r.rank", "integer"), ("r.id", "integer")],
+        true,
+        q.cursor.as_deref(),
+        q.limit,
+    )?;
+    let mut query = QueryBuilder::<Postgres>::new(RANKINGS);
+    query
+        .push("r.patch = ")
+        .push_bind(q.patch)
+        .push(" AND r.layout IS NOT DISTINCT FROM ")
+        .push_bind(q.layout)
+        .push(" AND r.category IS NOT DISTINCT FROM ")
+        .push_bind(q.category);
+    keyset.push(&mut query);
+    let rankings = query
+        .build_query_as::<Ranking>()
+        .fetch_all(&pool)
+        .await
+        .map_err(lookup_failed)?;
+    Ok(Json(
+        keyset.page(rankings, |r| vec![r.rank.to_string(), r.id.to_string()]),
+    ))
+}
+
+/// A user with their current ranks.
+#[utoipa::path(get, path = "/users/{id}", responses(
+    (status = 200, body = User),
+    (status = 404, body = ErrorBody),
+))]
+async fn user(State(pool): State<PgPool>, Path(id): Path<i64>) -> RestResult<User> {
+    User::get(id, &pool).await.map(Json).ok_or(ApiError::NotFound.into())
+}
+
+/// Every ranking of a user, across patches and leaderboards.
+#[utoipa::path(get, path = "/users/{id}/rankings", responses((status = 200, body = Vec<Ranking>)))]
+async fn user_rankings(State(pool): State<PgPool>, Path(id): Path<i64>) -> RestResult<Vec<Ranking>> {
+    Ok(Json(crate::api::ssr::user_rankings(&pool, id).await?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ActivityQuery {
+    /// `join`, `rank` or `title`.
+    event: Option<String>,
+    user: Option<i64>,
+    patch: Option<String>,
+    layout: Option<String>,
+    category: Option<String>,
+    /// Only activity at or before this time.
+    before: Option<DateTime<Local>>,
+    /// Only activity at or after this time.
+    after: Option<DateTime<Local>>,
+    /// Oldest activity first instead of newest.
+    ascending: Option<bool>,
+    /// `next_cursor` or `prev_cursor` of another page.
+    cursor: Option<String>,
+    /// Items per page, 50 by default and at most 200.
+    limit: Option<i64>,
+}
+
+/// Joins, rank and title changes, newest first.
+#[utoipa::path(get, path = "/activity", params(ActivityQuery), responses(
+    (status = 200, body = Page<Activity>),
+    (status = 400, body = ErrorBody),
+))]
+async fn activity(State(pool): State<PgPool>, Query(q): Query<ActivityQuery>) -> RestResult<Page<Activity>> {
+    let filter = ActivityFilters {
+        event: q.event,
+        user: q.user,
+        patch: q.patch,
+        layout: q.layout,
+        category: q.category,
+        before: q.before,
+        after: q.after,
+        ascending: q.ascending.unwrap_or(false),
+        ..Default::default()
+    };
+    let keyset = Keyset::new(
+        activity_keyset(&filter.sort),
+        filter.ascending,
+        q.cursor.as_deref(),
+        q.limit,
+    )?;
+    let mut query = QueryBuilder::<Postgres>::new(ACTIVITY);
+    push_activity_filters(&mut query, &filter);
+    keyset.push(&mut query);
+    let activity = query
+        .build_query_as::<Activity>()
+        .fetch_all(&pool)
+        .await
+        .map_err(lookup_failed)?;
+    Ok(Json(keyset.page(activity, |a| activity_key(&filter.sort, a))))
+}
@@ -0,0 +1,126 @@
+//! Leaderboard filters against a real database, run like `recalculate.rs`.
+#![cfg(feature = "ssr")]
+
+use server::{api::ssr::category_runs, auth::ssr::MIGRATOR};
+use sqlx::PgPool;
+use types::api::LeaderboardFilters;
+
+/// Two maps of a leaderboard, the runs of Busan submitted one minute apart in this order.
+/// Returns the ids of the Busan runs.
+async fn setup(pool: &PgPool) -> Vec<i32> {
+    let users = sqlx::query_scalar::<_, i64>(
+        r#"INSERT INTO "user" (name, password)
+        VALUES ('alpha', ''), ('bravo', ''), ('charlie', '')
+        RETURNING id;"#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    let sections = sqlx::query_scalar::<_, i32>(
+        r#"INSERT INTO section (patch, layout, category, map, code)
+        VALUES ('2.13', '1', 'Standard', 'Busan', 'BUS1'), ('2.13', '1', 'Standard', 'Ilios', 'ILI1')
+        RETURNING id;"#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap();
+
+    let mut ids = vec![];
+    for (i, (user, time, rejected)) in [
+        (0, "12.000", false),
+        (1, "13.000", false),
+        // Rejected runs never were records
+        (2, "10.000", true),
+        (0, "12.000", false),
+        (1, "11.500", false),
+        (0, "11.500", false),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let id = sqlx::query_scalar::<_, i32>(
+            r#"INSERT INTO run (section_id, user_id, time, proof, verified, rejected, created_at)
+            VALUES ($1, $2, $3::numeric, 'https://youtube.com/watch?v=dQw4w9WgXcQ', TRUE, $4,
+                now() - make_interval(mins => 10 - $5))
+            RETURNING id;"#,
+        )
+        .bind(sections[0])
+        .bind(users[user])
+        .bind(time)
+        .bind(rejected)
+        .bind(i as i32)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        ids.push(id);
+    }
+    ids
+}
+
+/// Ids of the Busan runs picked by `filter`. Ilios has no runs, but is listed anyway.
+async fn runs(pool: &PgPool, filter: &str, sort: Option<&str>) -> Vec<i32> {
+    let sections = category_runs(
+        pool,
+        "2.13".into(),
+        "1".into(),
+        "Standard".into(),
+        &LeaderboardFilters {
+            filter: Some(filter.into()),
+            sort: sort.map(String::from),
+            user: None,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        sections.iter().map(|s| s.map.as_str()).collect::<Vec<_>>(),
+        ["Busan", "Ilios"]
+    );
+    assert!(sections[1].runs.is_empty());
+    sections[0].runs.iter().map(|r| r.id).collect()
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn was_pb_and_was_wr_follow_history(pool: PgPool) {
+    let ids = setup(&pool).await;
+
+    // First runs count, repeating a time doesn't
+    assert_eq!(
+        runs(&pool, "was_pb", Some("date")).await,
+        [ids[5], ids[4], ids[1], ids[0]]
+    );
+    assert_eq!(runs(&pool, "was_pb", None).await, [ids[4], ids[5], ids[0], ids[1]]);
+    // Tying the record isn't one, and the rejected 10.000 didn't set it
+    assert_eq!(runs(&pool, "was_wr", None).await, [ids[4], ids[0]]);
+    assert_eq!(
+        runs(&pool, "none", None).await,
+        [ids[4], ids[5], ids[0], ids[3], ids[1]]
+    );
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn filters_by_user(pool: PgPool) {
+    let ids = setup(&pool).await;
+    let alpha = sqlx::query_scalar::<_, i64>(r#"SELECT id FROM "user" WHERE name = 'alpha';"#)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    let sections = category_runs(
+        &pool,
+        "2.13".into(),
+        "1".into(),
+        "Standard".into(),
+        &LeaderboardFilters {
+            filter: Some("was_pb".into()),
+            sort: Some("date".into()),
+            user: Some(alpha),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        sections[0].runs.iter().map(|r| r.id).collect::<Vec<_>>(),
+        [ids[5], ids[0]]
+    );
+}
@@ -0,0 +1,80 @@
+//! Login throttling against a real database. Every `sqlx::test` gets a freshly migrated
+//! database, point `DATABASE_URL` at a Postgres the tests may create databases on:
+//!
+//! `DATABASE_URL=postgres://postgres@localhost/postgres cargo test -p server`
+#![cfg(feature = "ssr")]
+
+use server::auth::ssr::{MIGRATOR, attempt_keys, check_lockout, clear_failures, record_failure};
+use sqlx::PgPool;
+use types::api::ApiError;
+
+/// Seconds until the key is unlocked, `None` if it isn't locked.
+async fn locked_for(pool: &PgPool, keys: &[(String, i32)]) -> Option<i64> {
+    match check_lockout(pool, keys).await {
+        Ok(()) => None,
+        Err(ApiError::TooManyAttempts(secs)) => Some(secs),
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn locks_out_at_threshold(pool: PgPool) {
+    // Without a request there is no client IP, only the username is tracked
+    let keys = attempt_keys("Alice");
+    assert_eq!(keys, vec![("user:alice".to_string(), 5)]);
+
+    for _ in 0..4 {
+        record_failure(&pool, &keys).await.unwrap();
+    }
+    assert_eq!(locked_for(&pool, &keys).await, None);
+    record_failure(&pool, &keys).await.unwrap();
+    assert!(
+        locked_for(&pool, &keys)
+            .await
+            .is_some_and(|secs| (29..=30).contains(&secs))
+    );
+
+    // Usernames are tracked case insensitively and cleared on a successful login
+    assert!(locked_for(&pool, &attempt_keys("ALICE")).await.is_some());
+    clear_failures(&pool, "alice").await.unwrap();
+    assert_eq!(locked_for(&pool, &keys).await, None);
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn lockout_doubles_up_to_an_hour(pool: PgPool) {
+    let keys = vec![("ip:192.0.2.1".to_string(), 20)];
+
+    for _ in 0..19 {
+        record_failure(&pool, &keys).await.unwrap();
+    }
+    assert_eq!(locked_for(&pool, &keys).await, None);
+    for expected in [30, 60, 120, 240, 480, 960, 1920, 3600, 3600, 3600] {
+        record_failure(&pool, &keys).await.unwrap();
+        let secs = locked_for(&pool, &keys).await.unwrap();
+        assert!(
+            (expected - 1..=expected).contains(&secs),
+            "expected {expected}s, locked for {secs}s"
+        );
+    }
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn failures_reset_after_an_hour(pool: PgPool) {
+    let keys = attempt_keys("bob");
+
+    for _ in 0..4 {
+        record_failure(&pool, &keys).await.unwrap();
+    }
+    sqlx::query("UPDATE login_attempt SET updated_at = now() - interval '61 minutes';")
+        .execute(&pool)
+        .await
+        .unwrap();
+    record_failure(&pool, &keys).await.unwrap();
+    assert_eq!(locked_for(&pool, &keys).await, None);
+
+    let failures = sqlx::query_scalar::<_, i32>("SELECT failures FROM login_attempt WHERE key = 'user:bob';")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(failures, 1);
+}
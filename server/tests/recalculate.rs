@@ -0,0 +1,365 @@
+//! Section recalculation against a real database. `sqlx::test` creates a fresh database
+//! with all migrations per test, set `DATABASE_URL` to a local Postgres to run them:
+//!
+//! `DATABASE_URL=postgres://postgres@localhost/postgres cargo test -p server`
+#![cfg(feature = "ssr")]
+
+use server::{
+    auth::ssr::MIGRATOR,
+    ranking::{recalculate_section, recompute_rankings},
+};
+use sqlx::{PgExecutor, PgPool};
+use types::{api::Title, rating::RatingParams};
+
+struct Setup {
+    sections: [i32; 2],
+    users: [i64; 3],
+}
+
+async fn setup(pool: &PgPool) -> Setup {
+    let users = sqlx::query_scalar::<_, i64>(
+        r#"INSERT INTO "user" (name, password)
+        VALUES ('alpha', ''), ('bravo', ''), ('charlie', '')
+        RETURNING id;"#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    let sections = sqlx::query_scalar::<_, i32>(
+        r#"INSERT INTO section (patch, layout, category, map, code)
+        VALUES ('2.13', '1', 'Standard', 'Busan', 'BUS1'), ('2.13', '1', 'Standard', 'Ilios', 'ILI1')
+        RETURNING id;"#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    Setup {
+        sections: sections.try_into().unwrap(),
+        users: users.try_into().unwrap(),
+    }
+}
+
+async fn insert(conn: impl PgExecutor<'_>, section_id: i32, user_id: i64, time: &str) -> i32 {
+    sqlx::query_scalar::<_, i32>(
+        r#"INSERT INTO run (section_id, user_id, time, proof, verified)
+        VALUES ($1, $2, $3::numeric, 'https://youtube.com/watch?v=dQw4w9WgXcQ', TRUE)
+        RETURNING id;"#,
+    )
+    .bind(section_id)
+    .bind(user_id)
+    .bind(time)
+    .fetch_one(conn)
+    .await
+    .unwrap()
+}
+
+/// Inserts and recalculates a run the way `submit` does.
+async fn submit(pool: &PgPool, section_id: i32, user_id: i64, time: &str) -> i32 {
+    let id = insert(pool, section_id, user_id, time).await;
+    recalculate(pool, section_id).await;
+    id
+}
+
+/// Inserts all runs before any of their transactions recalculates, like submits arriving
+/// at once, then recalculates and commits them concurrently.
+async fn submit_concurrently(pool: &PgPool, runs: &[(i32, i64, &str)]) -> Vec<i32> {
+    let mut txs = vec![];
+    let mut ids = vec![];
+    for &(section_id, user_id, time) in runs {
+        let mut tx = pool.begin().await.unwrap();
+        ids.push(insert(&mut *tx, section_id, user_id, time).await);
+        txs.push((tx, section_id));
+    }
+    futures::future::join_all(txs.into_iter().map(|(mut tx, section_id)| async move {
+        recalculate_section(&mut tx, section_id).await.unwrap();
+        tx.commit().await.unwrap();
+    }))
+    .await;
+    ids
+}
+
+async fn recalculate(pool: &PgPool, section_id: i32) {
+    let mut tx = pool.begin().await.unwrap();
+    recalculate_section(&mut tx, section_id).await.unwrap();
+    tx.commit().await.unwrap();
+}
+
+/// `(is_pb, is_wr, points)` of a run.
+async fn run(pool: &PgPool, id: i32) -> (bool, bool, Option<f32>) {
+    sqlx::query_as("SELECT is_pb, is_wr, points FROM run WHERE id = $1;")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct Rank {
+    rank: i32,
+    title: String,
+    rating: f64,
+    percentage: f64,
+}
+
+/// Category rank of a user, overall rank if `overall`.
+async fn rank(pool: &PgPool, user_id: i64, overall: bool) -> Option<Rank> {
+    sqlx::query_as(
+        r#"SELECT rank, title::text, rating, percentage
+        FROM rank
+        WHERE user_id = $1 AND (layout IS NULL) = $2;"#,
+    )
+    .bind(user_id)
+    .bind(overall)
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+}
+
+fn assert_run(actual: (bool, bool, Option<f32>), pb: bool, wr: bool, points: Option<f32>) {
+    assert_eq!((actual.0, actual.1), (pb, wr), "pb and wr of {actual:?}");
+    match (actual.2, points) {
+        (Some(a), Some(e)) => assert!((a - e).abs() < 1e-5, "points {a} != {e}"),
+        (a, e) => assert_eq!(a, e),
+    }
+}
+
+fn assert_rank(actual: Option<Rank>, rank: i32, title: &str, rating: f64, percentage: f64) {
+    let actual = actual.expect("missing rank");
+    assert_eq!((actual.rank, actual.title.as_str()), (rank, title), "{actual:?}");
+    assert!(
+        (actual.rating - rating).abs() < 1e-2,
+        "rating {} != {rating}",
+        actual.rating
+    );
+    assert!((actual.percentage - percentage).abs() < 1e-9, "{actual:?}");
+}
+
+const RUNS: &str = r#"SELECT id, is_pb, is_wr, points FROM run ORDER BY id;"#;
+const RANKS: &str =
+    r#"SELECT user_id, layout, rank, title::text, rating, percentage FROM rank ORDER BY user_id, layout;"#;
+
+type RunRow = (i32, bool, bool, Option<f32>);
+type RankRow = (i64, Option<String>, i32, String, f64, f64);
+type Snapshot = (Vec<RunRow>, Vec<RankRow>);
+
+async fn snapshot(pool: &PgPool) -> Snapshot {
+    let runs = sqlx::query_as(RUNS).fetch_all(pool).await.unwrap();
+    let ranks = sqlx::query_as(RANKS).fetch_all(pool).await.unwrap();
+    (runs, ranks)
+}
+
+async fn recompute(pool: &PgPool, params: &RatingParams) {
+    let mut tx = pool.begin().await.unwrap();
+    recompute_rankings(&mut tx, params, "2.13", None, None).await.unwrap();
+    tx.commit().await.unwrap();
+}
+
+fn assert_snapshots_eq((runs_before, ranks_before): Snapshot, (runs_after, ranks_after): Snapshot) {
+    assert_eq!(runs_before, runs_after);
+    assert_eq!(ranks_before.len(), ranks_after.len());
+    for (before, after) in ranks_before.into_iter().zip(ranks_after) {
+        assert_eq!(
+            (&before.0, &before.1, before.2, &before.3),
+            (&after.0, &after.1, after.2, &after.3)
+        );
+        assert!((before.4 - after.4).abs() < 1e-6, "{before:?} != {after:?}");
+        assert!((before.5 - after.5).abs() < 1e-9, "{before:?} != {after:?}");
+    }
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn recompute_rankings_repairs_patch(pool: PgPool) {
+    let Setup { sections, users } = setup(&pool).await;
+    submit(&pool, sections[0], users[0], "10.000").await;
+    submit(&pool, sections[0], users[1], "12.000").await;
+    submit(&pool, sections[0], users[2], "15.000").await;
+    submit(&pool, sections[1], users[0], "20.000").await;
+    submit(&pool, sections[1], users[1], "19.000").await;
+    submit(&pool, sections[0], users[2], "11.000").await;
+    let before = snapshot(&pool).await;
+
+    sqlx::query("UPDATE run SET is_pb = NOT is_pb, is_wr = FALSE, points = NULL;")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE rank SET rank = 9, title = 'None', rating = 0.0;")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM rank WHERE user_id = $1;")
+        .bind(users[2])
+        .execute(&pool)
+        .await
+        .unwrap();
+    recompute(&pool, &RatingParams::default()).await;
+
+    assert_snapshots_eq(before, snapshot(&pool).await);
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn concurrent_submits_to_a_section(pool: PgPool) {
+    let Setup { sections, users } = setup(&pool).await;
+    let ids = submit_concurrently(
+        &pool,
+        &[
+            (sections[0], users[0], "12.000"),
+            (sections[0], users[1], "10.000"),
+            (sections[0], users[2], "11.000"),
+        ],
+    )
+    .await;
+
+    assert_run(run(&pool, ids[0]).await, true, false, Some(3.0 - 2.0 * 12.0 / 10.0));
+    assert_run(run(&pool, ids[1]).await, true, true, Some(1.0));
+    assert_run(run(&pool, ids[2]).await, true, false, Some(3.0 - 2.0 * 11.0 / 10.0));
+    let concurrent = snapshot(&pool).await;
+    recompute(&pool, &RatingParams::default()).await;
+    assert_snapshots_eq(concurrent, snapshot(&pool).await);
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn concurrent_submits_to_a_patch(pool: PgPool) {
+    let Setup { sections, users } = setup(&pool).await;
+    submit_concurrently(
+        &pool,
+        &[(sections[0], users[0], "10.000"), (sections[1], users[1], "20.000")],
+    )
+    .await;
+
+    // Same rating, the first to reach it ranks first
+    assert_eq!(rank(&pool, users[0], false).await.unwrap().rank, 1);
+    assert_eq!(rank(&pool, users[1], false).await.unwrap().rank, 2);
+    let concurrent = snapshot(&pool).await;
+    recompute(&pool, &RatingParams::default()).await;
+    assert_snapshots_eq(concurrent, snapshot(&pool).await);
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn recompute_rankings_applies_params(pool: PgPool) {
+    let Setup { sections, users } = setup(&pool).await;
+    submit(&pool, sections[0], users[0], "10.000").await;
+    submit(&pool, sections[0], users[1], "12.000").await;
+    submit(&pool, sections[0], users[2], "15.000").await;
+    assert_rank(rank(&pool, users[2], false).await, 3, "None", 0.0, 0.5);
+
+    let params = RatingParams {
+        base: 1000.0,
+        titles: vec![(Title::Surfer, 0.0)],
+        ..Default::default()
+    };
+    recompute(&pool, &params).await;
+
+    for overall in [false, true] {
+        assert_rank(
+            rank(&pool, users[0], overall).await,
+            1,
+            "TopOne",
+            7479.037121649297,
+            0.5,
+        );
+        assert_rank(
+            rank(&pool, users[1], overall).await,
+            2,
+            "Surfer",
+            503.7968921638309,
+            0.5,
+        );
+        assert_rank(rank(&pool, users[2], overall).await, 3, "Surfer", 0.0, 0.5);
+    }
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn deleting_wr_promotes_next_run(pool: PgPool) {
+    let Setup { sections, users } = setup(&pool).await;
+    let wr = submit(&pool, sections[0], users[0], "10.000").await;
+    let second = submit(&pool, sections[0], users[1], "12.000").await;
+    let third = submit(&pool, sections[0], users[2], "15.000").await;
+
+    sqlx::query("DELETE FROM run WHERE id = $1;")
+        .bind(wr)
+        .execute(&pool)
+        .await
+        .unwrap();
+    recalculate(&pool, sections[0]).await;
+
+    assert_run(run(&pool, second).await, true, true, Some(1.0));
+    assert_run(run(&pool, third).await, true, false, Some(0.5));
+    assert!(rank(&pool, users[0], false).await.is_none());
+    assert!(rank(&pool, users[0], true).await.is_none());
+    for overall in [false, true] {
+        assert_rank(
+            rank(&pool, users[1], overall).await,
+            1,
+            "TopOne",
+            8479.037121649297,
+            0.5,
+        );
+        assert_rank(rank(&pool, users[2], overall).await, 2, "None", 201.21456063556545, 0.5);
+    }
+
+    let moved = sqlx::query_scalar::<_, i64>(
+        r#"SELECT COUNT(id)
+        FROM activity
+        WHERE user_id = $1 AND rank_old = 3 AND rank_new = 2 AND created_at > now() - interval '1 minute';"#,
+    )
+    .bind(users[2])
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(moved, 2);
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn rejecting_pb_restores_previous_pb(pool: PgPool) {
+    let Setup { sections, users } = setup(&pool).await;
+    let old = submit(&pool, sections[0], users[0], "12.000").await;
+    let pb = submit(&pool, sections[0], users[0], "10.000").await;
+    let other = submit(&pool, sections[0], users[1], "11.000").await;
+    assert_run(run(&pool, old).await, false, false, None);
+    assert_run(run(&pool, other).await, true, false, Some(0.8));
+
+    sqlx::query("UPDATE run SET rejected = TRUE, reason = 'Cut' WHERE id = $1;")
+        .bind(pb)
+        .execute(&pool)
+        .await
+        .unwrap();
+    recalculate(&pool, sections[0]).await;
+
+    assert_run(run(&pool, pb).await, false, false, None);
+    assert_run(run(&pool, old).await, true, false, Some(3.0 - 2.0 * 12.0 / 11.0));
+    assert_run(run(&pool, other).await, true, true, Some(1.0));
+    assert_rank(rank(&pool, users[1], false).await, 1, "TopOne", 8479.037121649297, 0.5);
+    assert_rank(
+        rank(&pool, users[0], false).await,
+        2,
+        "SuperSurfer",
+        3059.366271869902,
+        0.5,
+    );
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn editing_time_moves_wr(pool: PgPool) {
+    let Setup { sections, users } = setup(&pool).await;
+    let edited = submit(&pool, sections[0], users[0], "10.000").await;
+    let other = submit(&pool, sections[0], users[1], "12.000").await;
+    submit(&pool, sections[1], users[1], "20.000").await;
+
+    sqlx::query("UPDATE run SET time = 13.000 WHERE id = $1;")
+        .bind(edited)
+        .execute(&pool)
+        .await
+        .unwrap();
+    recalculate(&pool, sections[0]).await;
+
+    assert_run(run(&pool, other).await, true, true, Some(1.0));
+    assert_run(run(&pool, edited).await, true, false, Some(3.0 - 2.0 * 13.0 / 12.0));
+    assert_rank(rank(&pool, users[1], false).await, 1, "TopOne", 10000.0, 1.0);
+    assert_rank(
+        rank(&pool, users[0], false).await,
+        2,
+        "SuperSurfer",
+        3365.593887777195,
+        0.5,
+    );
+}
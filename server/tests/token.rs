@@ -0,0 +1,77 @@
+//! Personal access tokens against a real database, run with `DATABASE_URL` set like
+//! the other database tests.
+#![cfg(feature = "ssr")]
+
+use leptos::prelude::{Owner, provide_context};
+use server::auth::ssr::{MIGRATOR, TokenAuth, hash_token, session_only, token_user};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use types::api::{ApiError, Permissions};
+
+/// A user with `permissions` and a token `lsl_test` scoped to `scopes`.
+async fn setup(pool: &PgPool, permissions: &[Permissions], scopes: &[Permissions]) -> (i64, i32) {
+    let user =
+        sqlx::query_scalar::<_, i64>(r#"INSERT INTO "user" (name, password) VALUES ('alpha', '') RETURNING id;"#)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+    sqlx::query("INSERT INTO permission (user_id, token) SELECT $1, UNNEST($2::permissions[]);")
+        .bind(user)
+        .bind(permissions)
+        .execute(pool)
+        .await
+        .unwrap();
+    let token = sqlx::query_scalar::<_, i32>(
+        "INSERT INTO api_token (user_id, name, hash, scopes) VALUES ($1, 'test', $2, $3) RETURNING id;",
+    )
+    .bind(user)
+    .bind(hash_token("lsl_test"))
+    .bind(scopes)
+    .fetch_one(pool)
+    .await
+    .unwrap();
+    (user, token)
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn scopes_intersect_with_permissions(pool: PgPool) {
+    use Permissions::*;
+    // The user lost Trusted after creating the token, and never had Verify
+    let (user, token) = setup(&pool, &[View, Submit], &[Submit, Trusted, Verify]).await;
+
+    let (authenticated, auth) = token_user(&pool, "lsl_test").await.unwrap();
+    assert_eq!(authenticated.id, user);
+    assert_eq!(auth.id, token);
+    assert_eq!(authenticated.permissions, HashSet::from([Submit]));
+
+    let used = sqlx::query_scalar::<_, bool>("SELECT last_used_at IS NOT NULL FROM api_token WHERE id = $1;")
+        .bind(token)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(used);
+}
+
+#[sqlx::test(migrator = "MIGRATOR")]
+async fn rejects_unknown_tokens_and_disabled_users(pool: PgPool) {
+    let (user, _) = setup(&pool, &[Permissions::View], &[Permissions::View]).await;
+
+    assert!(token_user(&pool, "lsl_other").await.is_none());
+    assert!(token_user(&pool, "lsl_test").await.is_some());
+    sqlx::query(r#"UPDATE "user" SET disabled_at = now() WHERE id = $1;"#)
+        .bind(user)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert!(token_user(&pool, "lsl_test").await.is_none());
+}
+
+#[test]
+fn session_only_refuses_tokens() {
+    let owner = Owner::new();
+    owner.with(|| {
+        assert!(session_only().is_ok());
+        provide_context(TokenAuth { id: 1 });
+        assert!(matches!(session_only(), Err(ApiError::Unauthorized)));
+    });
+}
@@ -1,4 +1,4 @@
-use components::{Header, ListElements};
+use components::{Header, ListElements, provide_live_events};
 use leptos::{either::*, prelude::*};
 use leptos_meta::*;
 use leptos_router::{
@@ -9,16 +9,25 @@ use leptos_router::{
     path,
 };
 use pages::{
-    Activity, ComboRanking, Dashboard, ErrorTemplate, FAQ, HomePage, Leaderboard, Login, ManageRuns, Map, Profile,
-    Register, Submit, Submits, UserRanking,
-    dash::{Avatar, Bio, DiscordList, Password, Username},
+    Activity, AuditLog, ComboRanking, Dashboard, ErrorTemplate, FAQ, HomePage, Leaderboard, Login, ManageRuns,
+    ManageUsers, Map, Profile, Queue, Recover, Register, Submit, Submits, UserRanking,
+    dash::{Avatar, Bio, Disable, DiscordList, Password, RecoveryCodes, Sessions, Tokens, Username},
     error_template::AppError,
     leaderboard::Section,
     ranking::RankingHeader,
-    user::Delete,
+    user::{Delete, Edit},
+};
+use server::{
+    api::get_patches,
+    auth::{
+        DisableAccount, Login, Logout, LogoutEverywhere, Recover, Register, UpdateBio, UpdateCreds, get_current_user,
+        update_pfp,
+    },
+};
+use types::{
+    api::{Patch, Permissions},
+    leptos::{PatchResource, UserResource},
 };
-use server::auth::{Login, Logout, Register, UpdateBio, UpdateCreds, get_current_user, update_pfp};
-use types::leptos::UserResource;
 use wasm_bindgen::{JsCast, prelude::Closure};
 use web_sys::FormData;
 
@@ -57,6 +66,9 @@ pub fn App() -> impl IntoView {
     let update = ServerAction::<UpdateCreds>::new();
     let update_bio = ServerAction::<UpdateBio>::new();
     let update_pfp = Action::new_local(|data: &FormData| update_pfp(data.clone().into()));
+    let disable = ServerAction::<DisableAccount>::new();
+    let recover = ServerAction::<Recover>::new();
+    let logout_all = ServerAction::<LogoutEverywhere>::new();
     let user = Resource::new(
         move || {
             (
@@ -66,20 +78,29 @@ pub fn App() -> impl IntoView {
                 update.version().get(),
                 update_bio.version().get(),
                 update_pfp.version().get(),
+                disable.version().get(),
+                recover.version().get(),
+                logout_all.version().get(),
             )
         },
         move |_| get_current_user(),
     );
+    let patches: PatchResource = OnceResource::new(get_patches());
 
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
     provide_context(user);
+    provide_context(patches);
+    provide_live_events();
     provide_context(register);
     provide_context(login);
     provide_context(logout);
     provide_context(update);
     provide_context(update_bio);
     provide_context(update_pfp);
+    provide_context(disable);
+    provide_context(recover);
+    provide_context(logout_all);
 
     Effect::new(|_| document().document_element().unwrap().set_class_name("dark"));
 
@@ -103,8 +124,8 @@ pub fn App() -> impl IntoView {
             <Header attr:id="main-nav">
                 <ListElements>
                     <A href="/home">"Home"</A>
-                    <A href="/leaderboard/2.13/1/standard">"Leaderboard"</A>
-                    <A href="/ranking/2.13/1">"Ranking"</A>
+                    <A href="/leaderboard">"Leaderboard"</A>
+                    <A href="/ranking">"Ranking"</A>
                     <a href="https://discord.com/invite/G9QBCDY" rel="external">
                         "Discord"
                     </a>
@@ -129,6 +150,9 @@ pub fn App() -> impl IntoView {
                                     )
                                 }
                                 Ok(user) => {
+                                    let verifier = user.permissions.contains(&Permissions::Verify);
+                                    let admin = user.permissions.contains(&Permissions::Administrator);
+                                    let manager = admin || user.permissions.contains(&Permissions::ManageUsers);
                                     Either::Right(
                                         view! {
                                             <ListElements>
@@ -153,6 +177,15 @@ pub fn App() -> impl IntoView {
                                                                 <A href="/user/@me/submit">"Submit"</A>
                                                                 <A href="/user/@me/dashboard">"Dashboard"</A>
                                                                 <A href="/user/@me/manage">"Manage Runs"</A>
+                                                                <Show when=move || verifier>
+                                                                    <A href="/mod/queue">"Verify Runs"</A>
+                                                                </Show>
+                                                                <Show when=move || manager>
+                                                                    <A href="/user/@me/manage/users">"Manage Users"</A>
+                                                                </Show>
+                                                                <Show when=move || admin>
+                                                                    <A href="/admin/audit">"Audit Log"</A>
+                                                                </Show>
                                                                 <button
                                                                     type="button"
                                                                     class="dropdown-title"
@@ -204,6 +237,7 @@ fn AppRouter() -> impl IntoView {
             <RankingRouter />
             <Route path=path!("register") view=Register />
             <Route path=path!("login") view=Login />
+            <Route path=path!("recover") view=Recover />
             <UserRouter />
         </Routes>
     }
@@ -225,7 +259,17 @@ fn UserRouter() -> impl MatchNestedRoutes + Clone {
             <Route path=path!("bio") view=Bio />
             <Route path=path!("avatar") view=Avatar />
             <Route path=path!("discord") view=DiscordList />
+            <Route path=path!("recovery") view=RecoveryCodes />
+            <Route path=path!("sessions") view=Sessions />
+            <Route path=path!("tokens") view=Tokens />
+            <Route path=path!("disable") view=Disable />
         </ProtectedParentRoute>
+        <ProtectedRoute
+            path=path!("user/@me/manage/users")
+            condition=move || user.get().map(|n| n.is_ok())
+            redirect_path=|| "/login?redirect=user/@me/manage/users"
+            view=ManageUsers
+        />
         <ProtectedParentRoute
             path=path!("user/@me/manage")
             condition=move || user.get().map(|n| n.is_ok())
@@ -234,6 +278,7 @@ fn UserRouter() -> impl MatchNestedRoutes + Clone {
         >
             <Route path=path!("") view=() />
             <Route path=path!(":id") view=Delete />
+            <Route path=path!(":id/edit") view=Edit />
         </ProtectedParentRoute>
         <ProtectedRoute
             path=path!("user/@me/submit")
@@ -241,6 +286,18 @@ fn UserRouter() -> impl MatchNestedRoutes + Clone {
             redirect_path=|| "/login?redirect=user/@me/submit"
             view=Submit
         />
+        <ProtectedRoute
+            path=path!("mod/queue")
+            condition=move || user.get().map(|n| n.is_ok())
+            redirect_path=|| "/login?redirect=mod/queue"
+            view=Queue
+        />
+        <ProtectedRoute
+            path=path!("admin/audit")
+            condition=move || user.get().map(|n| n.is_ok())
+            redirect_path=|| "/login?redirect=admin/audit"
+            view=AuditLog
+        />
     }
     .into_inner()
     .into_any_nested_route()
@@ -251,11 +308,7 @@ fn LeaderboardRouter() -> impl MatchNestedRoutes + Clone {
     view! {
         <Route
             path=path!("leaderboard")
-            view=move || {
-                let mut options = NavigateOptions::default();
-                options.replace = true;
-                view! { <Redirect path="2.13/1/standard" options /> }
-            }
+            view=|| view! { <CurrentPatchRedirect to=Patch::leaderboard_path /> }
         />
         <Route
             path=path!("leaderboard/:patch/:layout/:category")
@@ -264,34 +317,16 @@ fn LeaderboardRouter() -> impl MatchNestedRoutes + Clone {
                 let patch = Signal::derive(move || { params.read().get("patch").unwrap() });
                 let layout = Signal::derive(move || { params.read().get("layout").unwrap() });
                 let category = Signal::derive(move || { params.read().get("category").unwrap() });
-                let layouts = Signal::derive(move || match patch.get().as_str() {
-                    "1.00" => vec![("1".into(), "Layout 1".into()), ("2".into(), "Layout 2".into())],
-                    "1.41" => {
-                        vec![
-                            ("1".into(), "Layout 1".into()),
-                            ("2".into(), "Layout 2".into()),
-                            ("3".into(), "Layout 3".into()),
-                            ("4".into(), "Layout 4".into()),
-                        ]
-                    }
-                    "1.50" | "2.00" | "2.13" => {
-                        vec![
-                            ("1".into(), "Layout 1".into()),
-                            ("2".into(), "Layout 2".into()),
-                            ("3".into(), "Layout 3".into()),
-                            ("4".into(), "Layout 4".into()),
-                            ("5".into(), "Layout 5".into()),
-                        ]
-                    }
-                    _ => vec![],
+                let layouts = patch_layouts(patch);
+                let patch_categories = patch_categories(patch);
+                let categories = Signal::derive(move || {
+                    patch_categories.get().into_iter().map(|c| (c.to_lowercase(), c)).collect()
                 });
-                let categories: Vec<(String, String)> = vec![
-                    ("standard".into(), "Standard".into()),
-                    ("gravspeed".into(), "Gravspeed".into()),
-                ];
                 view! {
                     <section id="leaderboard">
-                        <Section layouts categories category />
+                        <Transition>
+                            <Section layouts categories category />
+                        </Transition>
                         <Leaderboard patch layout category />
                     </section>
                 }
@@ -333,36 +368,16 @@ fn LeaderboardRouter() -> impl MatchNestedRoutes + Clone {
                     let category = Signal::derive(move || {
                         params.read().get("category").unwrap()
                     });
-                    let layouts = Signal::derive(move || match patch.get().as_str() {
-                        "1.00" => {
-                            vec![("1".into(), "Layout 1".into()), ("2".into(), "Layout 2".into())]
-                        }
-                        "1.41" => {
-                            vec![
-                                ("1".into(), "Layout 1".into()),
-                                ("2".into(), "Layout 2".into()),
-                                ("3".into(), "Layout 3".into()),
-                                ("4".into(), "Layout 4".into()),
-                            ]
-                        }
-                        "1.50" | "2.00" | "2.13" => {
-                            vec![
-                                ("1".into(), "Layout 1".into()),
-                                ("2".into(), "Layout 2".into()),
-                                ("3".into(), "Layout 3".into()),
-                                ("4".into(), "Layout 4".into()),
-                                ("5".into(), "Layout 5".into()),
-                            ]
-                        }
-                        _ => vec![],
+                    let layouts = patch_layouts(patch);
+                    let patch_categories = patch_categories(patch);
+                    let categories = Signal::derive(move || {
+                        patch_categories.get().into_iter().map(|c| (c.to_lowercase(), c)).collect()
                     });
-                    let categories: Vec<(String, String)> = vec![
-                        ("standard".into(), "Standard".into()),
-                        ("gravspeed".into(), "Gravspeed".into()),
-                    ];
                     view! {
                         <section id="leaderboard">
-                            <Section layouts categories category />
+                            <Transition>
+                                <Section layouts categories category />
+                            </Transition>
                             <Leaderboard patch layout category />
                         </section>
                     }
@@ -389,23 +404,13 @@ fn LeaderboardRouter() -> impl MatchNestedRoutes + Clone {
                     let id = Signal::derive(move || {
                         params.read().get("id").unwrap().parse::<i64>().unwrap_or(0)
                     });
-                    let patches: Vec<(String, String)> = vec![
-                        ("1.00".into(), "Patch 1.00".into()),
-                        ("1.41".into(), "Patch 1.41".into()),
-                        ("1.50".into(), "Patch 1.50".into()),
-                        ("2.00".into(), "Patch 2.00".into()),
-                        ("2.13".into(), "Patch 2.13".into()),
-                    ];
+                    let patches = patch_links();
                     view! { <UserRanking id patches /> }
                 }
             />
             <Route
                 path=path!("leaderboard")
-                view=move || {
-                    let mut options = NavigateOptions::default();
-                    options.replace = true;
-                    view! { <Redirect path="2.13/1/standard" options /> }
-                }
+                view=|| view! { <CurrentPatchRedirect to=Patch::leaderboard_path /> }
             />
             <Route path=path!("") view=move || view! {} />
         </ParentRoute>
@@ -415,7 +420,65 @@ fn LeaderboardRouter() -> impl MatchNestedRoutes + Clone {
 }
 
 #[derive(Clone)]
-struct Patch(Signal<String>);
+struct PatchName(Signal<String>);
+
+/// Looks up a patch in the [`PatchResource`], `None` until the resource has loaded.
+fn find_patch(patches: PatchResource, patch: Signal<String>) -> Option<Patch> {
+    patches.get()?.ok()?.into_iter().find(|p| p.name == patch.get())
+}
+
+fn patch_layouts(patch: Signal<String>) -> Signal<Vec<(String, String)>> {
+    let patches = expect_context::<PatchResource>();
+    Signal::derive(move || {
+        find_patch(patches, patch)
+            .map(|p| {
+                p.layouts
+                    .into_iter()
+                    .map(|l| (l.clone(), format!("Layout {l}")))
+                    .collect()
+            })
+            .unwrap_or_default()
+    })
+}
+
+fn patch_categories(patch: Signal<String>) -> Signal<Vec<String>> {
+    let patches = expect_context::<PatchResource>();
+    Signal::derive(move || find_patch(patches, patch).map(|p| p.categories).unwrap_or_default())
+}
+
+fn patch_links() -> Signal<Vec<(String, String)>> {
+    let patches = expect_context::<PatchResource>();
+    Signal::derive(move || {
+        patches
+            .get()
+            .and_then(Result::ok)
+            .map(|ps| ps.into_iter().map(|p| (p.name, p.display_name)).collect())
+            .unwrap_or_default()
+    })
+}
+
+/// Redirects to a path of the current patch, relative to the matched route.
+#[component]
+fn CurrentPatchRedirect(to: fn(&Patch) -> String) -> impl IntoView {
+    let patches = expect_context::<PatchResource>();
+    view! {
+        <Suspense>
+            {move || {
+                patches
+                    .get()
+                    .and_then(Result::ok)
+                    .and_then(|ps| ps.into_iter().find(|p| p.current))
+                    .map(|p| {
+                        let options = NavigateOptions {
+                            replace: true,
+                            ..Default::default()
+                        };
+                        view! { <Redirect path=to(&p) options /> }
+                    })
+            }}
+        </Suspense>
+    }
+}
 
 #[component(transparent)]
 fn RankingRouter() -> impl MatchNestedRoutes + Clone {
@@ -423,16 +486,12 @@ fn RankingRouter() -> impl MatchNestedRoutes + Clone {
         <ParentRoute
             path=path!("/ranking")
             view=move || {
-                let patches: Vec<(String, String)> = vec![
-                    ("1.00".into(), "Patch 1.00".into()),
-                    ("1.41".into(), "Patch 1.41".into()),
-                    ("1.50".into(), "Patch 1.50".into()),
-                    ("2.00".into(), "Patch 2.00".into()),
-                    ("2.13".into(), "Patch 2.13".into()),
-                ];
+                let patches = patch_links();
                 view! {
                     <section id="ranking">
-                        <RankingHeader links=patches />
+                        <Transition>
+                            <RankingHeader links=patches />
+                        </Transition>
                         <Outlet />
                     </section>
                 }
@@ -440,44 +499,30 @@ fn RankingRouter() -> impl MatchNestedRoutes + Clone {
         >
             <Route
                 path=path!("")
-                view=|| view! { <p>"Please select a patch to view the rankings of."</p> }
+                view=|| {
+                    view! {
+                        <CurrentPatchRedirect to=|p: &Patch| {
+                            format!("{}/{}", p.name, p.layouts.first().map_or("1", |l| l.as_str()))
+                        } />
+                    }
+                }
             />
             <ParentRoute
                 path=path!(":patch")
                 view=move || {
                     let params = use_params_map();
                     let patch = Signal::derive(move || { params.get().get("patch").unwrap() });
-                    let layouts = Signal::derive(move || match patch.get().as_str() {
-                        "1.00" => {
-                            vec![("1".into(), "Layout 1".into()), ("2".into(), "Layout 2".into())]
-                        }
-                        "1.41" => {
-                            vec![
-                                ("1".into(), "Layout 1".into()),
-                                ("2".into(), "Layout 2".into()),
-                                ("3".into(), "Layout 3".into()),
-                                ("4".into(), "Layout 4".into()),
-                            ]
-                        }
-                        "1.50" | "2.00" | "2.13" => {
-                            vec![
-                                ("1".into(), "Layout 1".into()),
-                                ("2".into(), "Layout 2".into()),
-                                ("3".into(), "Layout 3".into()),
-                                ("4".into(), "Layout 4".into()),
-                                ("5".into(), "Layout 5".into()),
-                            ]
-                        }
-                        _ => vec![],
-                    });
-                    provide_context(Patch(patch));
+                    let layouts = patch_layouts(patch);
+                    provide_context(PatchName(patch));
                     view! {
                         <ComboRanking
                             patch=patch
                             layout=None
                             categories=vec![(None, "Combined".into())]
                         />
-                        <RankingHeader links=layouts />
+                        <Transition>
+                            <RankingHeader links=layouts />
+                        </Transition>
                         <Outlet />
                     }
                 }
@@ -486,25 +531,26 @@ fn RankingRouter() -> impl MatchNestedRoutes + Clone {
                     path=path!(":layout")
                     view=move || {
                         let params = use_params_map();
-                        let patch = expect_context::<Patch>().0;
+                        let patch = expect_context::<PatchName>().0;
                         let layout = Signal::derive(move || { params.read().get("layout") });
-                        let categories = Signal::derive(move || match patch.get().as_str() {
-                            "1.00" | "1.41" | "1.50" | "2.00" => {
-                                vec![
-                                    (Some("Standard".into()), "Standard".into()),
-                                    (Some("Gravspeed".into()), "Gravspeed".into()),
-                                    (None, "Combined".into()),
-                                ]
+                        let patch_categories = patch_categories(patch);
+                        let categories = Signal::derive(move || {
+                            let mut categories: Vec<_> = patch_categories
+                                .get()
+                                .into_iter()
+                                .map(|c| (Some(c.clone()), c))
+                                .collect();
+                            // Per layout combined rankings were dropped after 2.00
+                            if matches!(patch.get().as_str(), "1.00" | "1.41" | "1.50" | "2.00") {
+                                categories.push((None, "Combined".into()));
                             }
-                            "2.13" => {
-                                vec![
-                                    (Some("Standard".into()), "Standard".into()),
-                                    (Some("Gravspeed".into()), "Gravspeed".into()),
-                                ]
-                            }
-                            _ => vec![],
+                            categories
                         });
-                        view! { <ComboRanking patch layout categories /> }
+                        view! {
+                            <Transition>
+                                <ComboRanking patch layout categories />
+                            </Transition>
+                        }
                     }
                 />
                 <Route
@@ -3,35 +3,45 @@
 cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
 use axum::{
     body::Body as AxumBody,
-    extract::{State},
+    extract::{ConnectInfo, State},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, post},
     Router,
 };
 use axum_session::{SessionConfig, SessionLayer};
 use axum_session_auth::{AuthConfig, AuthSessionLayer};
 use axum_session_sqlx::{SessionPgPool, SessionPgSessionStore};
-use http::Request;
+use http::{Request, header::{AUTHORIZATION, USER_AGENT}};
 use leptos::prelude::*;
 use leptos_axum::{generate_route_list, handle_server_fns_with_context, LeptosRoutes};
 use lsl_website::{app::*, state::{AppState, oauth_client}};
 use sqlx::PgPool;
+use std::net::SocketAddr;
 use tower::ServiceBuilder;
-use server::auth::ssr::connect_to_database;
+use server::{
+    auth::ssr::{authenticate_token, connect_to_database, migrate_database, prune_sessions, track_session},
+    interactions::{self, Interactions},
+    live,
+    proof::ProofValidators,
+};
 use types::{leptos::AuthSession, api::User};
 
 async fn leptos_handler(
     state: State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     session: AuthSession,
     req: Request<AxumBody>,
 ) -> Response {
     let pool = state.pool.clone();
     let options = state.leptos_options.clone();
+    let user_agent = req.headers().get(USER_AGENT).and_then(|v| v.to_str().ok());
+    let session = track_session(session, &pool, user_agent).await;
     let handler = leptos_axum::render_route_with_context(
         state.routes.clone(),
         move || {
             provide_context(pool.clone());
             provide_context(session.clone());
+            provide_context(addr);
         },
         move || shell(options.clone()),
     );
@@ -40,14 +50,34 @@ async fn leptos_handler(
 
 async fn server_handler(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     session: AuthSession,
     request: Request<AxumBody>,
 ) -> impl IntoResponse {
+    // Bots and tools authenticate with a personal access token instead of the session cookie
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string());
+    let (session, token) = match bearer {
+        Some(bearer) => authenticate_token(session, &state.pool, &bearer).await,
+        None => {
+            let user_agent = request.headers().get(USER_AGENT).and_then(|v| v.to_str().ok());
+            (track_session(session, &state.pool, user_agent).await, None)
+        }
+    };
     handle_server_fns_with_context(
         move || {
             provide_context(state.pool.clone());
             provide_context(state.oauth.clone());
+            provide_context(state.proof.clone());
             provide_context(session.clone());
+            provide_context(addr);
+            if let Some(token) = token {
+                provide_context(token);
+            }
         },
         request,
     )
@@ -59,6 +89,8 @@ async fn main() {
     simple_logger::init_with_env().expect("couldn't initialize logging");
 
     let pool = connect_to_database().await;
+    migrate_database(&pool).await;
+    prune_sessions(pool.clone());
     let session_config = SessionConfig::default().with_table_name("session");
     let auth_config = AuthConfig::<i64>::default().with_session_id("user_id".to_string());
     let session_store = SessionPgSessionStore::new(Some(pool.clone().into()), session_config)
@@ -80,10 +112,25 @@ async fn main() {
         pool: pool.clone(),
         routes: routes.clone(),
         oauth: oauth_client(),
+        proof: ProofValidators::from_env(),
+        live: live::listen(pool.clone()),
+        interactions: Interactions::from_env(),
     };
 
+    // Slash commands are only offered when their endpoint is served
+    if let Some(interactions) = state.interactions.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = interactions.register().await {
+                log::error!("Failed to register Discord commands: {e}");
+            }
+        });
+    }
+
     // build our application with a route
     let app = Router::new()
+        .nest("/api/v1", server::rest::router())
+        .route("/api/live", get(live::events))
+        .route("/api/discord/interactions", post(interactions::interactions))
         .route("/api/{*fn_name}", get(server_handler).post(server_handler))
         .leptos_routes_with_handler(routes.clone(), get(leptos_handler))
         .layer(ServiceBuilder::new()
@@ -100,7 +147,7 @@ async fn main() {
     // `axum::Server` is a re-export of `hyper::Server`
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     log::info!("listening on http://{}", &addr);
-    axum::serve(listener, app.into_make_service())
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
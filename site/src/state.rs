@@ -6,6 +6,7 @@ use leptos_axum::AxumRouteListing;
 use oauth2::{
     basic::BasicClient, AuthUrl, ClientId, ClientSecret, RedirectUrl, RevocationUrl, TokenUrl,
 };
+use server::{interactions::Interactions, live::Live, proof::ProofValidators};
 use sqlx::PgPool;
 
 /// This takes advantage of Axum's SubStates feature by deriving FromRef. This is the only way to have more than one
@@ -16,6 +17,9 @@ pub struct AppState {
     pub pool: PgPool,
     pub routes: Vec<AxumRouteListing>,
     pub oauth: BasicClient,
+    pub proof: ProofValidators,
+    pub live: Live,
+    pub interactions: Option<Interactions>,
 }
 
 pub fn oauth_client() -> BasicClient {
@@ -24,18 +24,26 @@ pub enum ApiError {
     #[error("Invalid Credentials")]
     #[strum(to_string = "Invalid Credentials")]
     InvalidCredentials,
+    #[error("Password Required")]
+    #[strum(to_string = "Password Required")]
+    PasswordRequired,
     #[error("Invalid Section")]
     #[strum(to_string = "Invalid Section")]
     InvalidSection,
-    #[error("Invalid YouTube ID")]
-    #[strum(to_string = "Invalid YouTube ID")]
-    InvalidYtId,
+    #[error("Invalid Proof")]
+    #[strum(to_string = "Invalid Proof")]
+    InvalidProof,
     #[error("Already Exists")]
     #[strum(to_string = "Already Exists")]
     AlreadyExists,
     #[error("Not Found")]
     #[strum(to_string = "Not Found")]
     NotFound,
+    #[error("Edit Window Expired")]
+    #[strum(to_string = "Edit Window Expired")]
+    EditWindowExpired,
+    #[error("Too Many Attempts, retry in {0} seconds")]
+    TooManyAttempts(i64),
     #[error("Client Error: {0}")]
     ClientError(String),
     #[error("Server Error: {0}")]
@@ -60,7 +68,7 @@ impl FromServerFnError for ApiError {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Display, Serialize, Deserialize, Hash)]
-#[cfg_attr(feature = "ssr", derive(sqlx::Type), sqlx(type_name = "title"))]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type, utoipa::ToSchema), sqlx(type_name = "title"))]
 pub enum Title {
     #[strum(to_string = "No Title")]
     None = 0,
@@ -111,6 +119,16 @@ impl Default for RunFilters {
     }
 }
 
+/// Which runs of a leaderboard to list and in which order.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct LeaderboardFilters {
+    /// `none`, `is_pb`, `is_wr`, `was_pb`, `was_wr` or `verified`, current personal bests by default.
+    pub filter: Option<String>,
+    /// `time` or `date`, fastest first by default.
+    pub sort: Option<String>,
+    pub user: Option<i64>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct ActivityFilters {
     pub event: Option<String>,
@@ -141,7 +159,7 @@ impl Default for ActivityFilters {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow, utoipa::ToSchema))]
 pub struct Run {
     pub id: i32,
     pub section_id: i32,
@@ -155,14 +173,21 @@ pub struct Run {
     pub time: Decimal,
     pub proof: String,
     pub yt_id: Option<String>,
+    pub proof_host: ProofHost,
     pub verified: bool,
+    pub rejected: bool,
+    pub reason: Option<String>,
     pub is_pb: bool,
     pub is_wr: bool,
     pub created_at: DateTime<Local>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(sqlx::Type), derive(sqlx::FromRow), sqlx(no_pg_array))]
+#[cfg_attr(
+    feature = "ssr",
+    derive(sqlx::Type, sqlx::FromRow, utoipa::ToSchema),
+    sqlx(no_pg_array)
+)]
 pub struct PartialRun {
     pub id: i32,
     pub section_id: i32,
@@ -171,12 +196,52 @@ pub struct PartialRun {
     pub time: Decimal,
     pub proof: String,
     pub yt_id: Option<String>,
+    pub proof_host: ProofHost,
     pub verified: bool,
     pub is_pb: bool,
     pub is_wr: bool,
     pub created_at: DateTime<Local>,
 }
 
+/// Site a proof video is hosted on, `yt_id` holds the id of the video on that site.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, EnumString, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub enum ProofHost {
+    YouTube,
+    Twitch,
+    Streamable,
+    #[default]
+    Other,
+}
+
+// Stored as text rather than a postgres enum, records in array_agg can't hold custom types
+#[cfg(feature = "ssr")]
+impl sqlx::Type<sqlx::Postgres> for ProofHost {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <String as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+    fn compatible(ty: &sqlx::postgres::PgTypeInfo) -> bool {
+        <String as sqlx::Type<sqlx::Postgres>>::compatible(ty)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl<'r> sqlx::Decode<'r, sqlx::Postgres> for ProofHost {
+    fn decode(value: sqlx::postgres::PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(<&str as sqlx::Decode<sqlx::Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl sqlx::Encode<'_, sqlx::Postgres> for ProofHost {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <String as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(&self.to_string(), buf)
+    }
+}
+
 // WARNING: Absolutely horrid hack to make query_as function work with array_agg
 // Probably destroys type safety, make sure to always double check queries
 #[cfg(feature = "ssr")]
@@ -200,7 +265,7 @@ impl sqlx::postgres::PgHasArrayType for PartialRanking {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow, utoipa::ToSchema))]
 pub struct SectionRuns {
     pub id: i32,
     pub patch: String,
@@ -211,7 +276,7 @@ pub struct SectionRuns {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow, utoipa::ToSchema))]
 pub struct Ranking {
     pub id: i32,
     pub patch: String,
@@ -251,6 +316,29 @@ pub struct ComboRanking {
     pub rankings: Vec<PartialRanking>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow, utoipa::ToSchema))]
+pub struct Patch {
+    pub name: String,
+    pub display_name: String,
+    pub released_at: DateTime<Local>,
+    pub current: bool,
+    pub layouts: Vec<String>,
+    pub categories: Vec<String>,
+}
+
+impl Patch {
+    /// Relative path of the first leaderboard of the patch, `{patch}/{layout}/{category}`.
+    pub fn leaderboard_path(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.name,
+            self.layouts.first().map_or("1", |l| l.as_str()),
+            self.categories.first().map_or("standard".into(), |c| c.to_lowercase())
+        )
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
 pub struct Map {
@@ -260,7 +348,39 @@ pub struct Map {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow, utoipa::ToSchema))]
+pub struct Section {
+    pub id: i32,
+    pub patch: String,
+    pub layout: String,
+    pub category: String,
+    pub map: String,
+    pub code: String,
+}
+
+/// One page of a list. `next_cursor` and `prev_cursor` fetch the pages around it and are `None`
+/// on the last and first page, `total` counts the whole list when it was asked for.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+/// Which page of a list to fetch, `cursor` being `next_cursor` or `prev_cursor` of another page
+/// of the same list and `limit` the page size.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub total: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow, utoipa::ToSchema))]
 pub struct Activity {
     pub id: i32,
     pub user_id: i64,
@@ -277,8 +397,22 @@ pub struct Activity {
     pub created_at: DateTime<Local>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
-#[cfg_attr(feature = "ssr", derive(sqlx::Type), sqlx(type_name = "permissions"))]
+/// A change sent to open pages over the `/api/live` event stream as it happens.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum LiveEvent {
+    Submit(Run),
+    /// A run of the section was verified, rejected, edited or deleted.
+    Change(Section),
+    Activity(Activity),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Display, EnumString, Serialize, Deserialize, Hash)]
+#[cfg_attr(
+    feature = "ssr",
+    derive(sqlx::Type, utoipa::ToSchema),
+    sqlx(type_name = "permissions")
+)]
 pub enum Permissions {
     View,
     Submit,
@@ -290,7 +424,59 @@ pub enum Permissions {
     Administrator,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::Type), sqlx(type_name = "audit_action"))]
+pub enum AuditAction {
+    Register,
+    UpdateUsername,
+    UpdatePassword,
+    UpdateBio,
+    UpdateAvatar,
+    Submit,
+    Approve,
+    Reject,
+    Delete,
+    DiscordLink,
+    DiscordUnlink,
+    GrantPermission,
+    RevokePermission,
+    Disable,
+    GenerateRecoveryCodes,
+    Recover,
+    RevokeSessions,
+    Edit,
+    CreateToken,
+    RevokeToken,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor_id: Option<i64>,
+    #[cfg_attr(feature = "ssr", sqlx(rename = "name"))]
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    pub target: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Local>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct ManagedUser {
+    pub id: i64,
+    #[cfg_attr(feature = "ssr", sqlx(rename = "name"))]
+    pub username: String,
+    pub pfp: String,
+    pub created_at: DateTime<Local>,
+    pub permissions: Vec<Permissions>,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct User {
     pub id: i64,
     pub username: String,
@@ -301,7 +487,7 @@ pub struct User {
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow, utoipa::ToSchema))]
 pub struct Rank {
     pub patch: String,
     pub layout: Option<String>,
@@ -329,6 +515,28 @@ impl Default for User {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct SessionInfo {
+    pub id: i32,
+    pub user_agent: Option<String>,
+    pub remember: bool,
+    pub created_at: DateTime<Local>,
+    pub last_seen: DateTime<Local>,
+    pub current: bool,
+}
+
+/// A personal access token as listed on the dashboard, the token itself is only known to its owner.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
+pub struct ApiTokenInfo {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Permissions>,
+    pub created_at: DateTime<Local>,
+    pub last_used_at: Option<DateTime<Local>>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "ssr", derive(sqlx::FromRow))]
 pub struct Discord {
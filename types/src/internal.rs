@@ -1,6 +1,9 @@
+use crate::api::ProofHost;
+
 #[derive(Clone, PartialEq, Eq)]
 pub struct Proof {
     pub yt_id: Option<String>,
+    pub host: ProofHost,
     pub url: String,
 }
 
@@ -108,11 +111,23 @@ pub mod ssr {
         }
     }
 
+    /// Disabled accounts stay in the database for their runs, but must never be logged in.
+    pub async fn is_disabled(id: i64, pool: &PgPool) -> bool {
+        sqlx::query_scalar::<_, bool>(r#"SELECT disabled_at IS NOT NULL FROM "user" WHERE id = $1;"#)
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap_or(true)
+    }
+
     #[async_trait]
     impl Authentication<User, i64, PgPool> for User {
         async fn load_user(userid: i64, pool: Option<&PgPool>) -> Result<User, anyhow::Error> {
             let pool = pool.unwrap();
 
+            if is_disabled(userid, pool).await {
+                return Err(anyhow::anyhow!("User is disabled"));
+            }
             User::get(userid, pool)
                 .await
                 .ok_or_else(|| anyhow::anyhow!("Cannot get user"))
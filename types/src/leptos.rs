@@ -75,7 +75,9 @@ where
 }
 
 pub type UserResource = Resource<Result<User, ApiError>>;
+pub type PatchResource = OnceResource<Result<Vec<Patch>, ApiError>>;
 pub type UpdatePfpAction = Action<FormData, Result<(), ApiError>>;
+pub type LiveEvents = ReadSignal<Option<LiveEvent>>;
 
 #[cfg(feature = "ssr")]
 pub type AuthSession = axum_session_auth::AuthSession<User, i64, axum_session_sqlx::SessionPgPool, sqlx::PgPool>;
@@ -1,3 +1,4 @@
 pub mod api;
 pub mod internal;
 pub mod leptos;
+pub mod rating;
@@ -0,0 +1,166 @@
+use crate::api::Title;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::E;
+
+/// Constants of the rating formula and title thresholds. A rating is made of a base that
+/// grows with the percentage of submitted sections, raised to a power of the average
+/// points that shrinks with the percentage, so completing more of a leaderboard makes
+/// slower times count more.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RatingParams {
+    /// Base of a user that submitted to a single section.
+    pub base: f64,
+    /// Base added by submitting to every section.
+    pub completion: f64,
+    /// Curvature of the base, smaller values reward the first sections more.
+    pub completion_curve: f64,
+    /// Exponent of the average points with no sections submitted.
+    pub exponent: f64,
+    /// Exponent removed by submitting to every section.
+    pub exponent_range: f64,
+    /// Curvature of the exponent.
+    pub exponent_curve: f64,
+    /// How much more both terms weigh at low percentages.
+    pub falloff: f64,
+    /// Lowest rating of every title, in ascending order. `TopOne` goes to rank 1 instead.
+    pub titles: Vec<(Title, f64)>,
+}
+
+impl Default for RatingParams {
+    fn default() -> Self {
+        Self {
+            base: 2000.0,
+            completion: 8000.0,
+            completion_curve: 0.15,
+            exponent: 50.0,
+            exponent_range: 44.0,
+            exponent_curve: 0.01,
+            falloff: 0.25,
+            titles: vec![
+                (Title::Surfer, 1500.0),
+                (Title::SuperSurfer, 3000.0),
+                (Title::EpicSurfer, 5000.0),
+                (Title::LegendarySurfer, 7500.0),
+                (Title::MythicSurfer, 9000.0),
+            ],
+        }
+    }
+}
+
+/// Points of a pb with `time` on a section where the world record is `wr`. The record
+/// gets 1 point, runs 50% slower or more get none.
+pub fn points(time: f64, wr: f64) -> f64 {
+    (3.0 - 2.0 * time / wr).max(0.0)
+}
+
+/// Share of `ln(1 + x / curve)` at `x` compared to `x = 1`.
+fn curve(x: f64, curve: f64) -> f64 {
+    (1.0 + x / curve).ln() / (1.0 + 1.0 / curve).ln()
+}
+
+impl RatingParams {
+    /// Rating of a user that submitted to `percentage` of the sections of a leaderboard,
+    /// both in `0.0..=1.0`, with `points` average points on their pbs.
+    pub fn rating(&self, percentage: f64, points: f64) -> f64 {
+        let weight = 1.0 + self.falloff * (1.0 - percentage);
+        let base = self.base + self.completion * curve(percentage, self.completion_curve) * weight;
+        let exponent = self.exponent - self.exponent_range * curve(percentage, self.exponent_curve) * weight;
+        base * (points * (E - 1.0) + 1.0).ln().powf(exponent)
+    }
+
+    /// Title of the user on `rank` with `rating`.
+    pub fn title(&self, rank: i32, rating: f64) -> Title {
+        if rank == 1 {
+            return Title::TopOne;
+        }
+        self.titles
+            .iter()
+            .rev()
+            .find(|(_, min)| rating >= *min)
+            .map(|(title, _)| title.clone())
+            .unwrap_or(Title::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn points_of_record_and_slow_runs() {
+        assert_eq!(points(10.0, 10.0), 1.0);
+        assert!(close(points(12.0, 10.0), 0.6));
+        assert_eq!(points(15.0, 10.0), 0.0);
+        assert_eq!(points(30.0, 10.0), 0.0);
+    }
+
+    #[test]
+    fn rating_matches_previous_formula() {
+        let params = RatingParams::default();
+        assert!(close(params.rating(1.0, 1.0), 10000.0));
+        assert!(close(params.rating(0.5, 1.0), 8479.037121649297));
+        assert!(close(params.rating(0.5, 0.6), 571.1580890624942));
+        assert!(close(params.rating(0.25, 0.9), 2711.989060299219));
+        assert!(close(params.rating(0.5, 0.5), 201.21456063556545));
+        assert_eq!(params.rating(0.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn titles() {
+        let params = RatingParams::default();
+        assert_eq!(params.title(1, 0.0), Title::TopOne);
+        assert_eq!(params.title(2, 1499.9), Title::None);
+        assert_eq!(params.title(2, 1500.0), Title::Surfer);
+        assert_eq!(params.title(2, 3000.0), Title::SuperSurfer);
+        assert_eq!(params.title(2, 7499.0), Title::EpicSurfer);
+        assert_eq!(params.title(2, 8999.0), Title::LegendarySurfer);
+        assert_eq!(params.title(3, 10000.0), Title::MythicSurfer);
+    }
+
+    #[test]
+    fn params_fill_missing_fields() {
+        let params = serde_json::from_str::<RatingParams>(r#"{ "base": 1000.0 }"#).unwrap();
+        assert_eq!(params.base, 1000.0);
+        assert_eq!(params.titles, RatingParams::default().titles);
+    }
+
+    proptest! {
+        #[test]
+        fn points_between_zero_and_one(wr in 1.0..1000.0f64, slower in 0.0..1000.0f64) {
+            let p = points(wr + slower, wr);
+            prop_assert!((0.0..=1.0).contains(&p));
+        }
+
+        #[test]
+        fn points_drop_with_time(wr in 1.0..1000.0f64, a in 0.0..1000.0f64, b in 0.0..1000.0f64) {
+            let (fast, slow) = if a < b { (a, b) } else { (b, a) };
+            prop_assert!(points(wr + fast, wr) >= points(wr + slow, wr));
+        }
+
+        #[test]
+        fn rating_bounded(percentage in 0.0..=1.0f64, points in 0.0..=1.0f64) {
+            let rating = RatingParams::default().rating(percentage, points);
+            prop_assert!((0.0..=10000.0 + 1e-6).contains(&rating));
+        }
+
+        #[test]
+        fn rating_grows_with_points(percentage in 0.0..=1.0f64, a in 0.0..=1.0f64, b in 0.0..=1.0f64) {
+            let params = RatingParams::default();
+            let (low, high) = if a < b { (a, b) } else { (b, a) };
+            prop_assert!(params.rating(percentage, low) <= params.rating(percentage, high));
+        }
+
+        #[test]
+        fn title_grows_with_rating(rank in 2..1000i32, a in 0.0..12000.0f64, b in 0.0..12000.0f64) {
+            let params = RatingParams::default();
+            let (low, high) = if a < b { (a, b) } else { (b, a) };
+            prop_assert!(params.title(rank, low) <= params.title(rank, high));
+        }
+    }
+}